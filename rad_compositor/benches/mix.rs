@@ -0,0 +1,145 @@
+// Compares the throughput of the block based mixing in `compute_frames` with mixing every frame of every source on its own,
+// which is how the compositor used to work.
+//
+// Run with `cargo bench -p rad_compositor --bench mix`.
+
+use std::{f32::consts::PI, hint::black_box, io::Write, path::PathBuf, time::Instant};
+
+use rad_compositor::{
+	channel_layout::{ChannelLayout, MixMatrix},
+	composition::{convert_sample_rates, CompositionSrc, CompositionState},
+	compositor::{compute_frames, MixState},
+	resampler::ResampleQuality,
+	source::{formatted::FormattedStreamSource, BaseSource, TFrameIdx, TSample}
+};
+
+const BUF_SIZE: usize = 1024;
+const COMPOSITOR_SAMPLE_RATE: TFrameIdx = 48000;
+const SOURCES: usize = 8;
+const SOURCE_SECS: f32 = 3.0;
+const ROUNDS: usize = 5;
+
+/// Writes a stereo 16-bit WAV file with a different tone on each channel.
+fn write_wav(path: &PathBuf, sample_rate: u32, secs: f32) {
+	let frames = (sample_rate as f32 * secs) as u32;
+	let data_len = frames * 4;
+
+	let mut bytes = Vec::with_capacity(44 + data_len as usize);
+	bytes.extend_from_slice(b"RIFF");
+	bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+	bytes.extend_from_slice(b"WAVEfmt ");
+	bytes.extend_from_slice(&16u32.to_le_bytes());
+	bytes.extend_from_slice(&1u16.to_le_bytes());
+	bytes.extend_from_slice(&2u16.to_le_bytes());
+	bytes.extend_from_slice(&sample_rate.to_le_bytes());
+	bytes.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+	bytes.extend_from_slice(&4u16.to_le_bytes());
+	bytes.extend_from_slice(&16u16.to_le_bytes());
+	bytes.extend_from_slice(b"data");
+	bytes.extend_from_slice(&data_len.to_le_bytes());
+
+	for i in 0..frames {
+		let t = i as f32 / sample_rate as f32;
+		for freq in [440.0, 660.0] {
+			let v = ((2.0 * PI * freq * t).sin() * 0.25 * i16::MAX as f32) as i16;
+			bytes.extend_from_slice(&v.to_le_bytes());
+		}
+	}
+
+	std::fs::File::create(path).unwrap().write_all(&bytes).unwrap();
+}
+
+fn make_composition(sample_rate: u32) -> CompositionState {
+	let path = std::env::temp_dir().join(format!("rad-bench-mix-{}.wav", sample_rate));
+	write_wav(&path, sample_rate, SOURCE_SECS);
+
+	let mut cmp = CompositionState::new("bench".to_string(), 1.0, ChannelLayout::Stereo);
+	for _ in 0..SOURCES {
+		let src = FormattedStreamSource::open_path(path.clone()).unwrap_or_else(|err| panic!("failed to open the generated wav file: {}", err));
+		cmp.push_src_default(src.into());
+	}
+
+	cmp
+}
+
+/// The mixing from before block mixing: every frame of every source is fetched and mixed on its own.
+fn compute_frames_per_frame(sources: &mut [CompositionSrc], offset: TFrameIdx) -> [f32; BUF_SIZE] {
+	let mut res = [0.0; BUF_SIZE];
+	let matrix = MixMatrix::identity(2);
+
+	for i in 0..BUF_SIZE / 2 {
+		let frame_idx = offset + i as TFrameIdx;
+		let mut frame = vec![0f32; 2];
+
+		for cmp_src in sources.iter_mut() {
+			let src_sample_rate = cmp_src.src.sample_rate();
+
+			let val = if src_sample_rate == COMPOSITOR_SAMPLE_RATE {
+				cmp_src.src.get_by_frame_i(frame_idx)
+			} else {
+				// Linear interpolation between the two nearest source frames
+				let pos = frame_idx as f64 * src_sample_rate as f64 / COMPOSITOR_SAMPLE_RATE as f64;
+				let idx = convert_sample_rates(COMPOSITOR_SAMPLE_RATE, frame_idx, src_sample_rate);
+				let frac = (pos - idx as f64) as TSample;
+
+				match (cmp_src.src.get_by_frame_i(idx), cmp_src.src.get_by_frame_i(idx + 1)) {
+					(Some(a), Some(b)) => Some(a.iter().zip(b.iter()).map(|(a, b)| a + (b - a) * frac).collect()),
+					(a, _) => a
+				}
+			};
+
+			if let Some(v) = val {
+				matrix.apply_add(&v, &mut frame, cmp_src.composition_data.amplification);
+			}
+		}
+
+		res[i * 2..i * 2 + 2].copy_from_slice(&frame);
+	}
+
+	res
+}
+
+/// Runs `compute` over the whole length of the sources a few times and returns the nanoseconds it took per output frame.
+fn measure(mut compute: impl FnMut(TFrameIdx) -> [f32; BUF_SIZE]) -> f64 {
+	let frames_per_buf = (BUF_SIZE / 2) as TFrameIdx;
+	// Staying a bit before the end so that every buffer has audio from every source
+	let bufs = (COMPOSITOR_SAMPLE_RATE as f32 * SOURCE_SECS) as TFrameIdx / frames_per_buf - 1;
+
+	// Warming up, which also decodes everything into the segment store so that only the mixing is measured.
+	for buf_i in 0..bufs {
+		black_box(compute(buf_i * frames_per_buf));
+	}
+
+	let start = Instant::now();
+	for _ in 0..ROUNDS {
+		for buf_i in 0..bufs {
+			black_box(compute(buf_i * frames_per_buf));
+		}
+	}
+
+	start.elapsed().as_nanos() as f64 / (ROUNDS as TFrameIdx * bufs * frames_per_buf) as f64
+}
+
+fn bench(name: &str, src_sample_rate: u32) {
+	let mut cmp = make_composition(src_sample_rate);
+	let per_frame = measure(|offset| compute_frames_per_frame(cmp.sources_mut(), offset));
+
+	let mut cmp = make_composition(src_sample_rate);
+	let mut mix = MixState::new(COMPOSITOR_SAMPLE_RATE, ChannelLayout::Stereo, 1.0, 20, ResampleQuality::Linear);
+	let block = measure(|offset| {
+		let mut res = [0.0; BUF_SIZE];
+		compute_frames(cmp.sources_mut(), &mut mix, offset, &mut res);
+
+		res
+	});
+
+	println!(
+		"{:<40} per-frame: {:>8.1} ns/frame   block: {:>8.1} ns/frame   speedup: {:.2}x",
+		name, per_frame, block, per_frame / block
+	);
+}
+
+fn main() {
+	bench(&format!("{} stereo sources, same sample-rate", SOURCES), COMPOSITOR_SAMPLE_RATE as u32);
+	bench(&format!("{} stereo sources, 44.1kHz (linear)", SOURCES), 44100);
+}
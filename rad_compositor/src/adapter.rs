@@ -2,6 +2,8 @@
 
 use std::sync::{atomic::AtomicBool, Arc, Mutex};
 
+use crate::event::{Event, GLOBAL_EVENT_BUS};
+
 #[derive(Clone)]
 pub struct AdapterHandle {
     id: String,
@@ -26,7 +28,11 @@ impl AdapterHandle {
 
 	pub fn close(&mut self) {
 		use std::sync::atomic::Ordering;
-		self.is_closed.store(true, Ordering::Relaxed);
+
+		// The handles of an adapter share its state, so only the first one to close it announces it
+		if !self.is_closed.swap(true, Ordering::Relaxed) {
+			GLOBAL_EVENT_BUS.publish(Event::AdapterClosed { adapter_id: self.id.clone() });
+		}
 	}
 
 	pub fn id(&self) -> &str {
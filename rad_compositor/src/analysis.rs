@@ -0,0 +1,4 @@
+// Analyses of audio for the front-ends, so that they can draw analyzers and scrubbers without decoding the audio themselves.
+
+pub mod spectrum;
+pub mod waveform;
@@ -0,0 +1,109 @@
+// Snapshots of the spectrum of the output of a compositor, taken with a Hann windowed FFT of its latest frames.
+
+use std::{f64::consts::PI, time::Duration};
+
+use crate::{compositor::{level::LEVEL_FLOOR_DB, BufferCursor}, source::{TFrameIdx, TSample}};
+
+pub const MIN_SPECTRUM_SIZE: usize = 64;
+pub const MAX_SPECTRUM_SIZE: usize = 32768;
+
+/// The magnitudes of the frequencies in a stretch of audio.
+#[derive(Clone, Debug)]
+pub struct Spectrum {
+	pub sample_rate: TFrameIdx,
+	/// The number of frames analysed, the spectrum has `size / 2 + 1` bins.
+	pub size: usize,
+	/// The magnitude of each bin in dBFS (a full scale sine reads 0 dB), from 0 Hz up to half the sample-rate.
+	pub magnitudes_db: Vec<f32>
+}
+
+impl Spectrum {
+	/// The distance between the frequencies of the bins in Hz.
+	pub fn bin_hz(&self) -> f32 {
+		self.sample_rate as f32 / self.size as f32
+	}
+}
+
+/// Whether `size` frames can be analysed, which has to be a power of two for the FFT.
+pub fn is_valid_size(size: usize) -> bool {
+	size.is_power_of_two() && (MIN_SPECTRUM_SIZE..=MAX_SPECTRUM_SIZE).contains(&size)
+}
+
+/// An in-place iterative radix-2 FFT, the length must be a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+	let n = re.len();
+
+	let mut j = 0;
+	for i in 1..n {
+		let mut bit = n >> 1;
+		while j & bit != 0 {
+			j ^= bit;
+			bit >>= 1;
+		}
+		j |= bit;
+
+		if i < j {
+			re.swap(i, j);
+			im.swap(i, j);
+		}
+	}
+
+	let mut len = 2;
+	while len <= n {
+		let angle = -2.0 * PI / len as f64;
+		for start in (0..n).step_by(len) {
+			for k in 0..len / 2 {
+				let (w_im, w_re) = (angle * k as f64).sin_cos();
+				let (a, b) = (start + k, start + k + len / 2);
+
+				let t_re = re[b] * w_re - im[b] * w_im;
+				let t_im = re[b] * w_im + im[b] * w_re;
+				re[b] = re[a] - t_re;
+				im[b] = im[a] - t_im;
+				re[a] += t_re;
+				im[a] += t_im;
+			}
+		}
+
+		len <<= 1;
+	}
+}
+
+/// The spectrum of a block of interleaved frames (with its channels mixed down), which has to hold a valid size of frames.
+pub fn analyze(block: &[TSample], channels: usize, sample_rate: TFrameIdx) -> Spectrum {
+	let size = block.len() / channels;
+	assert!(is_valid_size(size));
+
+	let window = |i: usize| 0.5 - 0.5 * (2.0 * PI * i as f64 / size as f64).cos();
+	let mut re = block.chunks_exact(channels)
+		.enumerate()
+		.map(|(i, frame)| window(i) * frame.iter().map(|v| *v as f64).sum::<f64>() / channels as f64)
+		.collect::<Vec<_>>();
+	let mut im = vec![0.0; size];
+
+	fft(&mut re, &mut im);
+
+	// Compensates for the window and for the half of the energy that's in the negative frequencies
+	let scale = 2.0 / (0..size).map(window).sum::<f64>();
+	let magnitudes_db = (0..=size / 2)
+		.map(|i| (20.0 * ((re[i] * re[i] + im[i] * im[i]).sqrt() * scale).log10()).max(LEVEL_FLOOR_DB as f64) as f32)
+		.collect();
+
+	Spectrum { sample_rate, size, magnitudes_db }
+}
+
+/// Reads `size` frames from the buffers computed after the latest one and analyses them,
+/// or gives up if the compositor doesn't compute a buffer within `timeout` (e.g. while its composition is paused).
+pub fn capture(cursor: &mut dyn BufferCursor, channels: usize, sample_rate: TFrameIdx, size: usize, timeout: Duration) -> Option<Spectrum> {
+	let mut block = Vec::with_capacity(size * channels);
+
+	cursor.skip_to_head();
+	while block.len() < size * channels {
+		let buf = cursor.next_buf(timeout)?;
+		let n = buf.len().min(size * channels - block.len());
+
+		block.extend_from_slice(&buf[..n]);
+	}
+
+	Some(analyze(&block, channels, sample_rate))
+}
@@ -0,0 +1,167 @@
+// Overviews of the waveforms of files (the lowest and the highest sample of every few frames), for drawing scrubbers.
+//
+// The overview of a file is made by decoding all of it on a background thread the same way its loudness is measured,
+// but only once it's first asked for, as most files are played without ever being drawn.
+
+use std::{fs::File, path::{Path, PathBuf}, sync::{mpsc::{channel, Sender}, Arc, LazyLock, Mutex, OnceLock}, thread};
+
+use symphonia::core::{io::{MediaSource, MediaSourceStream}, probe::Hint};
+
+use crate::source::{utils::{decode::decode_all, hint::hint_of_path}, TFrameIdx, TSample};
+
+/// The frames summed up by each bucket of an overview, which keeps the overview of an hour at 48 kHz under 3 MB.
+const BUCKET_FRAMES: usize = 512;
+
+/// The lowest and the highest sample of every `BUCKET_FRAMES` frames of a track, with its channels taken together.
+#[derive(Debug)]
+pub struct WaveformOverview {
+	pub sample_rate: TFrameIdx,
+	/// The number of frames of the track.
+	pub frames: TFrameIdx,
+	buckets: Vec<(TSample, TSample)>
+}
+
+impl WaveformOverview {
+	pub fn duration_secs(&self) -> f64 {
+		self.frames as f64 / self.sample_rate as f64
+	}
+
+	/// The lowest and the highest sample of each of `width` equal parts of the track (e.g. a part for each pixel of a scrubber).
+	/// Tracks shorter than `width` buckets give as many parts as they have buckets.
+	pub fn peaks(&self, width: usize) -> Vec<(TSample, TSample)> {
+		let width = width.min(self.buckets.len());
+
+		(0..width).map(|i| {
+			let range = i * self.buckets.len() / width..(i + 1) * self.buckets.len() / width;
+
+			let peaks = self.buckets[range].iter().fold(EMPTY_PEAKS, |(min, max), (b_min, b_max)| (b_min.min(min), b_max.max(max)));
+			settle_peaks(peaks)
+		}).collect()
+	}
+}
+
+/// The peaks of no samples at all, which every sample is lower and higher than.
+const EMPTY_PEAKS: (TSample, TSample) = (TSample::INFINITY, TSample::NEG_INFINITY);
+
+/// Peaks that are still `EMPTY_PEAKS` (e.g. as no samples were taken into them) are silent.
+fn settle_peaks(peaks: (TSample, TSample)) -> (TSample, TSample) {
+	if peaks.0 <= peaks.1 { peaks } else { (0.0, 0.0) }
+}
+
+/// Builds the overview of the blocks pushed into it.
+struct OverviewBuilder {
+	sample_rate: TFrameIdx,
+	frames: TFrameIdx,
+	buckets: Vec<(TSample, TSample)>,
+	/// The bucket being filled and the frames in it.
+	bucket: (TSample, TSample),
+	bucket_filled: usize
+}
+
+impl OverviewBuilder {
+	fn new(sample_rate: TFrameIdx) -> Self {
+		OverviewBuilder { sample_rate, frames: 0, buckets: Vec::new(), bucket: EMPTY_PEAKS, bucket_filled: 0 }
+	}
+
+	fn push(&mut self, block: &[TSample], channels: usize) {
+		for frame in block.chunks_exact(channels) {
+			for v in frame {
+				self.bucket = (self.bucket.0.min(*v), self.bucket.1.max(*v));
+			}
+
+			self.bucket_filled += 1;
+			if self.bucket_filled == BUCKET_FRAMES {
+				self.buckets.push(settle_peaks(std::mem::replace(&mut self.bucket, EMPTY_PEAKS)));
+				self.bucket_filled = 0;
+			}
+		}
+
+		self.frames += (block.len() / channels) as TFrameIdx;
+	}
+
+	fn build(mut self) -> WaveformOverview {
+		if self.bucket_filled != 0 {
+			self.buckets.push(settle_peaks(self.bucket));
+		}
+
+		WaveformOverview { sample_rate: self.sample_rate, frames: self.frames, buckets: self.buckets }
+	}
+}
+
+/// Makes the overview of a stream by decoding all of it.
+pub fn overview_of_stream(stream: Box<dyn MediaSource>, hint: &Hint) -> Option<WaveformOverview> {
+	let mut builder: Option<OverviewBuilder> = None;
+	decode_all(MediaSourceStream::new(stream, Default::default()), hint, |sample_rate, layout, block| {
+		builder.get_or_insert_with(|| OverviewBuilder::new(sample_rate)).push(block, layout.channels() as usize);
+	})?;
+
+	Some(builder?.build())
+}
+
+/// The overview of a track which gets filled in once it's made.
+#[derive(Clone, Debug, Default)]
+pub struct OverviewHandle {
+	overview: Arc<OnceLock<Arc<WaveformOverview>>>,
+	/// The file whose overview is made once it's first asked for, taken when it's handed to the background thread.
+	path: Arc<Mutex<Option<PathBuf>>>
+}
+
+impl OverviewHandle {
+	/// The overview once it has been made. The first call starts making it if it's made lazily (see `overview_of_path`).
+	pub fn get(&self) -> Option<Arc<WaveformOverview>> {
+		if let Some(overview) = self.overview.get() {
+			return Some(overview.clone());
+		}
+
+		if let Some(path) = self.path.lock().unwrap().take() {
+			let _ = OVERVIEW_QUEUE.lock().unwrap().send((path, self.clone()));
+		}
+
+		None
+	}
+
+	fn set(&self, waveform: WaveformOverview) {
+		let _ = self.overview.set(Arc::new(waveform));
+	}
+}
+
+/// The queue of files waiting for their overviews to be made by the background thread.
+static OVERVIEW_QUEUE: LazyLock<Mutex<Sender<(PathBuf, OverviewHandle)>>> = LazyLock::new(|| {
+	let (sender, receiver) = channel::<(PathBuf, OverviewHandle)>();
+
+	thread::Builder::new()
+		.name("waveform".into())
+		.spawn(move || {
+			for (path, handle) in receiver {
+				match File::open(&path).ok().and_then(|file| overview_of_stream(Box::new(file), &hint_of_path(&path))) {
+					Some(waveform) => handle.set(waveform),
+					None => log::warn!("Failed to make the waveform overview of '{}'.", path.display())
+				}
+			}
+		}).unwrap();
+
+	Mutex::new(sender)
+});
+
+/// Makes the overview of the file at `path` in the background once it's first asked for through the handle.
+pub fn overview_of_path(path: &Path) -> OverviewHandle {
+	OverviewHandle { path: Arc::new(Mutex::new(Some(path.to_path_buf()))), ..Default::default() }
+}
+
+/// Makes the overview of a stream that's still being received (e.g. an upload) on a thread of its own,
+/// as reading it waits for the rest of the stream to arrive.
+pub fn overview_of_incoming_stream(stream: Box<dyn MediaSource>, hint: Hint) -> OverviewHandle {
+	let handle = OverviewHandle::default();
+	let _handle = handle.clone();
+
+	thread::Builder::new()
+		.name("waveform-stream".into())
+		.spawn(move || {
+			match overview_of_stream(stream, &hint) {
+				Some(waveform) => _handle.set(waveform),
+				None => log::warn!("Failed to make the waveform overview of a stream.")
+			}
+		}).unwrap();
+
+	handle
+}
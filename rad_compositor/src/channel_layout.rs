@@ -0,0 +1,272 @@
+// Channel layouts of sources and compositions and the matrices used for mixing one layout into another.
+
+use std::{fmt::Display, str::FromStr};
+
+use symphonia::core::audio::Channels;
+
+use crate::source::TSample;
+
+/// -3dB, used when a channel is spread over two speakers or folded into another one.
+const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Speaker {
+	FrontLeft,
+	FrontRight,
+	FrontCenter,
+	Lfe,
+	RearLeft,
+	RearRight,
+	SideLeft,
+	SideRight
+}
+
+/// The arrangement of the channels in each frame.
+///
+/// The channels are ordered the same way as WAV files and symphonia order them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelLayout {
+	Mono,
+	Stereo,
+	/// Stereo with a low frequency channel.
+	TwoPointOne,
+	Quad,
+	FivePointOne,
+	SevenPointOne,
+	/// Channels without any known speaker positions.
+	Discrete(u8)
+}
+
+impl ChannelLayout {
+	/// Picks the standard layout with the given number of channels.
+	pub fn from_channels(channels: u8) -> Self {
+		match channels {
+			1 => Self::Mono,
+			2 => Self::Stereo,
+			3 => Self::TwoPointOne,
+			4 => Self::Quad,
+			6 => Self::FivePointOne,
+			8 => Self::SevenPointOne,
+			n => Self::Discrete(n)
+		}
+	}
+
+	/// Finds the layout matching the channels reported by symphonia, falling back to the number of channels.
+	pub fn from_symphonia(channels: Channels) -> Self {
+		const FRONT: Channels = Channels::FRONT_LEFT.union(Channels::FRONT_RIGHT);
+		const TWO_POINT_ONE: Channels = FRONT.union(Channels::LFE1);
+		const QUAD: Channels = FRONT.union(Channels::REAR_LEFT).union(Channels::REAR_RIGHT);
+		const FIVE_POINT_ONE: Channels = QUAD.union(Channels::FRONT_CENTRE).union(Channels::LFE1);
+		const FIVE_POINT_ONE_SIDE: Channels = FRONT.union(Channels::FRONT_CENTRE).union(Channels::LFE1).union(Channels::SIDE_LEFT).union(Channels::SIDE_RIGHT);
+		const SEVEN_POINT_ONE: Channels = FIVE_POINT_ONE.union(Channels::SIDE_LEFT).union(Channels::SIDE_RIGHT);
+
+		match channels {
+			Channels::FRONT_CENTRE | Channels::FRONT_LEFT => Self::Mono,
+			FRONT => Self::Stereo,
+			TWO_POINT_ONE => Self::TwoPointOne,
+			QUAD => Self::Quad,
+			FIVE_POINT_ONE | FIVE_POINT_ONE_SIDE => Self::FivePointOne,
+			SEVEN_POINT_ONE => Self::SevenPointOne,
+			other => Self::from_channels(other.count() as u8)
+		}
+	}
+
+	pub fn channels(&self) -> u8 {
+		match self {
+			Self::Discrete(n) => *n,
+			layout => layout.speakers().len() as u8
+		}
+	}
+
+	/// The speaker each channel is meant for, empty for discrete layouts.
+	pub fn speakers(&self) -> &'static [Speaker] {
+		use Speaker::*;
+
+		match self {
+			Self::Mono => &[FrontCenter],
+			Self::Stereo => &[FrontLeft, FrontRight],
+			Self::TwoPointOne => &[FrontLeft, FrontRight, Lfe],
+			Self::Quad => &[FrontLeft, FrontRight, RearLeft, RearRight],
+			Self::FivePointOne => &[FrontLeft, FrontRight, FrontCenter, Lfe, RearLeft, RearRight],
+			Self::SevenPointOne => &[FrontLeft, FrontRight, FrontCenter, Lfe, RearLeft, RearRight, SideLeft, SideRight],
+			Self::Discrete(_) => &[]
+		}
+	}
+}
+
+impl Display for ChannelLayout {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Mono => write!(f, "mono"),
+			Self::Stereo => write!(f, "stereo"),
+			Self::TwoPointOne => write!(f, "2.1"),
+			Self::Quad => write!(f, "quad"),
+			Self::FivePointOne => write!(f, "5.1"),
+			Self::SevenPointOne => write!(f, "7.1"),
+			Self::Discrete(n) => write!(f, "discrete-{}", n)
+		}
+	}
+}
+
+impl FromStr for ChannelLayout {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"mono" => Ok(Self::Mono),
+			"stereo" => Ok(Self::Stereo),
+			"2.1" => Ok(Self::TwoPointOne),
+			"quad" => Ok(Self::Quad),
+			"5.1" => Ok(Self::FivePointOne),
+			"7.1" => Ok(Self::SevenPointOne),
+			other => {
+				let n = other.strip_prefix("discrete-").ok_or(())?.parse::<u8>().map_err(|_| ())?;
+				if n == 0 { return Err(()); }
+
+				Ok(Self::Discrete(n))
+			}
+		}
+	}
+}
+
+/// Finds where a speaker's signal goes in a layout that may not have that speaker.
+fn route(speaker: Speaker, out: &[Speaker]) -> Vec<(usize, f32)> {
+	use Speaker::*;
+
+	let idx_of = |sp: Speaker| out.iter().position(|s| *s == sp);
+	let scaled = |routes: Vec<(usize, f32)>, amp: f32| routes.into_iter().map(|(i, v)| (i, v * amp)).collect();
+
+	if let Some(i) = idx_of(speaker) {
+		return vec![(i, 1.0)];
+	}
+
+	match speaker {
+		FrontCenter => match (idx_of(FrontLeft), idx_of(FrontRight)) {
+			(Some(l), Some(r)) => vec![(l, MINUS_3DB), (r, MINUS_3DB)],
+			_ => vec![]
+		},
+		FrontLeft | FrontRight => match idx_of(FrontCenter) {
+			Some(c) => vec![(c, MINUS_3DB)],
+			None => vec![]
+		},
+		RearLeft | SideLeft | RearRight | SideRight => {
+			let (rear, side, front) = match speaker {
+				RearLeft | SideLeft => (RearLeft, SideLeft, FrontLeft),
+				_ => (RearRight, SideRight, FrontRight)
+			};
+
+			match idx_of(rear).or(idx_of(side)) {
+				Some(i) => vec![(i, 1.0)],
+				None => scaled(route(front, out), MINUS_3DB)
+			}
+		},
+		// The low frequency channel is dropped when there isn't a speaker for it, like standard downmixes do.
+		Lfe => vec![]
+	}
+}
+
+/// A matrix for mixing frames of one channel count into another.
+///
+/// Each output channel is the weighted sum of the input channels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MixMatrix {
+	in_channels: u8,
+	out_channels: u8,
+	/// Row-major coefficients with a row for each output channel.
+	coeffs: Vec<f32>
+}
+
+impl MixMatrix {
+	/// Makes a custom matrix from row-major coefficients (one row of `in_channels` coefficients for each output channel).
+	pub fn new(in_channels: u8, out_channels: u8, coeffs: Vec<f32>) -> Option<Self> {
+		if in_channels == 0 || out_channels == 0 || coeffs.len() != in_channels as usize * out_channels as usize {
+			return None;
+		}
+
+		Some(MixMatrix { in_channels, out_channels, coeffs })
+	}
+
+	pub fn identity(channels: u8) -> Self {
+		let n = channels as usize;
+		let mut coeffs = vec![0.0; n * n];
+		for i in 0..n {
+			coeffs[i * n + i] = 1.0;
+		}
+
+		MixMatrix { in_channels: channels, out_channels: channels, coeffs }
+	}
+
+	/// The standard up/down-mix from one layout into another.
+	pub fn between(from: ChannelLayout, to: ChannelLayout) -> Self {
+		let (in_channels, out_channels) = (from.channels(), to.channels());
+		let mut coeffs = vec![0.0; in_channels as usize * out_channels as usize];
+
+		if from.speakers().is_empty() || to.speakers().is_empty() {
+			// Without known speakers, channels are just mapped one to one.
+			for i in 0..in_channels.min(out_channels) as usize {
+				coeffs[i * in_channels as usize + i] = 1.0;
+			}
+		} else if from == ChannelLayout::Mono && !to.speakers().contains(&Speaker::FrontCenter) {
+			// Mono is played at its full level on each of the front speakers rather than spread over them at -3dB like a center channel,
+			// so that mono sources are as loud as they were before layouts had speakers.
+			for (out_i, _) in route(Speaker::FrontCenter, to.speakers()) {
+				coeffs[out_i] = 1.0;
+			}
+		} else {
+			for (in_i, speaker) in from.speakers().iter().enumerate() {
+				for (out_i, amp) in route(*speaker, to.speakers()) {
+					coeffs[out_i * in_channels as usize + in_i] += amp;
+				}
+			}
+		}
+
+		MixMatrix { in_channels, out_channels, coeffs }
+	}
+
+	pub fn in_channels(&self) -> u8 {
+		self.in_channels
+	}
+
+	pub fn out_channels(&self) -> u8 {
+		self.out_channels
+	}
+
+	pub fn coeffs(&self) -> &[f32] {
+		&self.coeffs
+	}
+
+	/// Mixes `input` and adds the result multiplied by `gain` to `output`.
+	/// Missing input channels are treated as silence.
+	pub fn apply_add(&self, input: &[TSample], output: &mut [TSample], gain: f32) {
+		let in_n = self.in_channels as usize;
+
+		for (out_i, out) in output.iter_mut().enumerate().take(self.out_channels as usize) {
+			let row = &self.coeffs[out_i * in_n..(out_i + 1) * in_n];
+			let v: f32 = row.iter().zip(input.iter()).map(|(c, s)| c * s).sum();
+			*out += v * gain;
+		}
+	}
+
+	/// Like `apply_add` but for every frame of the interleaved `input` and `output` blocks.
+	pub fn apply_block_add(&self, input: &[TSample], output: &mut [TSample], gain: f32) {
+		if self.is_identity() {
+			// The common case of sources already in the layout of the composition gets a plain loop that the compiler can vectorize.
+			for (out, v) in output.iter_mut().zip(input.iter()) {
+				*out += v * gain;
+			}
+
+			return;
+		}
+
+		for (in_frame, out_frame) in input.chunks_exact(self.in_channels as usize).zip(output.chunks_exact_mut(self.out_channels as usize)) {
+			self.apply_add(in_frame, out_frame, gain);
+		}
+	}
+
+	fn is_identity(&self) -> bool {
+		let n = self.in_channels as usize;
+
+		self.in_channels == self.out_channels
+			&& self.coeffs.iter().enumerate().all(|(i, c)| *c == if i / n == i % n { 1.0 } else { 0.0 })
+	}
+}
@@ -1,48 +1,91 @@
 use std::{sync::{Arc, Mutex}, thread::ThreadId};
 
-use crate::{composition::TWrappedCompositionState, compositor::{init_compositor_thread, CompositionBufferNode}, source::TFrameIdx};
+use crate::{composition::{TSrcID, TWrappedCompositionState}, compositor::{init_compositor_thread, ring::{CompositionRing, RingCursor}}, event::{Event, GLOBAL_EVENT_BUS}, resampler::ResampleQuality, source::{nested::NestedSrc, Source, TFrameIdx}};
 
-pub enum CompositorState<const BUF_SIZE: usize> {
-    Active(ThreadId, Arc<CompositionBufferNode<BUF_SIZE>>),
+pub enum CompositorState {
+    Active(ThreadId, Arc<CompositionRing>),
     Killed
 }
 
-pub struct CompositorData<const BUF_SIZE: usize> {
+pub struct CompositorData {
     sample_rate: TFrameIdx,
+    /// The number of samples in each buffer of the compositor.
+    block_size: usize,
     cmp_id: String,
-    state: Arc<Mutex<CompositorState<BUF_SIZE>>>
+    state: Arc<Mutex<CompositorState>>
 }
 
-impl<const BUF_SIZE: usize> CompositorData<BUF_SIZE> {
-    pub fn new(cmp_id: String, sample_rate: TFrameIdx, state: Arc<Mutex<CompositorState<BUF_SIZE>>>) -> Self {
+impl CompositorData {
+    pub fn new(cmp_id: String, sample_rate: TFrameIdx, block_size: usize, state: Arc<Mutex<CompositorState>>) -> Self {
         CompositorData {
             cmp_id,
             sample_rate,
+            block_size,
             state,
         }
     }
 }
 
-pub struct CompositionRegistry<const BUF_SIZE: usize> {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NestError {
+    /// One of the compositions doesn't exist.
+    NotFound,
+    /// The composition would end up (directly or through other compositions) inside of itself.
+    Cycle
+}
+
+/// Whether `cmp` is the composition with the ID of `cmp_id` or has it nested somewhere inside of it.
+fn contains_composition(cmp: &TWrappedCompositionState, cmp_id: &str) -> bool {
+    let mut to_visit = vec![cmp.clone()];
+
+    while let Some(cmp) = to_visit.pop() {
+        let cmp = cmp.read().unwrap();
+        if cmp.get_id() == cmp_id { return true; }
+
+        to_visit.extend(cmp.sources().iter().filter_map(|cmp_src| match cmp_src.src {
+            Source::Nested(ref nested) => Some(nested.composition().clone()),
+            _ => None
+        }));
+    }
+
+    false
+}
+
+pub struct CompositionRegistry {
     compositions: Vec<TWrappedCompositionState>,
-    compositors: Vec<CompositorData<BUF_SIZE>>,
+    compositors: Vec<CompositorData>,
+    /// The resampling quality used by new compositors of each sample-rate, `ResampleQuality::default()` is used for the rest.
+    resample_qualities: Vec<(TFrameIdx, ResampleQuality)>,
+}
+
+impl Default for CompositionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<const BUF_SIZE: usize> CompositionRegistry<BUF_SIZE> {
+impl CompositionRegistry {
     pub fn new() -> Self {
         CompositionRegistry {
             compositions: Vec::new(),
             compositors: Vec::new(),
+            resample_qualities: Vec::new(),
         }
     }
 
-    pub fn get_active_buf(&mut self, cmp_id: &str, sample_rate: TFrameIdx) -> Option<Arc<CompositionBufferNode<BUF_SIZE>>> {
+    /// Gives a cursor reading the output of the composition at `sample_rate` in buffers of `block_size` samples from its latest buffer on,
+    /// starting a compositor for it if there isn't one. The readers asking for the same sample-rate and block size share a compositor.
+    /// 
+    /// Note: The compositor keeps running for as long as the cursor (or any other cursor of it) is around.
+    pub fn get_cursor(&mut self, cmp_id: &str, sample_rate: TFrameIdx, block_size: usize) -> Option<RingCursor> {
+        self.compositors.retain(|cmp| matches!(*cmp.state.lock().unwrap(), CompositorState::Active(..)));
+
         for cmp in self.compositors.iter_mut() {
             let cmp_lock = cmp.state.lock().unwrap();
 
-            if let CompositorState::Active(_, ref buf) = *cmp_lock {
-                if cmp.cmp_id == cmp_id && cmp.sample_rate == sample_rate {
-                    return Some(buf.clone());
+            if let CompositorState::Active(_, ref ring) = *cmp_lock {
+                if cmp.cmp_id == cmp_id && cmp.sample_rate == sample_rate && cmp.block_size == block_size {
+                    return Some(ring.cursor());
                 }
             }
 
@@ -50,22 +93,61 @@ impl<const BUF_SIZE: usize> CompositionRegistry<BUF_SIZE> {
 
         let cmp_state = self.compositions.iter().find(|d| d.read().unwrap().get_id() == cmp_id)?.clone();
 
-        let (compositor, node) = init_compositor_thread::<BUF_SIZE>(sample_rate, cmp_state);
+        let (compositor, cursor) = init_compositor_thread(sample_rate, block_size, cmp_state, self.resample_quality(sample_rate));
 
         self.compositors.push(compositor);
 
-        Some(node)
+        GLOBAL_EVENT_BUS.publish(Event::CompositorStarted { cmp_id: cmp_id.to_owned(), sample_rate });
+
+        Some(cursor)
     }
     
+    /// Sets the resampling quality of the compositors that are going to be made for `sample_rate`.
+    /// 
+    /// Note: Compositors that are already running keep the quality they were made with.
+    pub fn set_resample_quality(&mut self, sample_rate: TFrameIdx, quality: ResampleQuality) {
+        self.resample_qualities.retain(|(rate, _)| *rate != sample_rate);
+        self.resample_qualities.push((sample_rate, quality));
+    }
+
+    pub fn resample_quality(&self, sample_rate: TFrameIdx) -> ResampleQuality {
+        self.resample_qualities.iter()
+            .find_map(|(rate, quality)| if *rate == sample_rate { Some(*quality) } else { None })
+            .unwrap_or_default()
+    }
+
     pub fn find_composition(&self, cmp_id: &str) -> Option<&TWrappedCompositionState> {
         self.compositions.iter().find(|c| c.read().unwrap().get_id() == cmp_id )
     }
 
-    // pub fn find_compositor(&self, cmp_id: &str, sample_rate: u32) -> Option<&CompositorData<BUF_SIZE>> {
+    // pub fn find_compositor(&self, cmp_id: &str, sample_rate: u32) -> Option<&CompositorData> {
     //     self.compositors.iter().find(|d| d.cmp_id == cmp_id && d.sample_rate == sample_rate)
     // }
 
+    /// Adds the output of the composition `inner_id` as a source of the composition `outer_id`, read at `sample_rate`.
+    /// 
+    /// The output is read from the compositor of the nested composition at `sample_rate` in buffers of `block_size` samples,
+    /// which is shared with the endpoints reading the nested composition the same way (see `get_cursor`).
+    pub fn nest(&mut self, outer_id: &str, inner_id: &str, sample_rate: TFrameIdx, block_size: usize) -> Result<TSrcID, NestError> {
+        let outer = self.find_composition(outer_id).ok_or(NestError::NotFound)?.clone();
+        let inner = self.find_composition(inner_id).ok_or(NestError::NotFound)?.clone();
+
+        if contains_composition(&inner, outer_id) {
+            return Err(NestError::Cycle);
+        }
+
+        let cursor = self.get_cursor(inner_id, sample_rate, block_size).ok_or(NestError::NotFound)?;
+        let src = NestedSrc::new(inner, Box::new(cursor), sample_rate);
+
+        let id = outer.write().unwrap().push_src_default(src.into());
+
+        Ok(id)
+    }
+
     pub fn push_composition(&mut self, cmp: TWrappedCompositionState) {
+        let cmp_id = cmp.read().unwrap().get_id().clone();
         self.compositions.push(cmp);
+
+        GLOBAL_EVENT_BUS.publish(Event::CompositionAdded { cmp_id });
     }
 }
\ No newline at end of file
@@ -1,14 +1,173 @@
-use std::{sync::{atomic::AtomicU16, Arc, RwLock}, vec};
+use std::{fmt::Display, str::FromStr, sync::{atomic::{AtomicU16, AtomicU32}, Arc, RwLock}, vec};
 
 use coarsetime::Instant;
 
-use crate::source::{BaseSource, Source, TFrameIdx};
+use crate::{channel_layout::{ChannelLayout, MixMatrix}, compositor::{level::Levels, limiter::LimiterConfig}, effect::EffectChain, event::{Event, GLOBAL_EVENT_BUS}, loudness::{normalization_gain, TLufs}, source::{metadata::NowPlaying, BaseSource, Source, TFrameIdx}};
 
 pub type TWrappedCompositionState = Arc<RwLock<CompositionState>>;
 
+/// A unique identifier given to each source at the moment it's added to a composition.
+pub type TSrcID = u32;
+
+/// The default length of the ramps used for smoothing out live adjustments.
+pub const DEFAULT_RAMP_MS: u32 = 20;
+
+/// Whether the amplification can be given to a source, i.e. that it's a finite number and not negative.
+pub fn is_valid_amplification(amp: f32) -> bool {
+	amp.is_finite() && 0.0 <= amp
+}
+
 static COMPOSITION_ID_TO_ALLOCATE: AtomicU16 = AtomicU16::new(0);
-pub struct SrcCompositionData { pub frame_offset: i64, pub amplification: f32 }
-pub struct CompositionSrc { pub src: Source, pub composition_data: SrcCompositionData }
+static SRC_ID_TO_ALLOCATE: AtomicU32 = AtomicU32::new(0);
+
+/// What happens to a source once it has played to its end.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FinishPolicy {
+	/// The source gets removed from the composition.
+	Remove,
+	/// The source stays in the composition (e.g. for seeking back into it) without being read.
+	#[default]
+	Hold,
+	/// The source plays again this many times before finishing.
+	Loop(u32),
+	/// The source plays again and again, never finishing.
+	LoopForever
+}
+
+impl Display for FinishPolicy {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Remove => write!(f, "remove"),
+			Self::Hold => write!(f, "hold"),
+			Self::Loop(times) => write!(f, "loop:{}", times),
+			Self::LoopForever => write!(f, "loop")
+		}
+	}
+}
+
+/// Written as `remove`, `hold`, `loop` (forever) or `loop:{times}`.
+impl FromStr for FinishPolicy {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.split_once(':') {
+			None if s == "remove" => Ok(Self::Remove),
+			None if s == "hold" => Ok(Self::Hold),
+			None if s == "loop" => Ok(Self::LoopForever),
+			Some(("loop", times)) => times.parse().map(Self::Loop).map_err(|_| ()),
+			_ => Err(())
+		}
+	}
+}
+
+pub struct SrcCompositionData {
+	pub frame_offset: i64,
+	pub amplification: f32,
+	/// A muted source is computed as silence.
+	pub muted: bool,
+	/// When at least one source of a composition is soloed, only the soloed sources are heard.
+	pub solo: bool,
+	/// A custom matrix for mixing the channels of the source into the channels of the composition.
+	/// If it's not set or doesn't match the channel counts, the standard up/down-mix is used.
+	pub mix_matrix: Option<MixMatrix>,
+	/// Effects applied to the frames of the source before they're mixed into the composition.
+	pub effects: EffectChain,
+	/// The gain bringing the source to the loudness target of the composition, applied on top of `amplification`.
+	normalization_gain: f32,
+	/// Frame of the source at which it got paused.
+	paused_at: Option<TFrameIdx>,
+	pub finish_policy: FinishPolicy,
+	/// Whether the source has been announced as finished, which is forgotten on seeks so that it can finish again.
+	finished: bool,
+	/// Whether the source failed while being read and is left out of the mix (see `CompositionState::fault_sources`).
+	faulted: bool,
+	/// Recorded by the compositors like `CompositionState::gain_reduction`, `None` until the source has been mixed once.
+	levels: Option<Levels>
+}
+
+impl SrcCompositionData {
+	pub fn new(frame_offset: i64, amplification: f32) -> Self {
+		SrcCompositionData {
+			frame_offset,
+			amplification,
+			muted: false,
+			solo: false,
+			mix_matrix: None,
+			effects: EffectChain::default(),
+			normalization_gain: 1.0,
+			paused_at: None,
+			finish_policy: FinishPolicy::default(),
+			finished: false,
+			faulted: false,
+			levels: None
+		}
+	}
+
+	pub fn normalization_gain(&self) -> f32 {
+		self.normalization_gain
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused_at.is_some()
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	pub fn is_faulted(&self) -> bool {
+		self.faulted
+	}
+
+	/// The levels of the source after its effects and before its amplification, silence while it isn't heard (e.g. muted or paused).
+	pub fn levels(&self) -> Option<&Levels> {
+		self.levels.as_ref()
+	}
+
+	/// Whether the source should be heard in a composition where `any_solo` tells if any of the sources are soloed.
+	pub fn is_audible(&self, any_solo: bool) -> bool {
+		!self.muted && !self.is_paused() && (self.solo || !any_solo)
+	}
+}
+pub struct CompositionSrc { id: TSrcID, pub src: Source, pub composition_data: SrcCompositionData }
+
+impl CompositionSrc {
+	fn new(src: Source, composition_data: SrcCompositionData) -> Self {
+		let id = SRC_ID_TO_ALLOCATE.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+		CompositionSrc { id, src, composition_data }
+	}
+
+	/// The ID stays the same for as long as the source is part of the composition and is never reused for another source.
+	pub fn id(&self) -> TSrcID {
+		self.id
+	}
+
+	/// The length of a single play of the source in its own frames, if it's known and it ends.
+	pub fn loop_len(&self) -> Option<TFrameIdx> {
+		self.src.duration().filter(|d| *d != 0)
+	}
+
+	/// The frames (of the source) after which the source is finished, including its loops, `None` if it never finishes.
+	pub fn play_len(&self) -> Option<TFrameIdx> {
+		let len = self.loop_len()?;
+
+		match self.composition_data.finish_policy {
+			FinishPolicy::Remove | FinishPolicy::Hold => Some(len),
+			FinishPolicy::Loop(times) => Some(len.saturating_mul(times as TFrameIdx + 1)),
+			FinishPolicy::LoopForever => None
+		}
+	}
+}
+
+/// How much the limiter of a composition has turned its output down, in positive dBs.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GainReduction {
+	/// The most reduction in the last computed buffer.
+	pub current_db: f32,
+	/// The most reduction since the last reset.
+	pub max_db: f32
+}
 
 pub fn convert_sample_rates(sample_rate_a: TFrameIdx, rate_a: TFrameIdx, sample_rate_b: TFrameIdx) -> TFrameIdx {
 	rate_a * sample_rate_b / sample_rate_a
@@ -17,9 +176,20 @@ pub fn convert_sample_rates(sample_rate_a: TFrameIdx, rate_a: TFrameIdx, sample_
 pub struct CompositionState {
 	id: String,
 	pause_t: Option<Instant>,
-	channels: u8,
-	pub sources: Vec<CompositionSrc>,
+	channel_layout: ChannelLayout,
+	sources: Vec<CompositionSrc>,
 	amplification: f32,
+	/// How long it takes for amplification changes, seeks, pauses, and so on to fade in.
+	ramp_ms: u32,
+	/// Effects applied to the mix of all the sources (the master bus).
+	effects: EffectChain,
+	limiter: LimiterConfig,
+	/// The loudness the sources get normalized to, no normalization is done if it's not set.
+	loudness_target: Option<TLufs>,
+	/// Recorded by the compositors, which with several compositors (sample-rates) is from the one that computed last.
+	gain_reduction: GainReduction,
+	/// The levels of the output, recorded the same way as `gain_reduction`.
+	levels: Option<Levels>,
 	/// This field is used for checking whether `playback_offset_ms` has been changed and is used by the compositor to adapt accordingly.
 	pub config_change_idx: u16,
 	/// The anchor used to determine the elapsed time.
@@ -30,13 +200,19 @@ pub struct CompositionState {
 }
 
 impl CompositionState {
-	pub fn new(id: String, amp: f32) -> Self {
+	pub fn new(id: String, amp: f32, channel_layout: ChannelLayout) -> Self {
 		CompositionState {
 			id,
 			pause_t: None,
-			channels: 2,
+			channel_layout,
 			sources: vec![],
 			amplification: amp,
+			ramp_ms: DEFAULT_RAMP_MS,
+			effects: EffectChain::default(),
+			limiter: LimiterConfig::default(),
+			loudness_target: None,
+			gain_reduction: GainReduction::default(),
+			levels: None,
 			config_change_idx: 0,
 			start_t: Instant::now(),
 			playback_offset_ms: 0
@@ -44,29 +220,169 @@ impl CompositionState {
 	}
 
 	// Adds a source with its start set to now and amplification of 1.0
-	pub fn push_src_default(&mut self, src: Source) {
-		self.sources.push(CompositionSrc {
-			composition_data: SrcCompositionData { 
-				amplification: 1.0,
-				frame_offset: (self.get_time_sec() * src.sample_rate() as f64) as i64
-			},
-			src,
-		});
+	pub fn push_src_default(&mut self, src: Source) -> TSrcID {
+		let frame_offset = (self.get_time_sec() * src.sample_rate() as f64) as i64;
+
+		self.push_src_offset(src, frame_offset)
+	}
+
+	pub fn push_src_offset(&mut self, mut src: Source, frame_offset: i64) -> TSrcID {
+		if let Source::Queue(ref mut queue) = src {
+			queue.set_loudness_target(self.loudness_target);
+		}
+
+		let cmp_src = CompositionSrc::new(src, SrcCompositionData::new(frame_offset, 1.0));
+		let id = cmp_src.id();
+		let kind = cmp_src.src.kind().to_owned();
+
+		self.sources.push(cmp_src);
 
+		GLOBAL_EVENT_BUS.publish(Event::SourceAdded { cmp_id: self.id.clone(), src_id: id, kind });
+
+		id
+	}
+
+	pub fn sources(&self) -> &[CompositionSrc] {
+		&self.sources
 	}
 
-	pub fn push_src_offset(&mut self, src: Source, frame_offset: i64) {
-		self.sources.push(CompositionSrc {
-			composition_data: SrcCompositionData { 
-				amplification: 1.0,
-				frame_offset
+	pub fn sources_mut(&mut self) -> &mut [CompositionSrc] {
+		&mut self.sources
+	}
+
+	pub fn find_src(&self, id: TSrcID) -> Option<&CompositionSrc> {
+		self.sources.iter().find(|s| s.id == id)
+	}
+
+	pub fn find_src_mut(&mut self, id: TSrcID) -> Option<&mut CompositionSrc> {
+		self.sources.iter_mut().find(|s| s.id == id)
+	}
+
+	/// Pauses or resumes a single source while the rest of the composition keeps going.
+	/// 
+	/// While paused the source doesn't advance, so on resume it continues from where it was paused.
+	pub fn set_src_paused(&mut self, id: TSrcID, state: bool) -> Option<()> {
+		let time_ms = self.get_time_millis();
+		let cmp_src = self.find_src_mut(id)?;
+		// The composition frame of now in the sample rate of the source
+		let cmp_frame = time_ms as i64 * cmp_src.src.sample_rate() as i64 / 1000;
+		let data = &mut cmp_src.composition_data;
+
+		// The position of the source is kept in its own frames, so that seeks and pauses of the composition in the meantime don't move it
+		match (data.paused_at, state) {
+			(None, true) => { data.paused_at = Some((cmp_frame - data.frame_offset).max(0) as TFrameIdx); },
+			(Some(paused_at), false) => {
+				data.frame_offset = cmp_frame - paused_at as i64;
+				data.paused_at = None;
 			},
-			src,
+			_ => {}
+		}
+
+		Some(())
+	}
+
+	pub fn set_src_muted(&mut self, id: TSrcID, state: bool) -> Option<()> {
+		self.find_src_mut(id)?.composition_data.muted = state;
+		Some(())
+	}
+
+	/// Solos a source, so that only the soloed sources of the composition are heard.
+	pub fn set_src_solo(&mut self, id: TSrcID, state: bool) -> Option<()> {
+		self.find_src_mut(id)?.composition_data.solo = state;
+		Some(())
+	}
+
+	/// Lets a source that has finished finish again, for when it's given more to play (e.g. items started in a queue that had ended).
+	pub fn forget_src_finished(&mut self, id: TSrcID) -> Option<()> {
+		self.find_src_mut(id)?.composition_data.finished = false;
+		Some(())
+	}
+
+	/// What a source is playing and how far it has got.
+	///
+	/// Queues report their current item, other sources report themselves as a whole.
+	pub fn now_playing(&self, id: TSrcID) -> Option<NowPlaying<'_>> {
+		let cmp_src = self.find_src(id)?;
+		let src = &cmp_src.src;
+
+		if let Source::Queue(queue) = src {
+			return Some(NowPlaying {
+				metadata: queue.metadata(),
+				queue_idx: queue.current(),
+				elapsed: queue.current_position(),
+				length: queue.current_len(),
+				sample_rate: queue.sample_rate()
+			});
+		}
+
+		// Paused sources stay where they got paused
+		let frame = cmp_src.composition_data.paused_at.unwrap_or_else(|| {
+			(self.get_time_millis() as i64 * src.sample_rate() as i64 / 1000 - cmp_src.composition_data.frame_offset).max(0) as TFrameIdx
 		});
+
+		let length = src.duration().filter(|d| *d != 0);
+
+		Some(NowPlaying {
+			metadata: src.metadata(),
+			queue_idx: None,
+			elapsed: length.map_or(frame, |len| frame.min(len)),
+			length,
+			sample_rate: src.sample_rate()
+		})
+	}
+
+	/// Applies the finish policies of the sources that the compositor found to have played to their end and announces them,
+	/// unless they've already been announced (e.g. by the compositor of another sample-rate).
+	pub fn finish_sources(&mut self, ids: &[TSrcID]) {
+		for id in ids {
+			let cmp_src = match self.find_src_mut(*id) {
+				Some(s) if !s.composition_data.finished => s,
+				_ => continue
+			};
+
+			cmp_src.composition_data.finished = true;
+			let policy = cmp_src.composition_data.finish_policy;
+
+			GLOBAL_EVENT_BUS.publish(Event::SourceFinished { cmp_id: self.id.clone(), src_id: *id, policy });
+
+			if policy == FinishPolicy::Remove {
+				self.remove_src(*id);
+			}
+		}
+	}
+
+	/// Leaves the sources that the compositor found to have failed out of the mix from now on and announces them,
+	/// unless they've already been announced.
+	pub fn fault_sources(&mut self, ids: &[TSrcID]) {
+		for id in ids {
+			let cmp_src = match self.find_src_mut(*id) {
+				Some(s) if !s.composition_data.faulted => s,
+				_ => continue
+			};
+
+			cmp_src.composition_data.faulted = true;
+			let decode_errors = cmp_src.src.decode_errors();
+			log::error!("Source '{}' of composition '{}' is faulted after {} decode errors.", id, self.id, decode_errors);
+
+			GLOBAL_EVENT_BUS.publish(Event::SourceFaulted { cmp_id: self.id.clone(), src_id: *id });
+		}
+	}
+
+	/// Removes the source from the composition and hands it back.
+	/// 
+	/// Note: Compositors hold the write lock for the whole duration of computing a buffer,
+	/// so a removal always lands on a buffer boundary and the source simply stops contributing from the next buffer on.
+	pub fn remove_src(&mut self, id: TSrcID) -> Option<CompositionSrc> {
+		let idx = self.sources.iter().position(|s| s.id == id)?;
+		let cmp_src = self.sources.remove(idx);
+
+		GLOBAL_EVENT_BUS.publish(Event::SourceRemoved { cmp_id: self.id.clone(), src_id: id });
+
+		Some(cmp_src)
 	}
 
 	pub fn get_time_millis(&self) -> u64 {
-		let curr_now = self.pause_t.unwrap_or_else(|| Instant::now());
+		let curr_now = self.pause_t.unwrap_or_else(Instant::now);
 		let elapsed_time_ms = curr_now.duration_since(self.start_t).as_millis();
 		
 		elapsed_time_ms.saturating_add_signed(self.playback_offset_ms)
@@ -80,11 +396,18 @@ impl CompositionState {
 		let playback_time_ms = self.get_time_millis();
 		self.playback_offset_ms += time_ms as i64 - playback_time_ms as i64;
 
+		// Seeking back into the sources that have finished makes them finish again
+		for cmp_src in self.sources.iter_mut() {
+			cmp_src.composition_data.finished = false;
+		}
+
 		self.config_change_idx += 1;
+
+		GLOBAL_EVENT_BUS.publish(Event::CompositionSeeked { cmp_id: self.id.clone(), time_ms });
 	}
 
 	pub fn is_pushed_pass_zero(&self) -> bool {
-		self.playback_offset_ms < 0 && self.start_t.elapsed().as_millis() < self.playback_offset_ms.abs() as u64
+		self.playback_offset_ms < 0 && self.start_t.elapsed().as_millis() < self.playback_offset_ms.unsigned_abs()
 	}
 
 	pub fn is_paused(&self) -> bool {
@@ -101,13 +424,16 @@ impl CompositionState {
 		
 		if let Some(ref pause_t) = self.pause_t {
 			let now = Instant::now();
-			let time_passed_since_paused = now.duration_since(pause_t.clone());
+			let time_passed_since_paused = now.duration_since(*pause_t);
 			self.playback_offset_ms -= time_passed_since_paused.as_millis() as i64;
 			
 			self.pause_t = None;
 		} else {
 			self.pause_t = Some(Instant::now());
 		}
+
+		let cmp_id = self.id.clone();
+		GLOBAL_EVENT_BUS.publish(if state { Event::CompositionPaused { cmp_id } } else { Event::CompositionResumed { cmp_id } });
 	}
 
 	pub fn start_time(&self) -> &Instant {
@@ -115,7 +441,11 @@ impl CompositionState {
 	}
 
 	pub fn get_channels(&self) -> u8 {
-		self.channels
+		self.channel_layout.channels()
+	}
+
+	pub fn get_channel_layout(&self) -> ChannelLayout {
+		self.channel_layout
 	}
 
 	pub fn get_id(&self) -> &String {
@@ -129,6 +459,88 @@ impl CompositionState {
 	pub fn get_amplification(&self) -> f32 {
 		self.amplification
 	}
+
+	pub fn set_ramp_ms(&mut self, ramp_ms: u32) {
+		self.ramp_ms = ramp_ms;
+	}
+
+	pub fn get_ramp_ms(&self) -> u32 {
+		self.ramp_ms
+	}
+
+	/// Replaces the effects of the master bus, the compositors pick the new chain up from their next buffer.
+	pub fn set_effects(&mut self, effects: EffectChain) {
+		self.effects = effects;
+	}
+
+	pub fn get_effects(&self) -> &EffectChain {
+		&self.effects
+	}
+
+	/// Sets the output stage of the composition, the compositors pick it up from their next buffer.
+	pub fn set_limiter(&mut self, limiter: LimiterConfig) {
+		self.limiter = limiter;
+	}
+
+	pub fn get_limiter(&self) -> LimiterConfig {
+		self.limiter
+	}
+
+	/// Sets the loudness (in LUFS) every source gets normalized to, or turns normalization off with `None`.
+	/// 
+	/// The sources of queues are normalized one by one instead of the queue as a whole.
+	pub fn set_loudness_target(&mut self, target: Option<TLufs>) {
+		self.loudness_target = target;
+
+		for cmp_src in self.sources.iter_mut() {
+			if let Source::Queue(ref mut queue) = cmp_src.src {
+				queue.set_loudness_target(target);
+			}
+		}
+	}
+
+	pub fn get_loudness_target(&self) -> Option<TLufs> {
+		self.loudness_target
+	}
+
+	/// Updates the normalization gains of the sources, as the loudness of a source may only get known after it has been added.
+	pub(crate) fn refresh_normalization(&mut self) {
+		let target = self.loudness_target;
+
+		for cmp_src in self.sources.iter_mut() {
+			cmp_src.composition_data.normalization_gain = target.zip(cmp_src.src.loudness())
+				.map_or(1.0, |(target, lufs)| normalization_gain(lufs, target));
+		}
+	}
+
+	pub(crate) fn record_gain_reduction(&mut self, reduction_db: f32) {
+		self.gain_reduction.current_db = reduction_db;
+		self.gain_reduction.max_db = self.gain_reduction.max_db.max(reduction_db);
+	}
+
+	pub fn get_gain_reduction(&self) -> GainReduction {
+		self.gain_reduction
+	}
+
+	pub(crate) fn record_levels(&mut self, (master, sources): (Levels, Vec<(TSrcID, Levels)>)) {
+		self.levels = Some(master);
+
+		for (id, levels) in sources {
+			if let Some(cmp_src) = self.find_src_mut(id) {
+				cmp_src.composition_data.levels = Some(levels);
+			}
+		}
+	}
+
+	/// The levels of the output in the last buffer computed, `None` until a compositor has computed one.
+	pub fn get_levels(&self) -> Option<&Levels> {
+		self.levels.as_ref()
+	}
+
+	/// Starts keeping track of the maximum gain reduction over from now.
+	pub fn reset_gain_reduction(&mut self) {
+		self.gain_reduction = GainReduction::default();
+	}
 }
 
 impl Default for CompositionState {
@@ -139,9 +551,15 @@ impl Default for CompositionState {
 		CompositionState {
 			id,
 			pause_t: None,
-			channels: 2,
+			channel_layout: ChannelLayout::Stereo,
 			sources: vec![],
 			amplification: 1.0,
+			ramp_ms: DEFAULT_RAMP_MS,
+			effects: EffectChain::default(),
+			limiter: LimiterConfig::default(),
+			loudness_target: None,
+			gain_reduction: GainReduction::default(),
+			levels: None,
 			config_change_idx: 0,
 			start_t: Instant::now(),
 			playback_offset_ms: 0
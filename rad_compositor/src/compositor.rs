@@ -1,207 +1,507 @@
-use std::{sync::{Arc, Condvar, Mutex}, thread::{self, sleep}, time::Duration};
+use std::{collections::HashMap, panic::{catch_unwind, AssertUnwindSafe}, sync::{Arc, Mutex}, thread::{self, sleep}, time::Duration};
 
 use coarsetime::Instant;
 
-use crate::{cmp_reg::{CompositorData, CompositorState}, composition::{convert_sample_rates, CompositionSrc, TWrappedCompositionState}, source::{BaseSource, Source, TFrameIdx, TSample}};
+use crate::{cmp_reg::{CompositorData, CompositorState}, composition::{convert_sample_rates, CompositionSrc, FinishPolicy, TSrcID, TWrappedCompositionState}, channel_layout::{ChannelLayout, MixMatrix}, effect::{ChainProcessor, EffectChain}, event::{Event, GLOBAL_EVENT_BUS}, resampler::{ResampleQuality, Resampler}, source::{BaseSource, TFrameIdx, TSample}};
+use level::{LevelMeter, Levels};
+use limiter::{Limiter, LimiterConfig};
+use ramp::Ramp;
+use ring::{ReadError, RingCursor, RingWriter};
+
+pub mod level;
+pub mod limiter;
+pub mod ramp;
+pub mod ring;
 
 const COMPUTE_AHEAD_SEC: f32 = 0.3;
+/// The number of samples in each buffer computed by a compositor, unless an endpoint asks for something else.
+pub const DEFAULT_BLOCK_SIZE: usize = 1024;
+/// Smaller blocks lower the latency of the endpoints at the cost of more CPU time spent per sample.
+pub const MIN_BLOCK_SIZE: usize = 32;
+pub const MAX_BLOCK_SIZE: usize = 32768;
+/// The number of buffers kept by each compositor for its readers, a few times what's computed ahead of time.
+const RING_BUFFERS: usize = 128;
+
+/// Reads the buffers a compositor computes one after another, without depending on the size of the buffers.
+pub trait BufferCursor: Send + Sync {
+	/// Moves on to the next buffer and gives its data, or `None` if it wasn't computed within `timeout`.
+	fn next_buf(&mut self, timeout: Duration) -> Option<&[f32]>;
+
+	/// Moves on to the next buffer and gives its data, or `None` without waiting if it hasn't been computed yet.
+	fn try_next_buf(&mut self) -> Option<&[f32]>;
+
+	/// Skips the buffers that have already been computed, so that what's read next is live.
+	fn skip_to_head(&mut self);
+}
 
-/// A linked list like structure for streaming audio that supports multithreading. <br/>
-/// 
-/// Note: The `BUF_SIZE` argument means how many f32s will fit inside of this buffer
-pub struct CompositionBufferNode<const BUF_SIZE: usize>  {
-	cnxt: Condvar,
-	next: Mutex<Option<Arc<CompositionBufferNode<BUF_SIZE>>>>,
-	buf: [f32; BUF_SIZE]
+impl BufferCursor for RingCursor {
+	fn next_buf(&mut self, timeout: Duration) -> Option<&[f32]> {
+		// After falling behind the cursor goes on from the latest buffer, which is still worth reading
+		match self.read_timeout(timeout) {
+			Ok(_) => {},
+			Err(ReadError::Overrun { .. }) => { self.read_timeout(timeout).ok()?; },
+			Err(_) => return None
+		}
+
+		Some(self.buf())
+	}
+
+	fn try_next_buf(&mut self) -> Option<&[f32]> {
+		match self.try_read() {
+			Ok(_) => {},
+			Err(ReadError::Overrun { .. }) => { self.try_read().ok()?; },
+			Err(_) => return None
+		}
+
+		Some(self.buf())
+	}
+
+	fn skip_to_head(&mut self) {
+		RingCursor::skip_to_head(self);
+	}
 }
 
-impl<const BUF_SIZE: usize> CompositionBufferNode<BUF_SIZE> {
-	pub fn new(buf: [f32; BUF_SIZE]) -> Arc<Self> {
-		let res = Arc::new(
-			CompositionBufferNode {
-				buf,
-				cnxt: Condvar::new(),
-				next: Mutex::new(None),
-			}
-		);
+/// Fills `out` with the frames of the source for the frames of the compositor starting at `frame_idx`,
+/// and returns how many frames were filled before the source ended. Frames from before the source starts are silent.
+fn fetch_block(cmp_src: &mut CompositionSrc, resampler: &mut Resampler, target_sample_rate: TFrameIdx, frame_idx: TFrameIdx, out: &mut [TSample]) -> usize {
+	let channels = cmp_src.src.channels() as usize;
+	let frames = out.len() / channels;
+	let offset = cmp_src.composition_data.frame_offset;
+	let src_sample_rate = cmp_src.src.sample_rate();
+
+	let mut lead_frames = 0;
+	while lead_frames < frames
+		&& (convert_sample_rates(target_sample_rate, frame_idx + lead_frames as TFrameIdx, src_sample_rate) as i64) < offset {
+		lead_frames += 1;
+	}
+
+	out[..lead_frames * channels].fill(0.0);
+	if lead_frames == frames { return frames; }
+
+	let rest = &mut out[lead_frames * channels..frames * channels];
+	let first_frame_idx = frame_idx + lead_frames as TFrameIdx;
+	let rest_frames = frames - lead_frames;
+
+	// Loops are played by moving the start of the source forward by its length each time it starts over
+	let loop_len = match cmp_src.composition_data.finish_policy {
+		FinishPolicy::Loop(_) | FinishPolicy::LoopForever => cmp_src.loop_len(),
+		_ => None
+	};
+	let play_len = cmp_src.play_len();
+
+	let mut read = 0;
+	while read < rest_frames {
+		let idx = first_frame_idx + read as TFrameIdx;
+
+		let (loop_offset, until_loop_end) = match loop_len {
+			Some(len) => {
+				let pos = (convert_sample_rates(target_sample_rate, idx, src_sample_rate) as i64 - offset) as TFrameIdx;
+				if play_len.is_some_and(|play_len| play_len <= pos) { break; }
+
+				let loop_start = offset + (pos - pos % len) as i64;
+				// The first frame of the compositor that falls in the next loop
+				let next_loop_idx = ((loop_start + len as i64) as TFrameIdx * target_sample_rate).div_ceil(src_sample_rate);
+
+				(loop_start, (next_loop_idx - idx) as usize)
+			},
+			None => (offset, rest_frames)
+		};
+
+		let n = until_loop_end.min(rest_frames - read);
+		let buf = &mut rest[read * channels..(read + n) * channels];
+
+		let block_read = if src_sample_rate == target_sample_rate {
+			cmp_src.src.read_block((idx as i64 - loop_offset) as TFrameIdx, buf)
+		} else {
+			resampler.resample_block(&mut cmp_src.src, target_sample_rate, idx, loop_offset, buf)
+		};
+
+		read += block_read;
+		if block_read < n { break; }
+	}
+
+	lead_frames + read
+}
+
+/// The state a compositor keeps between the buffers it computes, used for smoothing the live adjustments made to the composition.
+pub struct MixState {
+	sample_rate: TFrameIdx,
+	channel_layout: ChannelLayout,
+	/// Length of the ramps and fades in frames.
+	ramp_frames: u32,
+	/// The amplification of the composition itself.
+	master: Ramp,
+	/// Fades the whole output in and out on seeks, pauses and resumes.
+	transport: Ramp,
+	/// The amplification of each source, including it being muted, paused, and so on.
+	src_gains: HashMap<TSrcID, Ramp>,
+	resample_quality: ResampleQuality,
+	/// Resamplers for the sources that don't have the same sample-rate as the compositor.
+	resamplers: HashMap<TSrcID, Resampler>,
+	/// The standard matrices for mixing each source into the layout of the composition.
+	matrices: HashMap<TSrcID, MixMatrix>,
+	/// While fading out for a seek, the frames are still read from the old timeline which is this far from the new one.
+	fade_out_delta: Option<i64>,
+	/// The effect processors of each source.
+	src_effects: HashMap<TSrcID, ChainProcessor>,
+	/// The effect processors of the master bus.
+	master_effects: ChainProcessor,
+	/// The last stage of the output, after the amplification of the composition.
+	limiter: Limiter,
+	/// Scratch space the frames of each source are read into before being mixed, kept around so that mixing doesn't allocate.
+	src_buf: Vec<TSample>,
+	/// The sources found to have played to their end, waiting for their finish policies to be applied (see `take_finished`).
+	finished: Vec<TSrcID>,
+	/// The sources found to have failed, waiting to be marked as faulted (see `take_faulted`).
+	faulted: Vec<TSrcID>,
+	/// The level meters of each source, measuring it after its effects and before its amplification.
+	src_meters: HashMap<TSrcID, LevelMeter>,
+	/// The level meter of the output, after the limiter.
+	master_meter: LevelMeter
+}
+
+impl MixState {
+	pub fn new(sample_rate: TFrameIdx, channel_layout: ChannelLayout, amplification: f32, ramp_ms: u32, resample_quality: ResampleQuality) -> Self {
+		let mut res = MixState {
+			sample_rate,
+			channel_layout,
+			ramp_frames: 1,
+			master: Ramp::new(amplification),
+			transport: Ramp::new(1.0),
+			src_gains: HashMap::new(),
+			resample_quality,
+			resamplers: HashMap::new(),
+			matrices: HashMap::new(),
+			fade_out_delta: None,
+			src_effects: HashMap::new(),
+			master_effects: ChainProcessor::default(),
+			limiter: Limiter::new(LimiterConfig::Off, sample_rate, channel_layout.channels()),
+			src_buf: Vec::new(),
+			finished: Vec::new(),
+			faulted: Vec::new(),
+			src_meters: HashMap::new(),
+			master_meter: LevelMeter::new(sample_rate, channel_layout)
+		};
+
+		res.set_ramp_ms(ramp_ms);
 
 		res
 	}
 
-	/// Sets the next buffers and causes the other threads waiting for the next buffer to resume.
-	/// Caution: This method panics if the next buffer is already set.
-	pub fn push_next(&self, buf: [f32; BUF_SIZE]) -> Arc<Self> {
-		let mut next_lock = self.next.lock().unwrap();
-		if next_lock.is_some() { panic!("Tried to push the next buf while it already existed.") }
-		
-		let next_node = Self::new(buf);
+	pub fn set_ramp_ms(&mut self, ramp_ms: u32) {
+		self.ramp_frames = (ramp_ms as TFrameIdx * self.sample_rate / 1000).max(1) as u32;
+	}
 
-		*next_lock = Some(next_node.clone());
-		self.cnxt.notify_all();
+	pub fn set_amplification(&mut self, amp: f32) {
+		self.master.set_target(amp, self.ramp_frames);
+	}
 
-		next_node
+	/// Makes the master bus use the processors of `chain`, rebuilding them only if the chain has changed.
+	pub fn set_effects(&mut self, chain: &EffectChain) {
+		self.master_effects.sync(chain, self.sample_rate, self.channel_layout);
 	}
 
-	/// Gives the next buffer. <br/>
-	/// Caution: This method **will wait** for the compositor thread to generate the next buffer if it wasn't generated.
-	pub fn next(&self) -> Arc<Self> {
-		let mut next = self.next.lock().unwrap();
-		while next.is_none() {
-			next = self.cnxt.wait(next).unwrap();
-		}
+	/// Rebuilds the limiter if its configuration has changed.
+	pub fn set_limiter(&mut self, config: LimiterConfig) {
+		if self.limiter.config() == config { return; }
 
-		next.as_ref().unwrap().clone()
+		self.limiter = Limiter::new(config, self.sample_rate, self.channel_layout.channels());
 	}
-	
-	/// Gives a reference to the buffer data.
-	pub fn buf(&self) -> &[f32; BUF_SIZE] {
-		&self.buf
-	}
-
-	/// Sets the current node to the last node generated.
-	pub fn set_to_head(node: &mut Arc<CompositionBufferNode<BUF_SIZE>>) {
-		loop {
-			let next = node.next.lock().unwrap().clone();
-			if let Some(next_node) = next {
-				*node = next_node;
-			} else {
-				break;
-			}
-		}
+
+	/// The most gain reduction (in positive dBs) the limiter has applied since the last call.
+	pub fn take_gain_reduction_db(&mut self) -> f32 {
+		self.limiter.take_reduction_db()
+	}
+
+	/// The sources that have played to their end since the last call, for `CompositionState::finish_sources`.
+	pub fn take_finished(&mut self) -> Vec<TSrcID> {
+		std::mem::take(&mut self.finished)
 	}
 
-	pub fn set_to_live(node: &mut Arc<CompositionBufferNode<BUF_SIZE>>, sample_rate: TFrameIdx, channels: u8) {
-		let computed_buffers_ahead_of_time = (COMPUTE_AHEAD_SEC * sample_rate as f32) as usize / (BUF_SIZE / channels as usize);
+	/// The sources that have failed since the last call, for `CompositionState::fault_sources`.
+	pub fn take_faulted(&mut self) -> Vec<TSrcID> {
+		std::mem::take(&mut self.faulted)
+	}
 
-		Self::set_to_head(node);
-		let mut head = node.clone();
+	/// The levels of the output and of each source since the last call, for `CompositionState::record_levels`.
+	pub fn take_levels(&mut self) -> (Levels, Vec<(TSrcID, Levels)>) {
+		let src_levels = self.src_meters.iter_mut().map(|(id, meter)| (*id, meter.take_levels())).collect();
 
-		for _ in 0..computed_buffers_ahead_of_time {
-			head = head.next();
-		}
+		(self.master_meter.take_levels(), src_levels)
 	}
-}
 
-/// Makes the sample-rate conversion a bit smoother.
-/// 
-/// Finds the nearest two frames in the source sample-rate and calculates the weighted average of them based on their closeness to the target.
-pub fn approximate_frame_linear(src: &mut Source, into_sample_rate: TFrameIdx, rate: TFrameIdx, offset: i64) -> Option<Vec<TSample>> {
-	let conv = (rate * src.sample_rate()) as f64 / into_sample_rate as f64 - offset as f64;
-	assert!(0.0 <= conv);
+	/// Fades out the old position before continuing from the new one.
+	pub fn begin_seek(&mut self, from_frame_idx: TFrameIdx, to_frame_idx: TFrameIdx) {
+		let prev_delta = self.fade_out_delta.unwrap_or(0);
+		self.fade_out_delta = Some(from_frame_idx as i64 + prev_delta - to_frame_idx as i64);
+		self.transport.set_target(0.0, self.ramp_frames);
+	}
 
-	let a = conv.floor() as TFrameIdx;
-	let diff = conv - conv.floor();
+	pub fn fade_out(&mut self) {
+		self.transport.set_target(0.0, self.ramp_frames);
+	}
 
-	let res_a = src.get_by_frame_i(a)?;
-	let res_b = src.get_by_frame_i(a + 1)?;
+	pub fn fade_in(&mut self) {
+		// The fade-in of a seek starts on its own once its fade-out is done.
+		if self.fade_out_delta.is_some() { return; }
 
-	let len = res_a.len().min(res_b.len());
-	let mut res = Vec::new();
-	res.reserve_exact(len);
+		self.transport.set_target(1.0, self.ramp_frames);
+	}
 
-	for i in 0..len {
-		let sp_diff = res_b[i] - res_a[i];
-		res.push(res_a[i] + (sp_diff as f64 * diff) as f32);
+	/// Whether the output has completely been faded out and is just silence.
+	pub fn is_faded_out(&self) -> bool {
+		self.transport.target() == 0.0 && self.transport.is_settled() && self.fade_out_delta.is_none()
 	}
 
-	Some(res)
-}
+	/// Drops the state kept for sources that are no longer part of the composition.
+	fn retain_sources(&mut self, sources: &[CompositionSrc]) {
+		self.src_gains.retain(|id, _| sources.iter().any(|s| s.id() == *id));
+		self.resamplers.retain(|id, _| sources.iter().any(|s| s.id() == *id));
+		self.matrices.retain(|id, _| sources.iter().any(|s| s.id() == *id));
+		self.faulted.retain(|id| sources.iter().any(|s| s.id() == *id));
+		self.src_effects.retain(|id, _| sources.iter().any(|s| s.id() == *id));
+		self.src_meters.retain(|id, _| sources.iter().any(|s| s.id() == *id));
+	}
 
-fn fetch_frame(cmp_src: &mut CompositionSrc, target_sample_rate: TFrameIdx, frame_idx: TFrameIdx) -> Option<Vec<TSample>> {
-	let conv_frame_i =
-			convert_sample_rates(target_sample_rate, frame_idx, cmp_src.src.sample_rate()) as i64
-			- cmp_src.composition_data.frame_offset;
+	/// Clears the state of the effects and the limiter so that nothing from before a jump in the timeline carries over.
+	fn reset_processors(&mut self) {
+		self.master_effects.reset();
+		self.limiter.reset();
+		for effects in self.src_effects.values_mut() {
+			effects.reset();
+		}
+	}
+}
 
-	if conv_frame_i < 0 { return None; }
+/// The level meter of a source, made anew if the source doesn't have one or the channels of the source have changed.
+fn src_meter<'a>(meters: &'a mut HashMap<TSrcID, LevelMeter>, sample_rate: TFrameIdx, cmp_src: &CompositionSrc) -> &'a mut LevelMeter {
+	let layout = cmp_src.src.channel_layout();
+	let meter = meters.entry(cmp_src.id()).or_insert_with(|| LevelMeter::new(sample_rate, layout));
 
-	if cmp_src.src.sample_rate() == target_sample_rate {
-		cmp_src.src.get_by_frame_i(conv_frame_i as TFrameIdx)
-	} else {
-		approximate_frame_linear(&mut cmp_src.src, target_sample_rate, frame_idx, cmp_src.composition_data.frame_offset)
+	if meter.channels() != layout.channels() as usize {
+		*meter = LevelMeter::new(sample_rate, layout);
 	}
+
+	meter
 }
 
-pub fn compute_eventual_frame(sources: &mut [CompositionSrc], channels: u8, sample_rate: TFrameIdx, frame_idx: TFrameIdx) -> Vec<TSample> {
-	let mut res = vec![0f32; channels as usize];
+/// Mixes the sources into `out`, a block of interleaved frames of the compositor starting at `frame_idx`.
+fn mix_block(sources: &mut [CompositionSrc], mix: &mut MixState, frame_idx: TFrameIdx, out: &mut [TSample]) {
+	let out_channels = mix.channel_layout.channels();
+	let frames = out.len() / out_channels as usize;
+	let any_solo = sources.iter().any(|s| s.composition_data.solo);
 
-	// Getting the output of each source
 	for cmp_src in sources.iter_mut() {
-		let conv_frame_i =
-			convert_sample_rates(sample_rate, frame_idx, cmp_src.src.sample_rate()) as i64
-			- cmp_src.composition_data.frame_offset;
+		// Finished sources aren't read anymore, while paused ones don't move towards their end
+		if let Some(play_len) = cmp_src.play_len().filter(|_| !cmp_src.composition_data.is_paused()) {
+			let pos = convert_sample_rates(mix.sample_rate, frame_idx, cmp_src.src.sample_rate()) as i64 - cmp_src.composition_data.frame_offset;
+
+			if play_len as i64 <= pos {
+				if !cmp_src.composition_data.is_finished() && !mix.finished.contains(&cmp_src.id()) {
+					mix.finished.push(cmp_src.id());
+				}
+
+				src_meter(&mut mix.src_meters, mix.sample_rate, cmp_src).push_silence(frames);
+				continue;
+			}
+		}
+
+		// Faulted sources aren't read anymore either, but the rest of the composition plays on
+		if cmp_src.composition_data.is_faulted() || mix.faulted.contains(&cmp_src.id()) {
+			src_meter(&mut mix.src_meters, mix.sample_rate, cmp_src).push_silence(frames);
+			continue;
+		}
 
-		if conv_frame_i < 0 { continue; }
+		let data = &cmp_src.composition_data;
+		let target_gain = if data.is_audible(any_solo) { data.amplification * data.normalization_gain() } else { 0.0 };
 
-		let val = fetch_frame(cmp_src, sample_rate, frame_idx);
+		let gain_ramp = mix.src_gains.entry(cmp_src.id()).or_insert_with(|| Ramp::new(target_gain));
+		gain_ramp.set_target(target_gain, mix.ramp_frames);
 
-		if let Some(src_res) = val {
-			for channel_i in 0..res.len() {
-				res[channel_i] += src_res[channel_i % src_res.len()] * cmp_src.composition_data.amplification;
+		if gain_ramp.is_settled() && gain_ramp.current() == 0.0 {
+			cmp_src.src.on_idle();
+			src_meter(&mut mix.src_meters, mix.sample_rate, cmp_src).push_silence(frames);
+			continue;
+		}
+
+		let in_channels = cmp_src.src.channels();
+		let buf_len = frames * in_channels as usize;
+		if mix.src_buf.len() < buf_len {
+			mix.src_buf.resize(buf_len, 0.0);
+		}
+
+		let quality = mix.resample_quality;
+		let resampler = mix.resamplers.entry(cmp_src.id()).or_insert_with(|| Resampler::new(quality));
+		// A panicking source would otherwise take the compositor down with the lock of the composition it holds
+		let sample_rate = mix.sample_rate;
+		let src_buf = &mut mix.src_buf[..buf_len];
+		let read = match catch_unwind(AssertUnwindSafe(|| fetch_block(cmp_src, resampler, sample_rate, frame_idx, src_buf))) {
+			Ok(read) => read,
+			Err(_) => {
+				log::error!("Source '{}' panicked while being read.", cmp_src.id());
+				mix.faulted.push(cmp_src.id());
+				src_meter(&mut mix.src_meters, mix.sample_rate, cmp_src).push_silence(frames);
+				continue;
 			}
+		};
+
+		// What was read before the source gave up is still played
+		if cmp_src.src.is_faulted() {
+			mix.faulted.push(cmp_src.id());
 		}
-	}
 
-	res
+		let src_effects = mix.src_effects.entry(cmp_src.id()).or_default();
+		src_effects.sync(&cmp_src.composition_data.effects, mix.sample_rate, cmp_src.src.channel_layout());
+		src_effects.process(&mut mix.src_buf[..read * in_channels as usize]);
+
+		let meter = src_meter(&mut mix.src_meters, mix.sample_rate, cmp_src);
+		meter.push(&mix.src_buf[..read * in_channels as usize]);
+		meter.push_silence(frames - read);
+
+		let matrix = match cmp_src.composition_data.mix_matrix {
+			Some(ref m) if m.in_channels() == in_channels && m.out_channels() == out_channels => m,
+			_ => {
+				let src_layout = cmp_src.src.channel_layout();
+				mix.matrices.entry(cmp_src.id()).or_insert_with(|| MixMatrix::between(src_layout, mix.channel_layout))
+			}
+		};
+
+		let input = &mix.src_buf[..read * in_channels as usize];
+		let output = &mut out[..read * out_channels as usize];
+
+		if gain_ramp.is_settled() {
+			matrix.apply_block_add(input, output, gain_ramp.current());
+		} else {
+			for (in_frame, out_frame) in input.chunks_exact(in_channels as usize).zip(output.chunks_exact_mut(out_channels as usize)) {
+				matrix.apply_add(in_frame, out_frame, gain_ramp.advance());
+			}
+
+			// Keeping the ramp in time even when the source ended in the middle of the block
+			for _ in read..frames {
+				gain_ramp.advance();
+			}
+		}
+	}
 }
 
-pub fn compute_frames<const BUF_SIZE: usize>(sources: &mut [CompositionSrc], channels: u8, sample_rate: TFrameIdx, amplification: f32, offset: TFrameIdx) -> [f32; BUF_SIZE] {
-	let mut res = [0.0; BUF_SIZE];
-	let n = BUF_SIZE / channels as usize;
+/// Whether compositors can compute buffers of `block_size` samples.
+pub fn is_valid_block_size(block_size: usize) -> bool {
+	(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size)
+}
 
-	for i in 0..n {
-		let frame = compute_eventual_frame(sources, channels, sample_rate, offset + i as TFrameIdx);
-		for (ch_i, v) in frame.into_iter().enumerate() {
-			res[i * channels as usize + ch_i] = v * amplification;
+/// Fills `res` with the frames of the sources starting at the frame `offset`, overwriting whatever it held.
+/// 
+/// Note: Only whole frames are computed, so the samples after the last whole frame are left silent.
+pub fn compute_frames(sources: &mut [CompositionSrc], mix: &mut MixState, offset: TFrameIdx, res: &mut [TSample]) {
+	let channels = mix.channel_layout.channels() as usize;
+	let n = res.len() / channels;
+	// The sources are mixed into the buffer, which the compositors reuse from one block to the next
+	res.fill(0.0);
+	let mut i = 0;
+
+	while i < n {
+		// While fading out for a seek the frames are still read from the old timeline, so the buffer is split where the fade-out ends.
+		let (len, delta) = match mix.fade_out_delta {
+			Some(_) if mix.transport.is_settled() => {
+				mix.fade_out_delta = None;
+				mix.transport.set_target(1.0, mix.ramp_frames);
+				mix.reset_processors();
+				continue;
+			},
+			Some(delta) => ((mix.transport.frames_left() as usize).min(n - i), delta),
+			None => (n - i, 0)
+		};
+
+		let block = &mut res[i * channels..(i + len) * channels];
+		mix_block(sources, mix, (offset + i as TFrameIdx).saturating_add_signed(delta), block);
+		mix.master_effects.process(block);
+
+		if mix.master.is_settled() && mix.transport.is_settled() {
+			let gain = mix.master.current() * mix.transport.current();
+			if gain != 1.0 {
+				for v in block.iter_mut() { *v *= gain; }
+			}
+		} else {
+			for frame in block.chunks_exact_mut(channels) {
+				let gain = mix.master.advance() * mix.transport.advance();
+				for v in frame.iter_mut() { *v *= gain; }
+			}
 		}
+
+		i += len;
 	}
 
-	res
+	mix.limiter.process(&mut res[..n * channels]);
+	mix.master_meter.push(&res[..n * channels]);
+	mix.retain_sources(sources);
 }
 
-/// Initiates a new compositor to work on a separate thread and returns a pointer to the first buffer node which is an entry to the audio stream.
-pub fn init_compositor_thread<const BUF_SIZE: usize>(sample_rate: TFrameIdx, cmp_state: TWrappedCompositionState) -> (CompositorData<BUF_SIZE>, Arc<CompositionBufferNode<BUF_SIZE>>) {
-	assert!(BUF_SIZE & 1 != 1);
-	let first_node: Arc<CompositionBufferNode<BUF_SIZE>>;
+/// Initiates a new compositor computing buffers of `block_size` samples to work on a separate thread and returns a cursor reading its output from the first buffer on.
+pub fn init_compositor_thread(sample_rate: TFrameIdx, block_size: usize, cmp_state: TWrappedCompositionState, resample_quality: ResampleQuality) -> (CompositorData, RingCursor) {
+	assert!(is_valid_block_size(block_size));
+	let mut writer;
+	let mut buf;
 	let channels;
+	let buf_len;
 	let cmp_id;
-	let amp;
+	let mut mix;
+	let first_frame_idx;
+	let first_change_idx;
 	{
 		let mut cmp = cmp_state.write().unwrap();
-		channels = cmp.get_channels();
+		let channel_layout = cmp.get_channel_layout();
+		channels = channel_layout.channels();
+		// Buffers always hold whole frames, and at least one of them for compositions of many channels
+		buf_len = (block_size - block_size % channels as usize).max(channels as usize);
+		writer = RingWriter::new(RING_BUFFERS, buf_len);
+		buf = vec![0.0; buf_len];
 		cmp_id = cmp.get_id().clone();
-		amp = cmp.get_amplification();
-		first_node = 
-			CompositionBufferNode::new(compute_frames::<BUF_SIZE>(&mut cmp.sources, channels.into(), sample_rate, amp, 0))
+		// Starting where the composition is rather than at its beginning, so that a compositor started for a composition
+		// that has been playing for a while (e.g. for an endpoint of another sample-rate) is in time with it
+		first_frame_idx = (cmp.get_time_sec() * sample_rate as f64) as TFrameIdx;
+		first_change_idx = cmp.config_change_idx;
+		mix = MixState::new(sample_rate, channel_layout, cmp.get_amplification(), cmp.get_ramp_ms(), resample_quality);
+		mix.set_effects(cmp.get_effects());
+		mix.set_limiter(cmp.get_limiter());
+		cmp.refresh_normalization();
+		compute_frames(cmp.sources_mut(), &mut mix, first_frame_idx, &mut buf);
+		writer.push(&buf);
+		cmp.record_levels(mix.take_levels());
+		cmp.finish_sources(&mix.take_finished());
+		cmp.fault_sources(&mix.take_faulted());
 	}
 	
+	// The cursor is made before the thread starts, as the compositor stops as soon as nothing reads it
+	let cursor = writer.ring().cursor();
+
 	// Saving the current thread id does not mean anything. Its just a valid ThreadId to put instead of mem::uninitialized until changing it after creating the compositor thread.
-	let state = Arc::new(Mutex::new(CompositorState::Active(thread::current().id(), first_node.clone())));
+	let state = Arc::new(Mutex::new(CompositorState::Active(thread::current().id(), writer.ring().clone())));
 	
 	let _state = state.clone();
-	let _sample_rate = sample_rate.clone();
-	let _channels = channels;
+	let _sample_rate = sample_rate;
 	let _cmp_id = cmp_id.clone();
-	let _amp = amp;
-	let _first_node = first_node.clone();
 	
 	let thread_handle = thread::Builder::new()
-		.name(format!("cmp-{}/{}", cmp_id, sample_rate))
+		.name(format!("cmp-{}/{}/{}", cmp_id, sample_rate, block_size))
 		.spawn(move || {
 			let mut start = Instant::now();
 
-			let mut node = _first_node;
-			let frames_in_buf: TFrameIdx = (BUF_SIZE / channels as usize) as TFrameIdx;
-			let mut frame_idx: TFrameIdx = 0;
-			let mut change_idx = 0;
-			let mut secs_sent = 0.0;
+			let frames_in_buf: TFrameIdx = (buf_len / channels as usize) as TFrameIdx;
+			let mut frame_idx: TFrameIdx = first_frame_idx + frames_in_buf;
+			let mut change_idx = first_change_idx;
+			let mut secs_sent = 1.0 / (sample_rate as f32 / frames_in_buf as f32);
 			
 			loop {
 				// This condition ensures the compositor being killed in case of it not being used by anything
-				// The compositor and the compositor registry each have a pointer to node so if the strong count of node is less than or equal to 2 it is not being used and so it gets killed.
-				if Arc::strong_count(&node) < 3 {
-					log::debug!("Killing compositor with state id of '{}' and sample-rate of '{}'", _cmp_id, _sample_rate);
-					*state.lock().unwrap() = CompositorState::Killed;
-					return;
+				// The state is locked while checking so that the registry can't hand out a new cursor of a compositor about to be killed.
+				{
+					let mut state = state.lock().unwrap();
+					if writer.ring().readers() == 0 {
+						log::debug!("Killing compositor with state id of '{}' and sample-rate of '{}'", _cmp_id, _sample_rate);
+						*state = CompositorState::Killed;
+						GLOBAL_EVENT_BUS.publish(Event::CompositorKilled { cmp_id: _cmp_id, sample_rate: _sample_rate });
+						return;
+					}
 				}
 				
 				// This section is dedicated to preventing the compositor from computing too much audio as adjustments can be made live.
@@ -212,30 +512,50 @@ pub fn init_compositor_thread<const BUF_SIZE: usize>(sample_rate: TFrameIdx, cmp
 				
 				let mut cmp = cmp_state.write().unwrap();
 				if cmp.is_paused() {
-					drop(cmp);
-					sleep(Duration::from_secs_f32(0.05));
-					continue;
+					// One last buffer is computed while pausing so that the audio fades out instead of stopping abruptly.
+					if mix.is_faded_out() {
+						for cmp_src in cmp.sources_mut() {
+							cmp_src.src.on_idle();
+						}
+
+						drop(cmp);
+						sleep(Duration::from_secs_f32(0.05));
+						continue;
+					}
+
+					mix.fade_out();
+				} else {
+					mix.fade_in();
 				}
 
 				if change_idx < cmp.config_change_idx {
+					let new_frame_idx = (cmp.get_time_sec() as f32 * sample_rate as f32) as TFrameIdx;
+					mix.begin_seek(frame_idx, new_frame_idx);
+
 					start = Instant::now();
 					secs_sent = 0.0;
-					frame_idx = (cmp.get_time_sec() as f32 * sample_rate as f32) as TFrameIdx;
+					frame_idx = new_frame_idx;
 					change_idx = cmp.config_change_idx;
 				}
 
-				node = node.push_next(
-					compute_frames::<BUF_SIZE>(&mut cmp.sources, _channels, sample_rate, _amp, frame_idx)
-				);
+				mix.set_ramp_ms(cmp.get_ramp_ms());
+				mix.set_amplification(cmp.get_amplification());
+				mix.set_effects(cmp.get_effects());
+				mix.set_limiter(cmp.get_limiter());
+				cmp.refresh_normalization();
+
+				compute_frames(cmp.sources_mut(), &mut mix, frame_idx, &mut buf);
+				writer.push(&buf);
+
+				cmp.record_gain_reduction(mix.take_gain_reduction_db());
+				cmp.record_levels(mix.take_levels());
+				cmp.finish_sources(&mix.take_finished());
+				cmp.fault_sources(&mix.take_faulted());
 
 				drop(cmp);
 
 				secs_sent += 1.0 / (sample_rate as f32 / frames_in_buf as f32);
 				frame_idx += frames_in_buf;
-
-				if let CompositorState::Active(_, ref mut state_buff_p) = *state.lock().unwrap() {
-					*state_buff_p = node.clone();
-				}
 			}
 		}).unwrap();
 
@@ -243,5 +563,5 @@ pub fn init_compositor_thread<const BUF_SIZE: usize>(sample_rate: TFrameIdx, cmp
 		*thread_id = thread_handle.thread().id();
 	}
 
-	(CompositorData::new(cmp_id, sample_rate, _state), first_node)
+	(CompositorData::new(cmp_id, sample_rate, block_size, _state), cursor)
 }
\ No newline at end of file
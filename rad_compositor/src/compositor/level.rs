@@ -0,0 +1,100 @@
+// Level meters of the sources and the output of the compositors, for showing what's flowing through them without listening.
+
+use crate::{channel_layout::ChannelLayout, loudness::{meter::LoudnessMeter, TLufs}, source::{TFrameIdx, TSample}};
+
+/// Levels are never reported lower than this, which is what silence shows as.
+pub const LEVEL_FLOOR_DB: f32 = -100.0;
+
+fn to_db(amplitude: f32) -> f32 {
+	(20.0 * amplitude.log10()).max(LEVEL_FLOOR_DB)
+}
+
+/// The levels of the last buffer computed by a compositor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Levels {
+	/// The highest absolute sample of each channel in dBFS.
+	pub peak_db: Vec<f32>,
+	/// The RMS of each channel in dBFS.
+	pub rms_db: Vec<f32>,
+	/// The loudness of the last 3 s, `None` until 100 ms has been measured.
+	pub short_term_lufs: Option<TLufs>
+}
+
+impl Levels {
+	/// The levels of silence in `channels` channels.
+	pub fn silent(channels: usize) -> Self {
+		Levels {
+			peak_db: vec![LEVEL_FLOOR_DB; channels],
+			rms_db: vec![LEVEL_FLOOR_DB; channels],
+			short_term_lufs: None
+		}
+	}
+
+	/// The highest peak of all the channels.
+	pub fn max_peak_db(&self) -> f32 {
+		self.peak_db.iter().copied().fold(LEVEL_FLOOR_DB, f32::max)
+	}
+}
+
+/// Measures the blocks pushed into it until the levels get taken.
+pub struct LevelMeter {
+	channels: usize,
+	peaks: Vec<f32>,
+	squares: Vec<f64>,
+	frames: usize,
+	loudness: LoudnessMeter
+}
+
+impl LevelMeter {
+	pub fn new(sample_rate: TFrameIdx, layout: ChannelLayout) -> Self {
+		let channels = layout.channels() as usize;
+
+		LevelMeter {
+			channels,
+			peaks: vec![0.0; channels],
+			squares: vec![0.0; channels],
+			frames: 0,
+			loudness: LoudnessMeter::short_term_only(sample_rate, layout)
+		}
+	}
+
+	pub fn channels(&self) -> usize {
+		self.channels
+	}
+
+	/// Feeds a block of interleaved frames to the meter.
+	pub fn push(&mut self, block: &[TSample]) {
+		for frame in block.chunks_exact(self.channels) {
+			for (c, v) in frame.iter().enumerate() {
+				self.peaks[c] = self.peaks[c].max(v.abs());
+				self.squares[c] += (*v as f64) * (*v as f64);
+			}
+		}
+
+		self.frames += block.len() / self.channels;
+		self.loudness.push(block);
+	}
+
+	/// Counts `frames` frames of silence, e.g. for a source that wasn't read as it's paused.
+	pub fn push_silence(&mut self, frames: usize) {
+		self.frames += frames;
+		self.loudness.push_silence(frames);
+	}
+
+	/// The levels of what has been pushed since the last call.
+	pub fn take_levels(&mut self) -> Levels {
+		let frames = self.frames.max(1) as f64;
+
+		let levels = Levels {
+			peak_db: self.peaks.iter().map(|p| to_db(*p)).collect(),
+			rms_db: self.squares.iter().map(|s| to_db((s / frames).sqrt() as f32)).collect(),
+			short_term_lufs: self.loudness.short_term().map(|lufs| lufs.max(LEVEL_FLOOR_DB))
+		};
+
+		self.peaks.fill(0.0);
+		self.squares.fill(0.0);
+		self.frames = 0;
+
+		levels
+	}
+}
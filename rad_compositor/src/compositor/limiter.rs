@@ -0,0 +1,319 @@
+// The output stage of the compositors, which keeps the mix under a ceiling before the adapters convert it to integers and
+// would otherwise hard-clip it.
+
+use std::{collections::VecDeque, f32::consts::PI, fmt::Display, str::FromStr};
+
+use crate::source::{TFrameIdx, TSample};
+
+const DEFAULT_CEILING_DB: f32 = -1.0;
+const DEFAULT_LOOKAHEAD_MS: f32 = 5.0;
+const DEFAULT_RELEASE_MS: f32 = 50.0;
+
+/// Where the soft clipper starts bending the signal, relative to the ceiling (about -3dB).
+const SOFT_CLIP_KNEE: f32 = 0.7;
+
+/// Taps of each phase of the interpolation filter used for finding the peaks between samples.
+const TRUE_PEAK_TAPS: usize = 8;
+/// The points checked between every two samples, which makes the detection 4x oversampled.
+const TRUE_PEAK_PHASES: [f32; 3] = [0.25, 0.5, 0.75];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LimiterConfig {
+	/// The output is left as it is.
+	#[default]
+	Off,
+	/// Bends the peaks smoothly towards the ceiling, which is cheap and adds no latency but distorts a bit.
+	SoftClip { ceiling_db: f32 },
+	/// Turns the gain down ahead of the peaks so that the output never goes over the ceiling, which delays the output by the look-ahead.
+	Brickwall {
+		ceiling_db: f32,
+		lookahead_ms: f32,
+		release_ms: f32,
+		/// Whether the peaks between the samples are considered as well, as those are what a DAC will actually output.
+		true_peak: bool
+	}
+}
+
+impl Display for LimiterConfig {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Off => write!(f, "off"),
+			Self::SoftClip { ceiling_db } => write!(f, "soft-clip:ceiling={}", ceiling_db),
+			Self::Brickwall { ceiling_db, lookahead_ms, release_ms, true_peak } => write!(
+				f, "brickwall:ceiling={},lookahead={},release={},true-peak={}",
+				ceiling_db, lookahead_ms, release_ms, if *true_peak { "on" } else { "off" }
+			)
+		}
+	}
+}
+
+/// Written as `off`, `soft-clip[:ceiling={db}]`, or `brickwall[:ceiling={db},lookahead={ms},release={ms},true-peak={on|off}]`.
+impl FromStr for LimiterConfig {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, args) = s.split_once(':').unwrap_or((s, ""));
+		let args = args.split(',')
+			.filter(|arg| !arg.is_empty())
+			.map(|arg| arg.split_once('=').ok_or(()))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let num = |v: &str| v.parse::<f32>().ok().filter(|v| v.is_finite()).ok_or(());
+
+		let (mut ceiling_db, mut lookahead_ms, mut release_ms, mut true_peak) = (DEFAULT_CEILING_DB, DEFAULT_LOOKAHEAD_MS, DEFAULT_RELEASE_MS, true);
+		for (key, val) in args {
+			match (name, key) {
+				("off", _) => { return Err(()); },
+				(_, "ceiling") => ceiling_db = num(val)?,
+				("brickwall", "lookahead") => lookahead_ms = num(val)?,
+				("brickwall", "release") => release_ms = num(val)?,
+				("brickwall", "true-peak") => true_peak = match val {
+					"on" => true,
+					"off" => false,
+					_ => { return Err(()); }
+				},
+				_ => { return Err(()); }
+			}
+		}
+
+		if 0.0 < ceiling_db || lookahead_ms <= 0.0 || release_ms < 0.0 { return Err(()); }
+
+		match name {
+			"off" => Ok(Self::Off),
+			"soft-clip" => Ok(Self::SoftClip { ceiling_db }),
+			"brickwall" => Ok(Self::Brickwall { ceiling_db, lookahead_ms, release_ms, true_peak }),
+			_ => Err(())
+		}
+	}
+}
+
+fn db_to_amp(db: f32) -> f32 {
+	10f32.powf(db / 20.0)
+}
+
+/// Estimates the peaks between samples by interpolating a few points between each two samples of every channel.
+struct TruePeakDetector {
+	/// The most recent samples of each channel, oldest first.
+	history: Vec<[TSample; TRUE_PEAK_TAPS]>,
+	/// Windowed sinc coefficients for each point checked between the two samples in the middle of the history.
+	phases: Vec<[f32; TRUE_PEAK_TAPS]>
+}
+
+impl TruePeakDetector {
+	/// The detection is about the samples this many frames back, as it needs the samples around them.
+	const LATENCY: usize = TRUE_PEAK_TAPS / 2;
+
+	fn new(channels: usize) -> Self {
+		let half = (TRUE_PEAK_TAPS / 2) as f32;
+
+		let phases = TRUE_PEAK_PHASES.iter().map(|frac| {
+			let mut taps = [0.0; TRUE_PEAK_TAPS];
+			for (k, tap) in taps.iter_mut().enumerate() {
+				let t = k as f32 - (half - 1.0 + frac);
+				let sinc = if t == 0.0 { 1.0 } else { (PI * t).sin() / (PI * t) };
+				let window = 0.5 * (1.0 + (PI * t / half).cos());
+				*tap = sinc * window;
+			}
+
+			taps
+		}).collect();
+
+		TruePeakDetector {
+			history: vec![[0.0; TRUE_PEAK_TAPS]; channels],
+			phases
+		}
+	}
+
+	/// Pushes a frame and returns the peak of the frame `LATENCY` frames back along with the peaks right after it.
+	fn push(&mut self, frame: &[TSample]) -> f32 {
+		let mut peak = 0f32;
+
+		for (history, v) in self.history.iter_mut().zip(frame.iter()) {
+			history.copy_within(1.., 0);
+			history[TRUE_PEAK_TAPS - 1] = *v;
+
+			peak = peak.max(history[TRUE_PEAK_TAPS - 1 - Self::LATENCY].abs());
+			for taps in self.phases.iter() {
+				let interpolated: f32 = taps.iter().zip(history.iter()).map(|(t, s)| t * s).sum();
+				peak = peak.max(interpolated.abs());
+			}
+		}
+
+		peak
+	}
+
+	fn reset(&mut self) {
+		self.history.fill([0.0; TRUE_PEAK_TAPS]);
+	}
+}
+
+/// A look-ahead limiter, the gain is the moving average of the minimum gain needed over the look-ahead window,
+/// which reaches the needed gain exactly when the peak leaves the delay line.
+struct Brickwall {
+	ceiling: f32,
+	channels: usize,
+	/// Length of the windows of the minimum and the average, one more than the look-ahead so that the gain is reached right when the frame leaves.
+	window: usize,
+	release_coeff: f32,
+	true_peak: Option<TruePeakDetector>,
+	/// The delayed frames, interleaved.
+	delay: Vec<TSample>,
+	delay_pos: usize,
+	/// Indexes and values of the needed gains that can still become the minimum of the window, increasing from front to back.
+	min_window: VecDeque<(u64, f32)>,
+	/// The window minimums that are being averaged.
+	avg_window: Vec<f32>,
+	avg_sum: f64,
+	frame_count: u64,
+	gain: f32
+}
+
+impl Brickwall {
+	fn new(ceiling_db: f32, lookahead_ms: f32, release_ms: f32, true_peak: bool, sample_rate: TFrameIdx, channels: usize) -> Self {
+		let lookahead = ((lookahead_ms / 1000.0 * sample_rate as f32) as usize).max(1);
+		let true_peak = true_peak.then(|| TruePeakDetector::new(channels));
+		let delay_frames = lookahead + true_peak.as_ref().map_or(0, |_| TruePeakDetector::LATENCY);
+		let window = lookahead + 1;
+		let release_frames = release_ms / 1000.0 * sample_rate as f32;
+
+		Brickwall {
+			ceiling: db_to_amp(ceiling_db),
+			channels,
+			window,
+			release_coeff: if release_frames <= 0.0 { 0.0 } else { (-1.0 / release_frames).exp() },
+			true_peak,
+			delay: vec![0.0; delay_frames * channels],
+			delay_pos: 0,
+			min_window: VecDeque::with_capacity(window + 1),
+			avg_window: vec![1.0; window],
+			avg_sum: window as f64,
+			frame_count: 0,
+			gain: 1.0
+		}
+	}
+
+	/// Limits the frame in place (replacing it with the delayed one) and returns the gain applied to it.
+	fn process_frame(&mut self, frame: &mut [TSample]) -> f32 {
+		let peak = match self.true_peak {
+			Some(ref mut detector) => detector.push(frame),
+			None => frame.iter().fold(0f32, |acc, v| acc.max(v.abs()))
+		};
+		let needed = if self.ceiling < peak { self.ceiling / peak } else { 1.0 };
+
+		// Sliding minimum over the look-ahead window
+		let idx = self.frame_count;
+		while self.min_window.back().is_some_and(|(_, g)| needed <= *g) {
+			self.min_window.pop_back();
+		}
+		self.min_window.push_back((idx, needed));
+		while self.min_window.front().is_some_and(|(i, _)| *i + (self.window as u64) <= idx) {
+			self.min_window.pop_front();
+		}
+		let window_min = self.min_window.front().unwrap().1;
+
+		// Moving average of the minimums
+		let avg_i = (idx % self.window as u64) as usize;
+		self.avg_sum += window_min as f64 - self.avg_window[avg_i] as f64;
+		self.avg_window[avg_i] = window_min;
+		let target = (self.avg_sum / self.window as f64) as f32;
+
+		self.gain = if target < self.gain { target } else { target + self.release_coeff * (self.gain - target) };
+		self.frame_count += 1;
+
+		// Swapping the frame with the oldest one in the delay line
+		let delay_frames = self.delay.len() / self.channels;
+		let start = self.delay_pos * self.channels;
+		for (v, delayed) in frame.iter_mut().zip(self.delay[start..start + self.channels].iter_mut()) {
+			std::mem::swap(v, delayed);
+			*v = (*v * self.gain).clamp(-self.ceiling, self.ceiling);
+		}
+		self.delay_pos = (self.delay_pos + 1) % delay_frames;
+
+		self.gain
+	}
+
+	fn reset(&mut self) {
+		if let Some(ref mut detector) = self.true_peak { detector.reset(); }
+		self.delay.fill(0.0);
+		self.min_window.clear();
+		self.avg_window.fill(1.0);
+		self.avg_sum = self.window as f64;
+		self.gain = 1.0;
+	}
+}
+
+enum Stage {
+	Off,
+	SoftClip { ceiling: f32 },
+	Brickwall(Box<Brickwall>)
+}
+
+/// The limiter a compositor runs its output through, built from the `LimiterConfig` of the composition.
+pub struct Limiter {
+	config: LimiterConfig,
+	stage: Stage,
+	channels: usize,
+	/// The lowest gain applied since the gain reduction was last taken.
+	min_gain: f32
+}
+
+impl Limiter {
+	pub fn new(config: LimiterConfig, sample_rate: TFrameIdx, channels: u8) -> Self {
+		let stage = match config {
+			LimiterConfig::Off => Stage::Off,
+			LimiterConfig::SoftClip { ceiling_db } => Stage::SoftClip { ceiling: db_to_amp(ceiling_db) },
+			LimiterConfig::Brickwall { ceiling_db, lookahead_ms, release_ms, true_peak } =>
+				Stage::Brickwall(Box::new(Brickwall::new(ceiling_db, lookahead_ms, release_ms, true_peak, sample_rate, channels as usize)))
+		};
+
+		Limiter {
+			config,
+			stage,
+			channels: channels as usize,
+			min_gain: 1.0
+		}
+	}
+
+	pub fn config(&self) -> LimiterConfig {
+		self.config
+	}
+
+	pub fn process(&mut self, block: &mut [TSample]) {
+		match self.stage {
+			Stage::Off => {},
+			Stage::SoftClip { ceiling } => {
+				let knee = ceiling * SOFT_CLIP_KNEE;
+
+				for v in block.iter_mut() {
+					let abs = v.abs();
+					if abs <= knee { continue; }
+
+					let clipped = knee + (ceiling - knee) * ((abs - knee) / (ceiling - knee)).tanh();
+					self.min_gain = self.min_gain.min(clipped / abs);
+					*v = clipped.copysign(*v);
+				}
+			},
+			Stage::Brickwall(ref mut brickwall) => {
+				for frame in block.chunks_exact_mut(self.channels) {
+					let gain = brickwall.process_frame(frame);
+					self.min_gain = self.min_gain.min(gain);
+				}
+			}
+		}
+	}
+
+	/// Forgets the audio that is still in the look-ahead, used when the timeline jumps.
+	pub fn reset(&mut self) {
+		if let Stage::Brickwall(ref mut brickwall) = self.stage {
+			brickwall.reset();
+		}
+	}
+
+	/// The most gain reduction (in positive dBs) applied since the last call.
+	pub fn take_reduction_db(&mut self) -> f32 {
+		let reduction = if self.min_gain < 1.0 { -20.0 * self.min_gain.log10() } else { 0.0 };
+		self.min_gain = 1.0;
+
+		reduction
+	}
+}
@@ -0,0 +1,61 @@
+/// A linear ramp for moving a parameter (e.g. gain) towards its target value over a number of frames instead of jumping to it,
+/// which is what keeps live adjustments from producing zipper noise and clicks.
+#[derive(Clone, Copy, Debug)]
+pub struct Ramp {
+	current: f32,
+	target: f32,
+	step: f32
+}
+
+impl Ramp {
+	pub fn new(value: f32) -> Self {
+		Ramp {
+			current: value,
+			target: value,
+			step: 0.0
+		}
+	}
+
+	/// Starts moving towards `target` so that it's reached after `len_frames` calls to `advance`.
+	/// Setting the same target again doesn't restart the ramp.
+	pub fn set_target(&mut self, target: f32, len_frames: u32) {
+		if target == self.target { return; }
+
+		self.target = target;
+		self.step = (target - self.current) / len_frames.max(1) as f32;
+	}
+
+	/// Advances the ramp by one frame and returns the new value.
+	pub fn advance(&mut self) -> f32 {
+		if self.current != self.target {
+			self.current += self.step;
+
+			let has_passed_target = (0.0 < self.step && self.target <= self.current) || (self.step < 0.0 && self.current <= self.target);
+			if has_passed_target || self.step == 0.0 {
+				self.current = self.target;
+			}
+		}
+
+		self.current
+	}
+
+	pub fn current(&self) -> f32 {
+		self.current
+	}
+
+	pub fn target(&self) -> f32 {
+		self.target
+	}
+
+	/// How many more calls to `advance` it takes to reach the target.
+	pub fn frames_left(&self) -> u32 {
+		if self.is_settled() { return 0; }
+		if self.step == 0.0 { return 1; }
+
+		((self.target - self.current) / self.step).ceil().max(1.0) as u32
+	}
+
+	pub fn is_settled(&self) -> bool {
+		self.current == self.target
+	}
+}
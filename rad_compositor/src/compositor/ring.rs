@@ -0,0 +1,277 @@
+// The bounded buffer the compositors put their output in, read by the adapters and the nested sources through cursors of their own.
+
+use std::{sync::{atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering}, Arc, Condvar, Mutex}, time::{Duration, Instant}};
+
+/// A slot holding no buffer, or one that is being overwritten.
+const SLOT_EMPTY: u64 = 0;
+
+/// Why a cursor couldn't give a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadError {
+	/// The next buffer hasn't been computed yet.
+	Empty,
+	/// The next buffer wasn't computed within the timeout.
+	Timeout,
+	/// The reader fell so far behind that `missed` buffers were overwritten before it got to them.
+	/// The cursor has been moved on to the latest buffer, so reading again goes on from there.
+	Overrun { missed: u64 },
+	/// The compositor has stopped and no more buffers are coming.
+	Closed
+}
+
+struct Slot {
+	/// `SLOT_EMPTY`, or the index of the buffer in the slot plus one.
+	seq: AtomicU64,
+	len: AtomicUsize,
+	/// The bits of the samples, as they're read while they may get overwritten.
+	data: Box<[AtomicU32]>
+}
+
+/// A ring of the last buffers computed by a compositor, written by a single `RingWriter` and read by any number of `RingCursor`s.
+///
+/// Writing never waits on the readers: a reader that falls more than the capacity of the ring behind gets `ReadError::Overrun`
+/// instead of holding on to the buffers it hasn't read yet.
+pub struct CompositionRing {
+	slots: Box<[Slot]>,
+	/// The index of the next buffer to be written, so the number of buffers written so far.
+	head: AtomicU64,
+	readers: AtomicUsize,
+	closed: AtomicBool,
+	/// The number of readers waiting for the next buffer, so the writer only wakes them up when there are any.
+	waiters: AtomicUsize,
+	wait_lock: Mutex<()>,
+	wait_cnd: Condvar
+}
+
+impl CompositionRing {
+	/// The number of buffers that fit in the ring.
+	pub fn capacity(&self) -> usize {
+		self.slots.len()
+	}
+
+	/// The number of samples that fit in each buffer.
+	pub fn block_size(&self) -> usize {
+		self.slots[0].data.len()
+	}
+
+	/// The number of cursors reading the ring.
+	pub fn readers(&self) -> usize {
+		self.readers.load(Ordering::SeqCst)
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.closed.load(Ordering::SeqCst)
+	}
+
+	/// Makes a cursor reading the latest buffer next, or the first one if nothing has been written yet.
+	pub fn cursor(self: &Arc<Self>) -> RingCursor {
+		self.readers.fetch_add(1, Ordering::SeqCst);
+
+		RingCursor {
+			ring: self.clone(),
+			next: self.head.load(Ordering::Acquire).saturating_sub(1),
+			buf: vec![0.0; self.block_size()],
+			len: 0
+		}
+	}
+
+	fn wake_readers(&self) {
+		if self.waiters.load(Ordering::SeqCst) > 0 {
+			let _lock = self.wait_lock.lock().unwrap();
+			self.wait_cnd.notify_all();
+		}
+	}
+}
+
+/// The only handle that writes into a `CompositionRing`, the ring gets closed when it's dropped.
+pub struct RingWriter {
+	ring: Arc<CompositionRing>
+}
+
+impl RingWriter {
+	/// Makes a ring of `capacity` buffers of up to `buf_size` samples each.
+	pub fn new(capacity: usize, buf_size: usize) -> Self {
+		assert!(capacity > 1 && buf_size > 0);
+
+		let slots = (0..capacity).map(|_| Slot {
+			seq: AtomicU64::new(SLOT_EMPTY),
+			len: AtomicUsize::new(0),
+			data: (0..buf_size).map(|_| AtomicU32::new(0)).collect()
+		}).collect();
+
+		RingWriter {
+			ring: Arc::new(CompositionRing {
+				slots,
+				head: AtomicU64::new(0),
+				readers: AtomicUsize::new(0),
+				closed: AtomicBool::new(false),
+				waiters: AtomicUsize::new(0),
+				wait_lock: Mutex::new(()),
+				wait_cnd: Condvar::new()
+			})
+		}
+	}
+
+	pub fn ring(&self) -> &Arc<CompositionRing> {
+		&self.ring
+	}
+
+	/// Writes the next buffer over the oldest one and wakes up the readers waiting for it.
+	pub fn push(&mut self, buf: &[f32]) {
+		let ring = &self.ring;
+		let idx = ring.head.load(Ordering::Relaxed);
+		let slot = &ring.slots[(idx % ring.slots.len() as u64) as usize];
+		assert!(buf.len() <= slot.data.len());
+
+		// The slot is marked as empty while it's written, so the readers that were reading it notice it changed
+		slot.seq.store(SLOT_EMPTY, Ordering::Relaxed);
+		fence(Ordering::Release);
+
+		for (dst, v) in slot.data.iter().zip(buf) {
+			dst.store(v.to_bits(), Ordering::Relaxed);
+		}
+		slot.len.store(buf.len(), Ordering::Relaxed);
+
+		slot.seq.store(idx + 1, Ordering::Release);
+		ring.head.store(idx + 1, Ordering::SeqCst);
+
+		ring.wake_readers();
+	}
+}
+
+impl Drop for RingWriter {
+	fn drop(&mut self) {
+		self.ring.closed.store(true, Ordering::SeqCst);
+		self.ring.wake_readers();
+	}
+}
+
+/// Reads the buffers of a `CompositionRing` one after another from a position of its own.
+///
+/// The compositor keeps running for as long as there are cursors reading its ring.
+pub struct RingCursor {
+	ring: Arc<CompositionRing>,
+	/// The index of the buffer that is read next.
+	next: u64,
+	/// The last buffer read, copied out of the ring.
+	buf: Vec<f32>,
+	len: usize
+}
+
+impl RingCursor {
+	pub fn ring(&self) -> &Arc<CompositionRing> {
+		&self.ring
+	}
+
+	/// The last buffer read.
+	///
+	/// Note: The buffer always holds whole frames, so its length depends on the channel count.
+	pub fn buf(&self) -> &[f32] {
+		&self.buf[..self.len]
+	}
+
+	/// Gives the next buffer if it has been computed, without waiting for it.
+	pub fn try_read(&mut self) -> Result<&[f32], ReadError> {
+		let head = self.ring.head.load(Ordering::SeqCst);
+
+		if self.next >= head {
+			return Err(if self.ring.is_closed() { ReadError::Closed } else { ReadError::Empty });
+		}
+
+		if head - self.next > self.ring.slots.len() as u64 {
+			return Err(self.overrun(head));
+		}
+
+		let slot = &self.ring.slots[(self.next % self.ring.slots.len() as u64) as usize];
+		let seq = slot.seq.load(Ordering::Acquire);
+		if seq != self.next + 1 {
+			return Err(self.overrun(self.ring.head.load(Ordering::SeqCst)));
+		}
+
+		let len = slot.len.load(Ordering::Relaxed);
+		for (dst, v) in self.buf.iter_mut().zip(&slot.data[..len]) {
+			*dst = f32::from_bits(v.load(Ordering::Relaxed));
+		}
+
+		// The writer got to the slot while it was being copied
+		fence(Ordering::Acquire);
+		if slot.seq.load(Ordering::Relaxed) != seq {
+			return Err(self.overrun(self.ring.head.load(Ordering::SeqCst)));
+		}
+
+		self.next += 1;
+		self.len = len;
+
+		Ok(self.buf())
+	}
+
+	/// Gives the next buffer, waiting up to `timeout` for it to be computed.
+	pub fn read_timeout(&mut self, timeout: Duration) -> Result<&[f32], ReadError> {
+		self.read_until(Some(Instant::now() + timeout))
+	}
+
+	/// Gives the next buffer, waiting for as long as it takes for it to be computed or for the compositor to stop.
+	pub fn read(&mut self) -> Result<&[f32], ReadError> {
+		self.read_until(None)
+	}
+
+	/// Skips the buffers that have already been computed, so that what's read next is live.
+	pub fn skip_to_head(&mut self) {
+		self.next = self.ring.head.load(Ordering::SeqCst);
+	}
+
+	fn read_until(&mut self, deadline: Option<Instant>) -> Result<&[f32], ReadError> {
+		let ring = self.ring.clone();
+		let is_ready = |next: u64| ring.head.load(Ordering::SeqCst) > next || ring.is_closed();
+
+		while !is_ready(self.next) {
+			ring.waiters.fetch_add(1, Ordering::SeqCst);
+			let lock = ring.wait_lock.lock().unwrap();
+
+			// Checked again while holding the lock, so the wake-up of the writer can't be missed
+			let timed_out = match (is_ready(self.next), deadline) {
+				(true, _) => false,
+				(false, None) => { drop(ring.wait_cnd.wait(lock).unwrap()); false },
+				(false, Some(deadline)) => match deadline.checked_duration_since(Instant::now()) {
+					Some(left) => ring.wait_cnd.wait_timeout(lock, left).unwrap().1.timed_out(),
+					None => true
+				}
+			};
+
+			ring.waiters.fetch_sub(1, Ordering::SeqCst);
+
+			if timed_out && !is_ready(self.next) {
+				return Err(ReadError::Timeout);
+			}
+		}
+
+		self.try_read()
+	}
+
+	fn overrun(&mut self, head: u64) -> ReadError {
+		let missed = head.saturating_sub(1) - self.next;
+		log::warn!("A reader of a compositor fell behind and missed {} buffers.", missed);
+		self.next = head.saturating_sub(1);
+
+		ReadError::Overrun { missed }
+	}
+}
+
+impl Clone for RingCursor {
+	fn clone(&self) -> Self {
+		self.ring.readers.fetch_add(1, Ordering::SeqCst);
+
+		RingCursor {
+			ring: self.ring.clone(),
+			next: self.next,
+			buf: self.buf.clone(),
+			len: self.len
+		}
+	}
+}
+
+impl Drop for RingCursor {
+	fn drop(&mut self) {
+		self.ring.readers.fetch_sub(1, Ordering::SeqCst);
+	}
+}
@@ -0,0 +1,245 @@
+// Processing applied to the frames of single sources and to the mix of whole compositions.
+//
+// Compositions only hold the descriptions of their effects (`EffectChain`). Each compositor builds its own processors out of them,
+// as every compositor runs at its own sample-rate and the processors keep state of their own (e.g. filter memories).
+
+use std::{fmt::Display, str::FromStr, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use crate::{channel_layout::ChannelLayout, source::{TFrameIdx, TSample}};
+use biquad::{BiquadCoeffs, BiquadEffect, EqBand};
+use compressor::{Compressor, CompressorParams};
+use pan::Pan;
+
+pub mod biquad;
+pub mod compressor;
+pub mod pan;
+
+/// The Q of a Butterworth filter, used when a filter is given no Q.
+const DEFAULT_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+static CHAIN_VERSION_TO_ALLOCATE: AtomicU64 = AtomicU64::new(1);
+
+/// A processor working on blocks of interleaved frames.
+pub trait Effect: Send {
+	/// Processes the frames of the block in place.
+	fn process(&mut self, block: &mut [TSample]);
+
+	/// Forgets the state built up from the previous blocks, used when the audio jumps (e.g. on seeks).
+	fn reset(&mut self) {}
+}
+
+/// The description of an effect which the processors of the compositors are built from.
+///
+/// The `Display` implementation is what the effect is listed as.
+pub trait EffectDesc: Display + Send + Sync {
+	/// Builds a processor for frames of `layout` at `sample_rate`.
+	fn build(&self, sample_rate: TFrameIdx, layout: ChannelLayout) -> Box<dyn Effect>;
+}
+
+/// An ordered list of effects, the output of each effect being the input of the next one.
+#[derive(Clone, Default)]
+pub struct EffectChain {
+	effects: Vec<Arc<dyn EffectDesc>>,
+	/// Different for every chain that is made, which is how compositors know when to rebuild their processors.
+	version: u64
+}
+
+impl EffectChain {
+	pub fn new(effects: Vec<Arc<dyn EffectDesc>>) -> Self {
+		EffectChain {
+			effects,
+			version: CHAIN_VERSION_TO_ALLOCATE.fetch_add(1, Ordering::Relaxed)
+		}
+	}
+
+	/// Makes a chain out of the specs of built-in effects (see `BuiltinEffect`), `None` if any of them is invalid.
+	pub fn parse<'a>(specs: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+		let effects = specs.into_iter()
+			.map(|spec| spec.parse::<BuiltinEffect>().ok().map(|e| Arc::new(e) as Arc<dyn EffectDesc>))
+			.collect::<Option<Vec<_>>>()?;
+
+		Some(Self::new(effects))
+	}
+
+	pub fn effects(&self) -> &[Arc<dyn EffectDesc>] {
+		&self.effects
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.effects.is_empty()
+	}
+
+	pub fn version(&self) -> u64 {
+		self.version
+	}
+
+	/// The listing of each effect of the chain.
+	pub fn specs(&self) -> Vec<String> {
+		self.effects.iter().map(|e| e.to_string()).collect()
+	}
+}
+
+/// The processors a compositor has built from an `EffectChain`.
+#[derive(Default)]
+pub struct ChainProcessor {
+	version: u64,
+	effects: Vec<Box<dyn Effect>>
+}
+
+impl ChainProcessor {
+	/// Rebuilds the processors if the chain has changed since they were built.
+	///
+	/// Note: The state of the processors is lost when they are rebuilt.
+	pub fn sync(&mut self, chain: &EffectChain, sample_rate: TFrameIdx, layout: ChannelLayout) {
+		if self.version == chain.version() { return; }
+
+		self.effects = chain.effects().iter().map(|e| e.build(sample_rate, layout)).collect();
+		self.version = chain.version();
+	}
+
+	pub fn process(&mut self, block: &mut [TSample]) {
+		for effect in self.effects.iter_mut() {
+			effect.process(block);
+		}
+	}
+
+	pub fn reset(&mut self) {
+		for effect in self.effects.iter_mut() {
+			effect.reset();
+		}
+	}
+}
+
+/// The effects that come with rad, written as `{name}` or `{name}:{key}={value},{key}={value},...`:
+/// - `hp:freq={hz}[,q={q}]` and `lp:freq={hz}[,q={q}]` for high/low-pass filters
+/// - `eq:{band}={hz}/{gain-db}[/{q}],...` for a parametric EQ, where each band is one of `peak`, `low-shelf` and `high-shelf`
+/// - `comp[:threshold={db},ratio={ratio},attack={ms},release={ms},makeup={db}]` for a compressor
+/// - `pan:pos={-1..1}` for panning the front left/right channels (balance)
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuiltinEffect {
+	HighPass { freq: f32, q: f32 },
+	LowPass { freq: f32, q: f32 },
+	Eq(Vec<EqBand>),
+	Compressor(CompressorParams),
+	Pan { pos: f32 }
+}
+
+impl EffectDesc for BuiltinEffect {
+	fn build(&self, sample_rate: TFrameIdx, layout: ChannelLayout) -> Box<dyn Effect> {
+		let channels = layout.channels();
+
+		match self {
+			Self::HighPass { freq, q } => Box::new(BiquadEffect::new(vec![BiquadCoeffs::high_pass(sample_rate, *freq, *q)], channels)),
+			Self::LowPass { freq, q } => Box::new(BiquadEffect::new(vec![BiquadCoeffs::low_pass(sample_rate, *freq, *q)], channels)),
+			Self::Eq(bands) => Box::new(BiquadEffect::new(bands.iter().map(|b| b.coeffs(sample_rate)).collect(), channels)),
+			Self::Compressor(params) => Box::new(Compressor::new(*params, sample_rate, channels)),
+			Self::Pan { pos } => Box::new(Pan::new(*pos, layout))
+		}
+	}
+}
+
+impl Display for BuiltinEffect {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::HighPass { freq, q } => write!(f, "hp:freq={},q={}", freq, q),
+			Self::LowPass { freq, q } => write!(f, "lp:freq={},q={}", freq, q),
+			Self::Eq(bands) => {
+				write!(f, "eq:")?;
+				for (i, band) in bands.iter().enumerate() {
+					if i != 0 { write!(f, ",")?; }
+					write!(f, "{}={}/{}/{}", band.kind.name(), band.freq, band.gain_db, band.q)?;
+				}
+
+				Ok(())
+			},
+			Self::Compressor(p) => write!(
+				f, "comp:threshold={},ratio={},attack={},release={},makeup={}",
+				p.threshold_db, p.ratio, p.attack_ms, p.release_ms, p.makeup_db
+			),
+			Self::Pan { pos } => write!(f, "pan:pos={}", pos)
+		}
+	}
+}
+
+impl FromStr for BuiltinEffect {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, args) = s.split_once(':').unwrap_or((s, ""));
+		let args = args.split(',')
+			.filter(|arg| !arg.is_empty())
+			.map(|arg| arg.split_once('=').ok_or(()))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let num = |v: &str| v.parse::<f32>().ok().filter(|v| v.is_finite()).ok_or(());
+
+		match name {
+			"hp" | "lp" => {
+				let (mut freq, mut q) = (None, DEFAULT_Q);
+				for (key, val) in args {
+					match key {
+						"freq" => freq = Some(num(val)?),
+						"q" => q = num(val)?,
+						_ => { return Err(()); }
+					}
+				}
+
+				let freq = freq.filter(|f| 0.0 < *f).ok_or(())?;
+				if q <= 0.0 { return Err(()); }
+
+				Ok(if name == "hp" { Self::HighPass { freq, q } } else { Self::LowPass { freq, q } })
+			},
+			"eq" => {
+				let mut bands = Vec::new();
+				for (key, val) in args {
+					let kind = key.parse()?;
+					let parts = val.split('/').map(num).collect::<Result<Vec<_>, _>>()?;
+
+					let (freq, gain_db, q) = match *parts.as_slice() {
+						[freq, gain_db] => (freq, gain_db, DEFAULT_Q),
+						[freq, gain_db, q] => (freq, gain_db, q),
+						_ => { return Err(()); }
+					};
+
+					if freq <= 0.0 || q <= 0.0 { return Err(()); }
+
+					bands.push(EqBand { kind, freq, gain_db, q });
+				}
+
+				Ok(Self::Eq(bands))
+			},
+			"comp" => {
+				let mut params = CompressorParams::default();
+				for (key, val) in args {
+					let val = num(val)?;
+					match key {
+						"threshold" => params.threshold_db = val,
+						"ratio" => params.ratio = val,
+						"attack" => params.attack_ms = val,
+						"release" => params.release_ms = val,
+						"makeup" => params.makeup_db = val,
+						_ => { return Err(()); }
+					}
+				}
+
+				if params.ratio < 1.0 || params.attack_ms < 0.0 || params.release_ms < 0.0 { return Err(()); }
+
+				Ok(Self::Compressor(params))
+			},
+			"pan" => {
+				let mut pos = 0.0;
+				for (key, val) in args {
+					match key {
+						"pos" => pos = num(val)?,
+						_ => { return Err(()); }
+					}
+				}
+
+				if !(-1.0..=1.0).contains(&pos) { return Err(()); }
+
+				Ok(Self::Pan { pos })
+			},
+			_ => Err(())
+		}
+	}
+}
@@ -0,0 +1,178 @@
+// Second order IIR filters after the formulas of the "Audio EQ Cookbook" by Robert Bristow-Johnson.
+
+use std::{f64::consts::PI, str::FromStr};
+
+use crate::source::{TFrameIdx, TSample};
+
+use super::Effect;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EqBandKind {
+	Peak,
+	LowShelf,
+	HighShelf
+}
+
+impl EqBandKind {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Peak => "peak",
+			Self::LowShelf => "low-shelf",
+			Self::HighShelf => "high-shelf"
+		}
+	}
+}
+
+impl FromStr for EqBandKind {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"peak" => Ok(Self::Peak),
+			"low-shelf" => Ok(Self::LowShelf),
+			"high-shelf" => Ok(Self::HighShelf),
+			_ => Err(())
+		}
+	}
+}
+
+/// A band of a parametric EQ.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EqBand {
+	pub kind: EqBandKind,
+	pub freq: f32,
+	pub gain_db: f32,
+	pub q: f32
+}
+
+impl EqBand {
+	pub fn coeffs(&self, sample_rate: TFrameIdx) -> BiquadCoeffs {
+		match self.kind {
+			EqBandKind::Peak => BiquadCoeffs::peak(sample_rate, self.freq, self.gain_db, self.q),
+			EqBandKind::LowShelf => BiquadCoeffs::low_shelf(sample_rate, self.freq, self.gain_db, self.q),
+			EqBandKind::HighShelf => BiquadCoeffs::high_shelf(sample_rate, self.freq, self.gain_db, self.q)
+		}
+	}
+}
+
+/// Coefficients of a biquad filter, normalized so that `a0` is 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BiquadCoeffs {
+	b0: f64,
+	b1: f64,
+	b2: f64,
+	a1: f64,
+	a2: f64
+}
+
+/// The angular frequency and the cookbook's alpha, with the frequency kept below the Nyquist frequency.
+fn w0_alpha(sample_rate: TFrameIdx, freq: f32, q: f32) -> (f64, f64) {
+	let freq = (freq as f64).min(sample_rate as f64 * 0.49);
+	let w0 = 2.0 * PI * freq / sample_rate as f64;
+
+	(w0, w0.sin() / (2.0 * q as f64))
+}
+
+impl BiquadCoeffs {
+	/// Coefficients from the raw ones of the difference equation, normalizing them by `a0`.
+	pub fn normalized(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+		BiquadCoeffs { b0: b0 / a0, b1: b1 / a0, b2: b2 / a0, a1: a1 / a0, a2: a2 / a0 }
+	}
+
+	pub fn low_pass(sample_rate: TFrameIdx, freq: f32, q: f32) -> Self {
+		let (w0, alpha) = w0_alpha(sample_rate, freq, q);
+		let cos = w0.cos();
+
+		Self::normalized((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+	}
+
+	pub fn high_pass(sample_rate: TFrameIdx, freq: f32, q: f32) -> Self {
+		let (w0, alpha) = w0_alpha(sample_rate, freq, q);
+		let cos = w0.cos();
+
+		Self::normalized((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+	}
+
+	pub fn peak(sample_rate: TFrameIdx, freq: f32, gain_db: f32, q: f32) -> Self {
+		let (w0, alpha) = w0_alpha(sample_rate, freq, q);
+		let cos = w0.cos();
+		let a = 10f64.powf(gain_db as f64 / 40.0);
+
+		Self::normalized(1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a)
+	}
+
+	pub fn low_shelf(sample_rate: TFrameIdx, freq: f32, gain_db: f32, q: f32) -> Self {
+		let (w0, alpha) = w0_alpha(sample_rate, freq, q);
+		let cos = w0.cos();
+		let a = 10f64.powf(gain_db as f64 / 40.0);
+		let k = 2.0 * a.sqrt() * alpha;
+
+		Self::normalized(
+			a * ((a + 1.0) - (a - 1.0) * cos + k),
+			2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+			a * ((a + 1.0) - (a - 1.0) * cos - k),
+			(a + 1.0) + (a - 1.0) * cos + k,
+			-2.0 * ((a - 1.0) + (a + 1.0) * cos),
+			(a + 1.0) + (a - 1.0) * cos - k
+		)
+	}
+
+	pub fn high_shelf(sample_rate: TFrameIdx, freq: f32, gain_db: f32, q: f32) -> Self {
+		let (w0, alpha) = w0_alpha(sample_rate, freq, q);
+		let cos = w0.cos();
+		let a = 10f64.powf(gain_db as f64 / 40.0);
+		let k = 2.0 * a.sqrt() * alpha;
+
+		Self::normalized(
+			a * ((a + 1.0) + (a - 1.0) * cos + k),
+			-2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+			a * ((a + 1.0) + (a - 1.0) * cos - k),
+			(a + 1.0) - (a - 1.0) * cos + k,
+			2.0 * ((a - 1.0) - (a + 1.0) * cos),
+			(a + 1.0) - (a - 1.0) * cos - k
+		)
+	}
+}
+
+/// A series of biquad filters applied to every channel, each channel with filter memories of its own.
+pub struct BiquadEffect {
+	filters: Vec<BiquadCoeffs>,
+	channels: usize,
+	/// The two state variables (transposed direct form II) of each filter for each channel, indexed by filter then channel.
+	state: Vec<[f64; 2]>
+}
+
+impl BiquadEffect {
+	pub fn new(filters: Vec<BiquadCoeffs>, channels: u8) -> Self {
+		let channels = channels as usize;
+
+		BiquadEffect {
+			state: vec![[0.0; 2]; filters.len() * channels],
+			filters,
+			channels
+		}
+	}
+}
+
+impl Effect for BiquadEffect {
+	fn process(&mut self, block: &mut [TSample]) {
+		for (filter_i, c) in self.filters.iter().enumerate() {
+			let state = &mut self.state[filter_i * self.channels..(filter_i + 1) * self.channels];
+
+			for frame in block.chunks_exact_mut(self.channels) {
+				for (v, s) in frame.iter_mut().zip(state.iter_mut()) {
+					let x = *v as f64;
+					let y = c.b0 * x + s[0];
+					s[0] = c.b1 * x - c.a1 * y + s[1];
+					s[1] = c.b2 * x - c.a2 * y;
+
+					*v = y as TSample;
+				}
+			}
+		}
+	}
+
+	fn reset(&mut self) {
+		self.state.fill([0.0; 2]);
+	}
+}
@@ -0,0 +1,80 @@
+use crate::source::{TFrameIdx, TSample};
+
+use super::Effect;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompressorParams {
+	/// The level (in dBFS) above which the gain gets reduced.
+	pub threshold_db: f32,
+	/// How many dBs the input has to go over the threshold for the output to go over it by one dB.
+	pub ratio: f32,
+	pub attack_ms: f32,
+	pub release_ms: f32,
+	/// Gain applied after the compression.
+	pub makeup_db: f32
+}
+
+impl Default for CompressorParams {
+	fn default() -> Self {
+		CompressorParams {
+			threshold_db: -18.0,
+			ratio: 4.0,
+			attack_ms: 10.0,
+			release_ms: 100.0,
+			makeup_db: 0.0
+		}
+	}
+}
+
+/// Smoothing coefficient of a one-pole filter reaching about 63% of a change after `ms`.
+fn smoothing_coeff(ms: f32, sample_rate: TFrameIdx) -> f32 {
+	if ms <= 0.0 { return 0.0; }
+
+	(-1.0 / (ms / 1000.0 * sample_rate as f32)).exp()
+}
+
+/// A feed-forward peak compressor with the channels linked, so that the stereo image doesn't shift while compressing.
+pub struct Compressor {
+	params: CompressorParams,
+	channels: usize,
+	attack_coeff: f32,
+	release_coeff: f32,
+	/// The current gain reduction in dB.
+	reduction_db: f32
+}
+
+impl Compressor {
+	pub fn new(params: CompressorParams, sample_rate: TFrameIdx, channels: u8) -> Self {
+		Compressor {
+			params,
+			channels: channels as usize,
+			attack_coeff: smoothing_coeff(params.attack_ms, sample_rate),
+			release_coeff: smoothing_coeff(params.release_ms, sample_rate),
+			reduction_db: 0.0
+		}
+	}
+}
+
+impl Effect for Compressor {
+	fn process(&mut self, block: &mut [TSample]) {
+		let slope = 1.0 - 1.0 / self.params.ratio;
+
+		for frame in block.chunks_exact_mut(self.channels) {
+			let peak = frame.iter().fold(0f32, |acc, v| acc.max(v.abs()));
+			let level_db = 20.0 * peak.max(1e-9).log10();
+			let target_db = (level_db - self.params.threshold_db).max(0.0) * slope;
+
+			let coeff = if self.reduction_db < target_db { self.attack_coeff } else { self.release_coeff };
+			self.reduction_db = target_db + coeff * (self.reduction_db - target_db);
+
+			let gain = 10f32.powf((self.params.makeup_db - self.reduction_db) / 20.0);
+			for v in frame.iter_mut() {
+				*v *= gain;
+			}
+		}
+	}
+
+	fn reset(&mut self) {
+		self.reduction_db = 0.0;
+	}
+}
@@ -0,0 +1,39 @@
+use crate::{channel_layout::{ChannelLayout, Speaker}, source::TSample};
+
+use super::Effect;
+
+/// Balances the front left and right channels, layouts without them pass through untouched.
+pub struct Pan {
+	channels: usize,
+	/// Indexes of the front left and right channels in a frame.
+	left_right: Option<(usize, usize)>,
+	gains: (f32, f32)
+}
+
+impl Pan {
+	/// `pos` goes from -1 (left only) to 1 (right only).
+	pub fn new(pos: f32, layout: ChannelLayout) -> Self {
+		let speakers = layout.speakers();
+		let idx_of = |sp: Speaker| speakers.iter().position(|s| *s == sp);
+
+		Pan {
+			channels: layout.channels() as usize,
+			left_right: idx_of(Speaker::FrontLeft).zip(idx_of(Speaker::FrontRight)),
+			gains: ((1.0 - pos).min(1.0), (1.0 + pos).min(1.0))
+		}
+	}
+}
+
+impl Effect for Pan {
+	fn process(&mut self, block: &mut [TSample]) {
+		let (l, r) = match self.left_right {
+			Some(lr) => lr,
+			None => { return; }
+		};
+
+		for frame in block.chunks_exact_mut(self.channels) {
+			frame[l] *= self.gains.0;
+			frame[r] *= self.gains.1;
+		}
+	}
+}
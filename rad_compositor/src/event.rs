@@ -0,0 +1,115 @@
+// Announcements of what happens to compositions, their sources, compositors and adapters, for the parts of the daemon that react to them
+// (e.g. the remote API, the debug CLI or scripts) instead of polling the state.
+
+use std::{fmt::Display, sync::{mpsc::{sync_channel, Receiver, SyncSender}, LazyLock, Mutex}};
+
+use crate::{composition::{FinishPolicy, TSrcID}, source::TFrameIdx};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+	/// A composition was added to the registry.
+	CompositionAdded { cmp_id: String },
+	CompositionPaused { cmp_id: String },
+	CompositionResumed { cmp_id: String },
+	/// The time of a composition was set to `time_ms`.
+	CompositionSeeked { cmp_id: String, time_ms: u64 },
+	/// A source was added to a composition, `kind` is what `BaseSource::kind` calls it.
+	SourceAdded { cmp_id: String, src_id: TSrcID, kind: String },
+	/// A source was taken out of a composition, either by hand or by its finish policy.
+	SourceRemoved { cmp_id: String, src_id: TSrcID },
+	/// A source played to its end (including all of its loops), after which its finish policy was applied.
+	SourceFinished { cmp_id: String, src_id: TSrcID, policy: FinishPolicy },
+	/// A source kept failing (or panicked) while being read, so it's silent from now on while the rest of the composition plays on.
+	SourceFaulted { cmp_id: String, src_id: TSrcID },
+	/// A compositor started computing the output of a composition at a sample-rate.
+	CompositorStarted { cmp_id: String, sample_rate: TFrameIdx },
+	/// A compositor stopped as nothing was reading its output anymore.
+	CompositorKilled { cmp_id: String, sample_rate: TFrameIdx },
+	AdapterClosed { adapter_id: String }
+}
+
+impl Event {
+	/// What the event is called, e.g. `source-finished`.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Event::CompositionAdded { .. } => "composition-added",
+			Event::CompositionPaused { .. } => "composition-paused",
+			Event::CompositionResumed { .. } => "composition-resumed",
+			Event::CompositionSeeked { .. } => "composition-seeked",
+			Event::SourceAdded { .. } => "source-added",
+			Event::SourceRemoved { .. } => "source-removed",
+			Event::SourceFinished { .. } => "source-finished",
+			Event::SourceFaulted { .. } => "source-faulted",
+			Event::CompositorStarted { .. } => "compositor-started",
+			Event::CompositorKilled { .. } => "compositor-killed",
+			Event::AdapterClosed { .. } => "adapter-closed"
+		}
+	}
+
+	/// The composition the event is about, if it's about one.
+	pub fn cmp_id(&self) -> Option<&str> {
+		match self {
+			Event::CompositionAdded { cmp_id } |
+			Event::CompositionPaused { cmp_id } |
+			Event::CompositionResumed { cmp_id } |
+			Event::CompositionSeeked { cmp_id, .. } |
+			Event::SourceAdded { cmp_id, .. } |
+			Event::SourceRemoved { cmp_id, .. } |
+			Event::SourceFinished { cmp_id, .. } |
+			Event::SourceFaulted { cmp_id, .. } |
+			Event::CompositorStarted { cmp_id, .. } |
+			Event::CompositorKilled { cmp_id, .. } => Some(cmp_id),
+			Event::AdapterClosed { .. } => None
+		}
+	}
+}
+
+impl Display for Event {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.name())?;
+
+		match self {
+			Event::CompositionAdded { cmp_id } |
+			Event::CompositionPaused { cmp_id } |
+			Event::CompositionResumed { cmp_id } => write!(f, " cmp={}", cmp_id),
+			Event::CompositionSeeked { cmp_id, time_ms } => write!(f, " cmp={} time={}ms", cmp_id, time_ms),
+			Event::SourceAdded { cmp_id, src_id, kind } => write!(f, " cmp={} src={} kind={}", cmp_id, src_id, kind),
+			Event::SourceRemoved { cmp_id, src_id } |
+			Event::SourceFaulted { cmp_id, src_id } => write!(f, " cmp={} src={}", cmp_id, src_id),
+			Event::SourceFinished { cmp_id, src_id, policy } => write!(f, " cmp={} src={} policy={}", cmp_id, src_id, policy),
+			Event::CompositorStarted { cmp_id, sample_rate } |
+			Event::CompositorKilled { cmp_id, sample_rate } => write!(f, " cmp={} rate={}", cmp_id, sample_rate),
+			Event::AdapterClosed { adapter_id } => write!(f, " adapter={}", adapter_id)
+		}
+	}
+}
+
+/// How many events are kept for a subscriber that hasn't received them yet, before it's dropped for falling behind.
+pub const SUBSCRIBER_BACKLOG: usize = 1024;
+
+/// Hands every published event to every subscriber.
+#[derive(Default)]
+pub struct EventBus {
+	subscribers: Mutex<Vec<SyncSender<Event>>>
+}
+
+pub static GLOBAL_EVENT_BUS: LazyLock<EventBus> = LazyLock::new(EventBus::default);
+
+impl EventBus {
+	/// The receiver gets every event published from now on, until it's dropped or falls `SUBSCRIBER_BACKLOG` events behind,
+	/// after which it's disconnected.
+	pub fn subscribe(&self) -> Receiver<Event> {
+		let (sender, receiver) = sync_channel(SUBSCRIBER_BACKLOG);
+		self.subscribers.lock().unwrap().push(sender);
+
+		receiver
+	}
+
+	/// Never blocks, as the events are queued for the subscribers that haven't received the earlier ones yet.
+	pub fn publish(&self, event: Event) {
+		log::debug!("Event: {}", event);
+
+		// The subscribers that have been dropped or whose backlog is full are forgotten
+		self.subscribers.lock().unwrap().retain(|sub| sub.try_send(event.clone()).is_ok());
+	}
+}
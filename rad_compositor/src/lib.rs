@@ -8,5 +8,11 @@ pub mod composition;
 pub mod compositor;
 pub mod cmp_reg;
 pub mod adapter;
+pub mod analysis;
+pub mod channel_layout;
+pub mod effect;
+pub mod event;
+pub mod loudness;
 pub mod prelude;
+pub mod resampler;
 pub mod source;
\ No newline at end of file
@@ -0,0 +1,127 @@
+// Measuring how loud sources are, so that compositions can bring them all to the same loudness.
+//
+// The loudness is the integrated loudness of EBU R128 in LUFS. It's read from the ReplayGain/R128 tags of a file when it has them,
+// and otherwise measured by decoding the whole file on a background thread. Measurements are kept in `cache::GLOBAL_LOUDNESS_CACHE`.
+
+use std::{fs::File, path::{Path, PathBuf}, sync::{mpsc::{channel, Sender}, Arc, LazyLock, Mutex, OnceLock}, thread};
+
+use symphonia::core::{io::MediaSourceStream, meta::{StandardTagKey, Tag}};
+
+use crate::source::utils::{decode::decode_all, hint::hint_of_path};
+use cache::GLOBAL_LOUDNESS_CACHE;
+use meter::LoudnessMeter;
+
+pub mod cache;
+pub mod meter;
+
+/// Loudness in LUFS (loudness units relative to full scale).
+pub type TLufs = f32;
+
+/// ReplayGain 2.0 gains bring tracks to this loudness.
+const REPLAY_GAIN_REFERENCE_LUFS: TLufs = -18.0;
+/// R128 gains (e.g. `R128_TRACK_GAIN` of Opus files) bring tracks to this loudness.
+const R128_REFERENCE_LUFS: TLufs = -23.0;
+
+/// Normalization never turns a source up by more than this, so that nearly silent sources don't get blown up.
+pub const MAX_NORMALIZATION_BOOST_DB: f32 = 12.0;
+
+/// The loudness of a source which gets filled in once it's known, e.g. when the background measurement finishes.
+#[derive(Clone, Debug, Default)]
+pub struct LoudnessHandle(Arc<OnceLock<TLufs>>);
+
+impl LoudnessHandle {
+	pub fn known(lufs: TLufs) -> Self {
+		LoudnessHandle(Arc::new(OnceLock::from(lufs)))
+	}
+
+	pub fn get(&self) -> Option<TLufs> {
+		self.0.get().copied()
+	}
+
+	fn set(&self, lufs: TLufs) {
+		let _ = self.0.set(lufs);
+	}
+}
+
+/// The linear gain bringing audio at `loudness` to `target`.
+pub fn normalization_gain(loudness: TLufs, target: TLufs) -> f32 {
+	10f32.powf((target - loudness).min(MAX_NORMALIZATION_BOOST_DB) / 20.0)
+}
+
+/// Parses gains written like `-6.48 dB`.
+fn parse_gain_db(value: &str) -> Option<f32> {
+	let value = value.trim();
+	let value = value.strip_suffix("dB").or_else(|| value.strip_suffix("db")).unwrap_or(value);
+
+	value.trim().parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+/// The loudness of a track according to its ReplayGain or R128 track gain tags.
+pub fn loudness_from_tags(tags: &[Tag]) -> Option<TLufs> {
+	tags.iter().find_map(|tag| {
+		if tag.std_key == Some(StandardTagKey::ReplayGainTrackGain) || tag.key.eq_ignore_ascii_case("REPLAYGAIN_TRACK_GAIN") {
+			return parse_gain_db(&tag.value.to_string()).map(|gain| REPLAY_GAIN_REFERENCE_LUFS - gain);
+		}
+
+		// A Q7.8 fixed point number of dBs
+		if tag.key.eq_ignore_ascii_case("R128_TRACK_GAIN") {
+			return tag.value.to_string().trim().parse::<i16>().ok().map(|gain| R128_REFERENCE_LUFS - gain as f32 / 256.0);
+		}
+
+		None
+	})
+}
+
+/// Measures the loudness of a file by decoding all of it, which for long files takes a while.
+pub fn measure_path(path: &Path) -> Option<TLufs> {
+	let mss = MediaSourceStream::new(Box::new(File::open(path).ok()?), Default::default());
+
+	let hint = hint_of_path(path);
+
+	let mut meter: Option<LoudnessMeter> = None;
+	decode_all(mss, &hint, |sample_rate, layout, block| {
+		meter.get_or_insert_with(|| LoudnessMeter::new(sample_rate, layout)).push(block);
+	})?;
+
+	meter?.integrated()
+}
+
+/// The queue of files waiting to be measured by the background thread.
+static MEASURE_QUEUE: LazyLock<Mutex<Sender<(PathBuf, LoudnessHandle)>>> = LazyLock::new(|| {
+	let (sender, receiver) = channel::<(PathBuf, LoudnessHandle)>();
+
+	thread::Builder::new()
+		.name("loudness".into())
+		.spawn(move || {
+			for (path, handle) in receiver {
+				// The same file may have been queued more than once
+				if let Some(lufs) = GLOBAL_LOUDNESS_CACHE.lock().unwrap().get(&path) {
+					handle.set(lufs);
+					continue;
+				}
+
+				log::debug!("Measuring the loudness of '{}'.", path.display());
+				match measure_path(&path) {
+					Some(lufs) => {
+						GLOBAL_LOUDNESS_CACHE.lock().unwrap().insert(&path, lufs);
+						handle.set(lufs);
+					},
+					None => log::warn!("Failed to measure the loudness of '{}'.", path.display())
+				}
+			}
+		}).unwrap();
+
+	Mutex::new(sender)
+});
+
+/// The loudness of the file at `path`, taken from the cache or otherwise measured in the background.
+pub fn loudness_of_path(path: &Path) -> LoudnessHandle {
+	if let Some(lufs) = GLOBAL_LOUDNESS_CACHE.lock().unwrap().get(path) {
+		return LoudnessHandle::known(lufs);
+	}
+
+	let handle = LoudnessHandle::default();
+	let _ = MEASURE_QUEUE.lock().unwrap().send((path.to_path_buf(), handle.clone()));
+
+	handle
+}
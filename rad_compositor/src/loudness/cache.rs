@@ -0,0 +1,95 @@
+use std::{collections::HashMap, fs::{File, OpenOptions}, io::{self, BufRead, BufReader, Write}, path::{Path, PathBuf}, sync::{LazyLock, Mutex}, time::UNIX_EPOCH};
+
+use super::TLufs;
+
+/// The measurements of all the files measured since rad started, shared by every source.
+pub static GLOBAL_LOUDNESS_CACHE: LazyLock<Mutex<LoudnessCache>> = LazyLock::new(|| Mutex::new(LoudnessCache::new()));
+
+/// What a file is recognized by, so that a file that got changed after being measured gets measured again.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+	len: u64,
+	modified_secs: u64
+}
+
+impl FileStamp {
+	fn of(path: &Path) -> Option<Self> {
+		let meta = std::fs::metadata(path).ok()?;
+		let modified_secs = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+		Some(FileStamp { len: meta.len(), modified_secs })
+	}
+}
+
+/// Integrated loudness of files by their canonical paths.
+///
+/// The cache can be backed by a file (see `open`), in which case the measurements survive restarts.
+/// The file has a measurement per line as `{lufs}\t{len}\t{modified-secs}\t{path}` and is only ever appended to, later lines taking precedence.
+pub struct LoudnessCache {
+	entries: HashMap<PathBuf, (FileStamp, TLufs)>,
+	file: Option<File>
+}
+
+impl LoudnessCache {
+	pub fn new() -> Self {
+		LoudnessCache { entries: HashMap::new(), file: None }
+	}
+
+	/// Loads the measurements in the file at `path` (creating it if it doesn't exist) and saves the new ones into it from now on.
+	pub fn open(&mut self, path: &Path) -> io::Result<()> {
+		let file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+
+		for line in BufReader::new(&file).lines() {
+			let line = line?;
+			let mut fields = line.splitn(4, '\t');
+
+			let entry = (|| {
+				let lufs = fields.next()?.parse().ok()?;
+				let len = fields.next()?.parse().ok()?;
+				let modified_secs = fields.next()?.parse().ok()?;
+				let path = PathBuf::from(fields.next()?);
+
+				Some((path, (FileStamp { len, modified_secs }, lufs)))
+			})();
+
+			match entry {
+				Some((path, entry)) => { self.entries.insert(path, entry); },
+				None => log::warn!("Skipped a malformed line in the loudness cache at '{}'.", path.display())
+			}
+		}
+
+		self.file = Some(file);
+
+		Ok(())
+	}
+
+	/// The loudness the file was measured at, if it hasn't changed since.
+	pub fn get(&self, path: &Path) -> Option<TLufs> {
+		let path = path.canonicalize().ok()?;
+		let (stamp, lufs) = self.entries.get(&path)?;
+
+		(Some(*stamp) == FileStamp::of(&path)).then_some(*lufs)
+	}
+
+	pub fn insert(&mut self, path: &Path, lufs: TLufs) {
+		let (path, stamp) = match path.canonicalize().ok().zip(FileStamp::of(path)) {
+			Some(v) => v,
+			None => { return; }
+		};
+
+		if let Some(ref mut file) = self.file {
+			let line = format!("{}\t{}\t{}\t{}\n", lufs, stamp.len, stamp.modified_secs, path.display());
+			if let Err(e) = file.write_all(line.as_bytes()) {
+				log::warn!("Failed to save the loudness of '{}' due to io error '{}'.", path.display(), e);
+			}
+		}
+
+		self.entries.insert(path, (stamp, lufs));
+	}
+}
+
+impl Default for LoudnessCache {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,178 @@
+// The integrated loudness of ITU-R BS.1770 (which EBU R128 is based on), measured over K-weighted audio with 400 ms gating
+// blocks that overlap by 75% and both the absolute (-70 LUFS) and the relative (-10 LU) gates.
+// The same measurement over just the last 3 s without the gates is the short-term loudness of EBU R128, used by the level meters.
+
+use std::f64::consts::PI;
+
+use crate::{channel_layout::{ChannelLayout, Speaker}, effect::{biquad::{BiquadCoeffs, BiquadEffect}, Effect}, source::{TFrameIdx, TSample}};
+
+use super::TLufs;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// The gating blocks are made out of this many steps, each step being 100 ms.
+const STEPS_PER_BLOCK: usize = 4;
+/// The short-term loudness is measured over the last 3 s.
+const SHORT_TERM_STEPS: usize = 30;
+
+/// The high shelf modelling the acoustic effect of the head, from the first stage of the K-weighting.
+fn k_shelf(sample_rate: TFrameIdx) -> BiquadCoeffs {
+	const FREQ: f64 = 1681.974450955533;
+	const GAIN_DB: f64 = 3.999843853973347;
+	const Q: f64 = 0.7071752369554196;
+
+	let k = (PI * FREQ / sample_rate as f64).tan();
+	let vh = 10f64.powf(GAIN_DB / 20.0);
+	let vb = vh.powf(0.4996667741545416);
+
+	BiquadCoeffs::normalized(
+		vh + vb * k / Q + k * k, 2.0 * (k * k - vh), vh - vb * k / Q + k * k,
+		1.0 + k / Q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / Q + k * k
+	)
+}
+
+/// The high-pass of the second stage of the K-weighting (the RLB filter).
+fn k_high_pass(sample_rate: TFrameIdx) -> BiquadCoeffs {
+	const FREQ: f64 = 38.13547087602444;
+	const Q: f64 = 0.5003270373238773;
+
+	let k = (PI * FREQ / sample_rate as f64).tan();
+
+	BiquadCoeffs::normalized(1.0, -2.0, 1.0, 1.0 + k / Q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / Q + k * k)
+}
+
+/// How much each channel counts towards the loudness, the surround channels count more and the LFE doesn't count at all.
+fn channel_weight(speaker: Speaker) -> f64 {
+	match speaker {
+		Speaker::Lfe => 0.0,
+		Speaker::RearLeft | Speaker::RearRight | Speaker::SideLeft | Speaker::SideRight => 1.41,
+		_ => 1.0
+	}
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+	-0.691 + 10.0 * mean_square.log10()
+}
+
+/// Measures the integrated loudness of the frames pushed into it.
+pub struct LoudnessMeter {
+	k_filter: BiquadEffect,
+	channels: usize,
+	weights: Vec<f64>,
+	step_frames: usize,
+	/// The weighted sum of squares of the step being filled and the number of frames in it.
+	step_sum: f64,
+	step_filled: usize,
+	/// The weighted mean square of each complete step.
+	steps: Vec<f64>,
+	/// Only this many of the last steps are kept.
+	keep_steps: usize,
+	filtered: Vec<TSample>
+}
+
+impl LoudnessMeter {
+	pub fn new(sample_rate: TFrameIdx, layout: ChannelLayout) -> Self {
+		Self::keeping(sample_rate, layout, usize::MAX)
+	}
+
+	/// A meter that only keeps what's needed for `short_term`, so that it can be fed for as long as a compositor runs.
+	pub fn short_term_only(sample_rate: TFrameIdx, layout: ChannelLayout) -> Self {
+		Self::keeping(sample_rate, layout, SHORT_TERM_STEPS)
+	}
+
+	fn keeping(sample_rate: TFrameIdx, layout: ChannelLayout, keep_steps: usize) -> Self {
+		let channels = layout.channels() as usize;
+		let weights = match layout.speakers() {
+			[] => vec![1.0; channels],
+			speakers => speakers.iter().map(|s| channel_weight(*s)).collect()
+		};
+
+		LoudnessMeter {
+			k_filter: BiquadEffect::new(vec![k_shelf(sample_rate), k_high_pass(sample_rate)], layout.channels()),
+			channels,
+			weights,
+			step_frames: (sample_rate as usize / 10).max(1),
+			step_sum: 0.0,
+			step_filled: 0,
+			steps: Vec::new(),
+			keep_steps,
+			filtered: Vec::new()
+		}
+	}
+
+	/// Feeds a block of interleaved frames to the meter.
+	pub fn push(&mut self, block: &[TSample]) {
+		self.filtered.clear();
+		self.filtered.extend_from_slice(block);
+		self.k_filter.process(&mut self.filtered);
+
+		// Taken out for the duration of the loop, as completing a step needs the whole meter
+		let filtered = std::mem::take(&mut self.filtered);
+		for frame in filtered.chunks_exact(self.channels) {
+			self.step_sum += frame.iter().zip(self.weights.iter()).map(|(v, w)| w * (*v as f64) * (*v as f64)).sum::<f64>();
+			self.step_filled += 1;
+
+			if self.step_filled == self.step_frames {
+				self.push_step();
+			}
+		}
+		self.filtered = filtered;
+	}
+
+	/// Feeds `frames` frames of silence to the meter without filtering them.
+	pub fn push_silence(&mut self, frames: usize) {
+		if frames == 0 { return; }
+		self.k_filter.reset();
+
+		let mut left = frames;
+		while 0 < left {
+			let n = left.min(self.step_frames - self.step_filled);
+			self.step_filled += n;
+			left -= n;
+
+			if self.step_filled == self.step_frames {
+				self.push_step();
+			}
+		}
+	}
+
+	fn push_step(&mut self) {
+		self.steps.push(self.step_sum / self.step_frames as f64);
+		self.step_sum = 0.0;
+		self.step_filled = 0;
+
+		if self.keep_steps < self.steps.len() {
+			let excess = self.steps.len() - self.keep_steps;
+			self.steps.drain(..excess);
+		}
+	}
+
+	/// The ungated loudness of the last 3 s (or less if less has been pushed), `None` if not even 100 ms has been pushed.
+	///
+	/// Note: Silence is `-inf`.
+	pub fn short_term(&self) -> Option<TLufs> {
+		let steps = &self.steps[self.steps.len().saturating_sub(SHORT_TERM_STEPS)..];
+		if steps.is_empty() { return None; }
+
+		Some(to_lufs(steps.iter().sum::<f64>() / steps.len() as f64) as TLufs)
+	}
+
+	/// The gated loudness of everything pushed so far, `None` if it's all too quiet or shorter than a gating block.
+	pub fn integrated(&self) -> Option<TLufs> {
+		let above_absolute = self.steps.windows(STEPS_PER_BLOCK)
+			.map(|block| block.iter().sum::<f64>() / STEPS_PER_BLOCK as f64)
+			.filter(|z| ABSOLUTE_GATE_LUFS < to_lufs(*z))
+			.collect::<Vec<_>>();
+
+		if above_absolute.is_empty() { return None; }
+
+		let relative_gate = to_lufs(above_absolute.iter().sum::<f64>() / above_absolute.len() as f64) + RELATIVE_GATE_LU;
+		let (sum, count) = above_absolute.iter()
+			.filter(|z| relative_gate < to_lufs(**z))
+			.fold((0.0, 0), |(sum, count), z| (sum + z, count + 1));
+
+		if count == 0 { return None; }
+
+		Some(to_lufs(sum / count as f64) as TLufs)
+	}
+}
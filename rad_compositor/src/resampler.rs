@@ -0,0 +1,336 @@
+// Sample-rate conversion used by the compositors and by sources that combine other sources (e.g. queues).
+//
+// Each resampler keeps a small window of the most recent source frames so that the band-limited interpolation
+// doesn't need to fetch every source frame over and over again for each output frame.
+
+use std::{f64::consts::PI, str::FromStr};
+
+use crate::source::{BaseSource, TFrameIdx, TSample};
+
+/// How finely the sinc kernel is tabulated between two source frames.
+const KERNEL_TABLE_OVERSAMPLE: usize = 256;
+
+/// When the requested frames jump further than this ahead of the window, the window is refilled from scratch instead of read through.
+const MAX_WINDOW_SKIP_FRAMES: i64 = 64;
+
+/// How many frames are read past the ones currently needed, which is also how many stale frames the window keeps before dropping them.
+const READ_AHEAD_FRAMES: i64 = 64;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResampleQuality {
+	/// Two-point linear interpolation, cheapest but aliases audibly.
+	Linear,
+	/// Four-point Catmull-Rom interpolation.
+	Cubic,
+	/// Kaiser-windowed sinc interpolation with a short kernel.
+	#[default]
+	SincFast,
+	/// Kaiser-windowed sinc interpolation with a long kernel and a steep transition band.
+	SincBest
+}
+
+impl ResampleQuality {
+	/// Zero crossings of the sinc kernel on each side, its rolloff relative to the Nyquist frequency, and the beta of its Kaiser window.
+	fn sinc_params(self) -> Option<(usize, f64, f64)> {
+		match self {
+			Self::SincFast => Some((8, 0.86, 6.0)),
+			Self::SincBest => Some((32, 0.94, 10.0)),
+			_ => None
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Linear => "linear",
+			Self::Cubic => "cubic",
+			Self::SincFast => "sinc-fast",
+			Self::SincBest => "sinc-best"
+		}
+	}
+}
+
+impl FromStr for ResampleQuality {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"linear" => Ok(Self::Linear),
+			"cubic" => Ok(Self::Cubic),
+			"sinc-fast" => Ok(Self::SincFast),
+			"sinc-best" => Ok(Self::SincBest),
+			_ => Err(())
+		}
+	}
+}
+
+/// Zeroth order modified Bessel function of the first kind, used for the Kaiser window.
+fn bessel_i0(x: f64) -> f64 {
+	let mut sum = 1.0;
+	let mut term = 1.0;
+	let half_x = x / 2.0;
+
+	for k in 1..64 {
+		term *= (half_x / k as f64).powi(2);
+		sum += term;
+
+		if term < sum * 1e-16 { break; }
+	}
+
+	sum
+}
+
+/// A tabulated Kaiser-windowed sinc low-pass kernel for a specific conversion ratio.
+struct SincKernel {
+	/// How far the kernel reaches on each side in source frames.
+	support: f64,
+	table: Vec<f64>,
+	/// Table entries per source frame.
+	density: f64
+}
+
+impl SincKernel {
+	fn new(quality: ResampleQuality, src_sample_rate: TFrameIdx, into_sample_rate: TFrameIdx) -> Option<Self> {
+		let (zero_crossings, rolloff, beta) = quality.sinc_params()?;
+
+		// When downsampling the cutoff has to move down to the Nyquist frequency of the target.
+		let cutoff = rolloff * (into_sample_rate as f64 / src_sample_rate as f64).min(1.0);
+		let support = zero_crossings as f64 / cutoff;
+		let density = KERNEL_TABLE_OVERSAMPLE as f64;
+		let len = (support * density).ceil() as usize + 2;
+
+		let i0_beta = bessel_i0(beta);
+		let table = (0..len).map(|i| {
+			let x = i as f64 / density;
+			if support <= x { return 0.0; }
+
+			let sinc = if x == 0.0 { 1.0 } else { (PI * cutoff * x).sin() / (PI * cutoff * x) };
+			let ratio = x / support;
+			let window = bessel_i0(beta * (1.0 - ratio * ratio).sqrt()) / i0_beta;
+
+			cutoff * sinc * window
+		}).collect();
+
+		Some(SincKernel { support, table, density })
+	}
+
+	fn weight(&self, distance: f64) -> f64 {
+		let pos = distance.abs() * self.density;
+		let i = pos as usize;
+
+		if self.table.len() <= i + 1 { return 0.0; }
+
+		let frac = pos - i as f64;
+		self.table[i] + (self.table[i + 1] - self.table[i]) * frac
+	}
+}
+
+/// Converts the frames of a single source into another sample-rate.
+///
+/// A resampler is meant to be used with the same source and target sample-rate, `reset` has to be called when either of them changes.
+pub struct Resampler {
+	quality: ResampleQuality,
+	/// The most recently read source frames interleaved, with silence for the frames that the source doesn't have.
+	window: Vec<TSample>,
+	/// Source frame index of the first frame in the window.
+	window_start: i64,
+	/// Channel count of the frames in the window.
+	channels: usize,
+	/// The first frame index the source turned out not to have while filling the window.
+	src_end: Option<i64>,
+	kernel: Option<SincKernel>,
+	/// The sample-rates the kernel was made for.
+	kernel_rates: (TFrameIdx, TFrameIdx)
+}
+
+impl Resampler {
+	pub fn new(quality: ResampleQuality) -> Self {
+		Resampler {
+			quality,
+			window: Vec::new(),
+			window_start: 0,
+			channels: 0,
+			src_end: None,
+			kernel: None,
+			kernel_rates: (0, 0)
+		}
+	}
+
+	pub fn quality(&self) -> ResampleQuality {
+		self.quality
+	}
+
+	/// Forgets the source frames read so far.
+	pub fn reset(&mut self) {
+		self.window.clear();
+		self.window_start = 0;
+		self.src_end = None;
+	}
+
+	/// Returns the frame of `src` at `frame_idx` in `into_sample_rate` where the source starts `offset` frames (in its own sample-rate) later.
+	///
+	/// `None` is returned once the source has no frame at the requested position.
+	pub fn resample<S: BaseSource + ?Sized>(&mut self, src: &mut S, into_sample_rate: TFrameIdx, frame_idx: TFrameIdx, offset: i64) -> Option<Vec<TSample>> {
+		let mut frame = vec![0.0; src.channels() as usize];
+
+		self.resample_into(src, into_sample_rate, frame_idx, offset, &mut frame).then_some(frame)
+	}
+
+	/// Like `resample` but fills the consecutive frames starting at `frame_idx` into `out`, and returns how many frames were written before the source ended.
+	pub fn resample_block<S: BaseSource + ?Sized>(&mut self, src: &mut S, into_sample_rate: TFrameIdx, frame_idx: TFrameIdx, offset: i64, out: &mut [TSample]) -> usize {
+		let channels = src.channels() as usize;
+
+		for (i, frame) in out.chunks_exact_mut(channels).enumerate() {
+			if !self.resample_into(src, into_sample_rate, frame_idx + i as TFrameIdx, offset, frame) {
+				return i;
+			}
+		}
+
+		out.len() / channels
+	}
+
+	/// Writes a single frame into `out`, returns false without touching it if the source has no frame at the requested position.
+	fn resample_into<S: BaseSource + ?Sized>(&mut self, src: &mut S, into_sample_rate: TFrameIdx, frame_idx: TFrameIdx, offset: i64, out: &mut [TSample]) -> bool {
+		let src_sample_rate = src.sample_rate();
+		// The frames before the start of the source are asked for by mistake only, which is no reason to take down the compositor reading it
+		let pos = ((frame_idx as f64 * src_sample_rate as f64) / into_sample_rate as f64 - offset as f64).max(0.0);
+
+		let center = pos.floor() as i64;
+		let frac = pos - pos.floor();
+
+		match self.quality {
+			ResampleQuality::Linear => {
+				self.fill_window(src, center, center + 1);
+				if !self.has_frame(center) { return false; }
+
+				self.weighted_sum(center..=center + 1, |i| if i == center { 1.0 - frac } else { frac }, out);
+			},
+			ResampleQuality::Cubic => {
+				self.fill_window(src, center - 1, center + 2);
+				if !self.has_frame(center) { return false; }
+
+				// Catmull-Rom weights for the four frames around the position
+				let f2 = frac * frac;
+				let f3 = f2 * frac;
+				let weights = [
+					-0.5 * f3 + f2 - 0.5 * frac,
+					1.5 * f3 - 2.5 * f2 + 1.0,
+					-1.5 * f3 + 2.0 * f2 + 0.5 * frac,
+					0.5 * f3 - 0.5 * f2
+				];
+
+				self.weighted_sum(center - 1..=center + 2, |i| weights[(i - center + 1) as usize], out);
+			},
+			ResampleQuality::SincFast | ResampleQuality::SincBest => {
+				if self.kernel.is_none() || self.kernel_rates != (src_sample_rate, into_sample_rate) {
+					self.kernel = SincKernel::new(self.quality, src_sample_rate, into_sample_rate);
+					self.kernel_rates = (src_sample_rate, into_sample_rate);
+				}
+
+				let support = self.kernel.as_ref().unwrap().support;
+				let first = (pos - support).ceil() as i64;
+				let last = (pos + support).floor() as i64;
+
+				self.fill_window(src, first, last);
+				if !self.has_frame(center) { return false; }
+
+				let kernel = self.kernel.as_ref().unwrap();
+
+				self.weighted_sum(first..=last, |i| kernel.weight(pos - i as f64), out);
+			}
+		}
+
+		true
+	}
+
+	fn window_frames(&self) -> i64 {
+		if self.channels == 0 { return 0; }
+
+		(self.window.len() / self.channels) as i64
+	}
+
+	fn has_frame(&self, idx: i64) -> bool {
+		0 <= idx
+			&& self.window_start <= idx
+			&& idx < self.window_start + self.window_frames()
+			&& self.src_end.is_none_or(|end| idx < end)
+	}
+
+	/// Gives a frame of the window, the frame has to be in the window.
+	fn frame(&self, idx: i64) -> &[TSample] {
+		let start = (idx - self.window_start) as usize * self.channels;
+
+		&self.window[start..start + self.channels]
+	}
+
+	/// Makes sure the source frames in `first..=last` are in the window.
+	fn fill_window<S: BaseSource + ?Sized>(&mut self, src: &mut S, first: i64, last: i64) {
+		let channels = src.channels() as usize;
+		let window_end = self.window_start + self.window_frames();
+		// Once past the end that was found, the source is read again as some sources (e.g. queues) can grow.
+		let is_past_end = self.src_end.is_some_and(|end| end <= first);
+
+		if self.channels != channels || self.window.is_empty() || is_past_end || first < self.window_start || window_end + MAX_WINDOW_SKIP_FRAMES < first {
+			self.window.clear();
+			self.window_start = first;
+			self.channels = channels;
+			self.src_end = None;
+		}
+
+		// The frames that are no longer needed are only dropped once there are enough of them, so that the window isn't moved for every frame.
+		if READ_AHEAD_FRAMES <= first - self.window_start {
+			let dropped = (first - self.window_start).min(self.window_frames());
+			self.window.drain(..dropped as usize * channels);
+			self.window_start += dropped;
+
+			if self.window.is_empty() { self.window_start = first; }
+		}
+
+		let window_end = self.window_start + self.window_frames();
+		if last < window_end { return; }
+
+		// Reading more frames than needed right now, for the next frames to be served from the window.
+		let read_end = last + 1 + READ_AHEAD_FRAMES;
+		let prev_len = self.window.len();
+		self.window.resize(prev_len + (read_end - window_end) as usize * channels, 0.0);
+
+		// Frames before the start of the source and after its end are left silent.
+		let read_start = window_end.max(0);
+		let readable_end = self.src_end.map_or(read_end, |end| end.min(read_end));
+
+		if read_start < readable_end {
+			let from = prev_len + (read_start - window_end) as usize * channels;
+			let to = from + (readable_end - read_start) as usize * channels;
+			let read = src.read_block(read_start as TFrameIdx, &mut self.window[from..to]);
+
+			if read < (readable_end - read_start) as usize {
+				self.src_end = Some(read_start + read as i64);
+			}
+		}
+	}
+
+	/// Sums the frames in the window weighted by `weight` into `out` and normalizes the result by the sum of the weights.
+	///
+	/// Only the frames the source has count, so that the frames around its start and end aren't faded towards the silence past them.
+	fn weighted_sum(&self, range: std::ops::RangeInclusive<i64>, weight: impl Fn(i64) -> f64, out: &mut [TSample]) {
+		let out = &mut out[..self.channels];
+		out.fill(0.0);
+		let mut weight_sum = 0.0;
+
+		for i in range {
+			let w = weight(i);
+			if w == 0.0 || !self.has_frame(i) { continue; }
+
+			weight_sum += w;
+
+			for (o, v) in out.iter_mut().zip(self.frame(i)) {
+				*o += *v * w as TSample;
+			}
+		}
+
+		if weight_sum == 0.0 { weight_sum = 1.0; }
+
+		for o in out.iter_mut() {
+			*o /= weight_sum as TSample;
+		}
+	}
+}
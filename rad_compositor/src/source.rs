@@ -1,9 +1,19 @@
 use formatted::FormattedStreamSource;
+use std::sync::Arc;
+
+use crate::{analysis::waveform::WaveformOverview, channel_layout::ChannelLayout, loudness::TLufs};
+use metadata::TrackMetadata;
+use generator::GeneratorSrc;
+use nested::NestedSrc;
 use queue::QueueSrc;
 
 pub mod utils;
 pub mod queue;
 pub mod formatted;
+pub mod nested;
+pub mod generator;
+pub mod metadata;
+pub mod error;
 
 pub type TSample = f32;
 pub type TFrameIdx = u64;
@@ -29,34 +39,122 @@ pub trait BaseSource {
     
     fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<TSample>>;
 
+    /// Fills `out` with as many interleaved frames starting at `frame_idx` as fit in it and returns how many frames were written.
+    /// Fewer frames are only written when the source has no frames after them, the rest of `out` is left as it was.
+    /// 
+    /// The default implementation goes through `get_by_frame_i` frame by frame, sources that can copy whole blocks should override it.
+    fn read_block(&mut self, frame_idx: TFrameIdx, out: &mut [TSample]) -> usize {
+        let channels = self.channels() as usize;
+
+        for (i, frame) in out.chunks_exact_mut(channels).enumerate() {
+            match self.get_by_frame_i(frame_idx + i as TFrameIdx) {
+                Some(src_frame) => {
+                    for (o, v) in frame.iter_mut().zip(src_frame) { *o = v; }
+                },
+                None => { return i; }
+            }
+        }
+
+        out.len() / channels
+    }
+
     fn channels(&self) -> u8;
+
+    /// The arrangement of the channels in the frames of the source.
+    /// 
+    /// **Note: The returned value will stay the same over every call.**
+    fn channel_layout(&self) -> ChannelLayout {
+        ChannelLayout::from_channels(self.channels())
+    }
+
+    /// The integrated loudness of the source if it's known, which compositions use for normalizing it (see `loudness`).
+    fn loudness(&self) -> Option<TLufs> {
+        None
+    }
+
+    /// What the tags of the track the source is playing say about it, if it has any.
+    fn metadata(&self) -> Option<&TrackMetadata> {
+        None
+    }
+
+    /// The overview of the waveform of the track the source is playing, once it has been made.
+    fn waveform_overview(&self) -> Option<Arc<WaveformOverview>> {
+        None
+    }
+
+    /// The number of packets of the stream the source is playing that failed to decode and were played as silence instead.
+    fn decode_errors(&self) -> u64 {
+        0
+    }
+
+    /// Whether the source gave up on its stream after it kept failing, it gives no more frames once it has.
+    fn is_faulted(&self) -> bool {
+        false
+    }
+
+    /// Called instead of reading from the source while it isn't heard (e.g. while it or its composition is paused).
+    /// Live sources use it for keeping up with their input.
+    fn on_idle(&mut self) {}
+
+    /// Called a little before the source is first read from `frame_idx` (e.g. by queues before their next item starts),
+    /// so that it can get the frames ready instead of being caught cold by the read.
+    fn prepare(&mut self, _frame_idx: TFrameIdx) {}
 }
 
 /// A type for staying generic over different types of sources.
 pub enum Source {
     File(formatted::FormattedStreamSource),
-    Queue(queue::QueueSrc)
+    Queue(queue::QueueSrc),
+    Nested(nested::NestedSrc),
+    Generator(generator::GeneratorSrc)
+}
+
+impl Source {
+    /// A short human readable name for the type of the source.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::File(_) => "file",
+            Self::Queue(_) => "queue",
+            Self::Nested(_) => "nested",
+            Self::Generator(_) => "generator"
+        }
+    }
 }
 
 impl BaseSource for Source {
     fn get_by_frame_i(&mut self, frame_i: TFrameIdx) -> Option<Vec<TSample>> {
         match self {
             Self::File(file) => file.get_by_frame_i(frame_i),
-            Self::Queue(queue) => queue.get_by_frame_i(frame_i)
+            Self::Queue(queue) => queue.get_by_frame_i(frame_i),
+            Self::Nested(nested) => nested.get_by_frame_i(frame_i),
+            Self::Generator(gen) => gen.get_by_frame_i(frame_i)
+        }
+    }
+
+    fn read_block(&mut self, frame_idx: TFrameIdx, out: &mut [TSample]) -> usize {
+        match self {
+            Self::File(file) => file.read_block(frame_idx, out),
+            Self::Queue(queue) => queue.read_block(frame_idx, out),
+            Self::Nested(nested) => nested.read_block(frame_idx, out),
+            Self::Generator(gen) => gen.read_block(frame_idx, out)
         }
     }
 
     fn current_duration_frames(&self) -> TFrameIdx {
         match self {
             Self::File(file) => file.current_duration_frames(),
-            Self::Queue(queue) => queue.current_duration_frames()
+            Self::Queue(queue) => queue.current_duration_frames(),
+            Self::Nested(nested) => nested.current_duration_frames(),
+            Self::Generator(gen) => gen.current_duration_frames()
         }
     }
 
     fn duration(&self) -> Option<TFrameIdx> {
         match self {
             Self::File(file) => file.duration(),
-            Self::Queue(queue) => queue.duration()
+            Self::Queue(queue) => queue.duration(),
+            Self::Nested(nested) => nested.duration(),
+            Self::Generator(gen) => gen.duration()
         }
     }
 
@@ -64,6 +162,8 @@ impl BaseSource for Source {
         match self {
             Self::File(file) => file.sample_rate(),
             Self::Queue(queue) => queue.sample_rate(),
+            Self::Nested(nested) => nested.sample_rate(),
+            Self::Generator(gen) => gen.sample_rate()
         }
     }
 
@@ -71,6 +171,80 @@ impl BaseSource for Source {
         match self {
             Self::File(file) => file.channels(),
             Self::Queue(queue) => queue.channels(),
+            Self::Nested(nested) => nested.channels(),
+            Self::Generator(gen) => gen.channels()
+        }
+    }
+
+    fn channel_layout(&self) -> ChannelLayout {
+        match self {
+            Self::File(file) => file.channel_layout(),
+            Self::Queue(queue) => queue.channel_layout(),
+            Self::Nested(nested) => nested.channel_layout(),
+            Self::Generator(gen) => gen.channel_layout()
+        }
+    }
+
+    fn loudness(&self) -> Option<TLufs> {
+        match self {
+            Self::File(file) => file.loudness(),
+            Self::Queue(queue) => queue.loudness(),
+            Self::Nested(nested) => nested.loudness(),
+            Self::Generator(gen) => gen.loudness()
+        }
+    }
+
+    fn metadata(&self) -> Option<&TrackMetadata> {
+        match self {
+            Self::File(file) => file.metadata(),
+            Self::Queue(queue) => queue.metadata(),
+            Self::Nested(nested) => nested.metadata(),
+            Self::Generator(gen) => gen.metadata()
+        }
+    }
+
+    fn waveform_overview(&self) -> Option<Arc<WaveformOverview>> {
+        match self {
+            Self::File(file) => file.waveform_overview(),
+            Self::Queue(queue) => queue.waveform_overview(),
+            Self::Nested(nested) => nested.waveform_overview(),
+            Self::Generator(gen) => gen.waveform_overview()
+        }
+    }
+
+    fn decode_errors(&self) -> u64 {
+        match self {
+            Self::File(file) => file.decode_errors(),
+            Self::Queue(queue) => queue.decode_errors(),
+            Self::Nested(nested) => nested.decode_errors(),
+            Self::Generator(gen) => gen.decode_errors()
+        }
+    }
+
+    fn is_faulted(&self) -> bool {
+        match self {
+            Self::File(file) => file.is_faulted(),
+            Self::Queue(queue) => queue.is_faulted(),
+            Self::Nested(nested) => nested.is_faulted(),
+            Self::Generator(gen) => gen.is_faulted()
+        }
+    }
+
+    fn on_idle(&mut self) {
+        match self {
+            Self::File(file) => file.on_idle(),
+            Self::Queue(queue) => queue.on_idle(),
+            Self::Nested(nested) => nested.on_idle(),
+            Self::Generator(gen) => gen.on_idle()
+        }
+    }
+
+    fn prepare(&mut self, frame_idx: TFrameIdx) {
+        match self {
+            Self::File(file) => file.prepare(frame_idx),
+            Self::Queue(queue) => queue.prepare(frame_idx),
+            Self::Nested(nested) => nested.prepare(frame_idx),
+            Self::Generator(gen) => gen.prepare(frame_idx)
         }
     }
 }
@@ -81,4 +255,12 @@ impl From<FormattedStreamSource> for Source {
 
 impl From<QueueSrc> for Source {
     fn from(value: QueueSrc) -> Self { Source::Queue(value) }
+}
+
+impl From<NestedSrc> for Source {
+    fn from(value: NestedSrc) -> Self { Source::Nested(value) }
+}
+
+impl From<GeneratorSrc> for Source {
+    fn from(value: GeneratorSrc) -> Self { Source::Generator(value) }
 }
\ No newline at end of file
@@ -0,0 +1,54 @@
+use std::{fmt::Display, io};
+
+use symphonia::core::errors::Error as SymphoniaError;
+
+/// Why a source couldn't be opened or decoded.
+#[derive(Debug)]
+pub enum SourceError {
+    /// The file doesn't exist.
+    NotFound,
+    /// The format of the stream isn't recognized, or its codec is one that the crate was built without (see the codec features).
+    UnsupportedFormat,
+    /// The stream has no track of audio to play.
+    NoAudioTrack,
+    /// The length of the stream isn't known in advance (e.g. raw ADTS), which sources need.
+    UnknownDuration,
+    /// The stream is in a supported format but its audio couldn't be decoded.
+    Decode(String),
+    Io(io::Error)
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "the file wasn't found"),
+            Self::UnsupportedFormat => write!(f, "the format isn't supported"),
+            Self::NoAudioTrack => write!(f, "there's no audio track"),
+            Self::UnknownDuration => write!(f, "the duration isn't known"),
+            Self::Decode(msg) => write!(f, "decoding failed ({})", msg),
+            Self::Io(e) => write!(f, "reading failed ({})", e)
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+impl From<io::Error> for SourceError {
+    fn from(value: io::Error) -> Self {
+        match value.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            _ => Self::Io(value)
+        }
+    }
+}
+
+impl From<SymphoniaError> for SourceError {
+    fn from(value: SymphoniaError) -> Self {
+        match value {
+            SymphoniaError::IoError(e) => e.into(),
+            SymphoniaError::Unsupported(_) => Self::UnsupportedFormat,
+            SymphoniaError::DecodeError(msg) => Self::Decode(msg.to_owned()),
+            e => Self::Decode(e.to_string())
+        }
+    }
+}
@@ -1,16 +1,31 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use rad_storage::{respond_storage, GLOBAL_SEGMENT_STORE};
+use rad_storage::GLOBAL_SEGMENT_STORE;
 use rad_storage::segment_store::PileID;
-use symphonia::core::{audio::SampleBuffer, formats::FormatReader};
-use symphonia::core::codecs::{Decoder, DecoderOptions};
-use symphonia::core::formats::{FormatOptions, SeekMode, SeekTo};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 
-use super::utils::sample_buf::SampleBuf;
-use super::{BaseSource, TFrameIdx};
+use crate::analysis::waveform::{overview_of_path, OverviewHandle, WaveformOverview};
+use crate::channel_layout::ChannelLayout;
+use crate::loudness::{loudness_from_tags, loudness_of_path, LoudnessHandle, TLufs};
+
+use decode_ahead::{decode_ahead_ms, SharedDecoder, StreamDecoder, MAX_UNDERRUN_WAIT};
+use seek::{seek_precision, SeekPrecision};
+use super::error::SourceError;
+use super::metadata::TrackMetadata;
+use super::utils::hint::hint_of_path;
+use super::{BaseSource, TFrameIdx, TSample};
+
+pub mod decode_ahead;
+pub mod seek;
+
+/// After this many packets in a row fail to be read or decoded, the source gives up on the stream and is faulted.
+pub const MAX_CONSECUTIVE_DECODE_ERRORS: u32 = 16;
 
 pub enum StreamOrigin {
     FileSystem(PathBuf),
@@ -23,93 +38,138 @@ pub enum StreamOrigin {
 pub struct FormattedStreamSource {
     storage_pile_id: PileID,
     origin: Option<StreamOrigin>,
-    reader: Box<dyn FormatReader>,
-    decoder: Box<dyn Decoder>,
+    decoding: Arc<SharedDecoder>,
     sample_rate: TFrameIdx,
     channels: u8,
-    track_id: u32,
-    last_frame_idx: TFrameIdx,
+    channel_layout: ChannelLayout,
     duration: TFrameIdx,
-    is_seekable: bool,
+    loudness: LoudnessHandle,
+    metadata: TrackMetadata,
+    overview: OverviewHandle,
 }
 
 impl FormattedStreamSource {
-    pub fn open_path(path: PathBuf) -> Option<Self> {
-        let file = std::fs::File::open(&path).unwrap();
-        let origin = StreamOrigin::FileSystem(path);
+    pub fn open_path(path: PathBuf) -> Result<Self, SourceError> {
+        let file = std::fs::File::open(&path)?;
+        let origin = StreamOrigin::FileSystem(path.clone());
 
-        Self::open_stream(Box::new(file), Some(origin))
+        let mut src = Self::open_stream(Box::new(file), &hint_of_path(&path), Some(origin))?;
+        if src.loudness.get().is_none() {
+            src.loudness = loudness_of_path(&path);
+        }
+        src.overview = overview_of_path(&path);
+
+        Ok(src)
     }
     
+    /// Opens a stream in any of the formats enabled by the codec features, `hint` tells the probe what the format likely is (see `utils::hint`).
+    /// 
     /// Warning: The stream must yield something on the first opening
-    pub fn open_stream(stream: Box<dyn MediaSource>, origin: Option<StreamOrigin>) -> Option<Self> {
+    pub fn open_stream(stream: Box<dyn MediaSource>, hint: &Hint, origin: Option<StreamOrigin>) -> Result<Self, SourceError> {
         let is_stream_seekable = stream.is_seekable();
         
         // Create the media source stream.
         let mss = MediaSourceStream::new(stream, Default::default());
 
-        // Create a probe hint using the file's extension. [Optional]
-        let mut hint = Hint::default();
-        hint.mime_type("audio/mpeg");
-
         // Use the default options for metadata and format readers.
         let meta_opts: MetadataOptions = Default::default();
-        let fmt_opts: FormatOptions = Default::default();
+        // Gapless playback leaves out the encoder delay and padding (given by the codec parameters of the track), so that the
+        // sources of a queue follow each other without silence in between.
+        let fmt_opts = FormatOptions { enable_gapless: true, ..Default::default() };
 
         // Probe the media source.
-        let probed;
-        if let Ok(_probed) = symphonia::default::get_probe()
-            .format(&hint, mss, &fmt_opts, &meta_opts) {
-            
-            probed = _probed
-        } else {
-            // TODO: Add error handling
-            return None;
-            // return Err(InitError::UnsupportedFormat);
+        let mut probed = symphonia::default::get_probe().format(hint, mss, &fmt_opts, &meta_opts)?;
+
+        // The tags may come before the container (e.g. ID3v2) or be a part of it.
+        let mut metadata = TrackMetadata::default();
+        let mut tag_loudness = None;
+        if let Some(rev) = probed.metadata.get().as_ref().and_then(|meta| meta.current()) {
+            tag_loudness = loudness_from_tags(rev.tags());
+            metadata.add_revision(rev);
         }
 
         // Get the instantiated format reader.
         let mut format = probed.format;
 
+        let tag_loudness = tag_loudness.or_else(|| format.metadata().current().and_then(|rev| loudness_from_tags(rev.tags())));
+        if let Some(rev) = format.metadata().current() { metadata.add_revision(rev); }
+
         // Find the first audio track with a known (decodable) codec.
-        let track;
-        if let Some(_track) = format.default_track() {
-            track = _track.clone();
-        } else {
-            // TODO: Add error handling
-            return None;
-            // return Err(InitError::NoTrackFound);
-        }
+        let track = format.default_track().ok_or(SourceError::NoAudioTrack)?.clone();
+
+        // The codec may be one that was left out of the build
+        let mut decoder = match symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default()) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                log::warn!("Failed to make a decoder for codec '{}': {}", track.codec_params.codec, e);
+                return Err(SourceError::UnsupportedFormat);
+            }
+        };
 
-        let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default()).unwrap();
+        // TODO: Support streams of an unknown length (e.g. raw ADTS)
+        let Some(duration) = track.codec_params.n_frames else {
+            return Err(SourceError::UnknownDuration);
+        };
 
         // Store the track identifier, it will be used to filter packets.
         let track_id = track.id;        
 
-        let decoded = decoder.decode(&format.next_packet().unwrap()).unwrap();
-        let mut buf: SampleBuffer<f32> = SampleBuffer::new(decoded.capacity() as u64, decoded.spec().clone());
-        let spec = decoded.spec().clone();
+        let decoded = decoder.decode(&format.next_packet()?)?;
+        let mut buf: SampleBuffer<f32> = SampleBuffer::new(decoded.capacity() as u64, *decoded.spec());
+        let spec = *decoded.spec();
         buf.copy_interleaved_ref(decoded);
         
         let pile_id = GLOBAL_SEGMENT_STORE.write().unwrap().new_pile_id();
+        // Some containers leave the sample rate to the codec, which gives it with the first packet
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(spec.rate) as TFrameIdx;
+        let channels = spec.channels.count() as u8;
 
-        Some(FormattedStreamSource {
+        let window = decode_ahead_ms() as TFrameIdx * sample_rate / 1000;
+        let stream = StreamDecoder::new(format, decoder, track_id, channels, sample_rate, is_stream_seekable);
+        let decoding = SharedDecoder::new(pile_id, stream, seek_precision(), window, duration as TFrameIdx);
+
+        Ok(FormattedStreamSource {
             storage_pile_id: pile_id,
-            decoder,
             origin,
-            reader: format,
-            sample_rate: track.codec_params.sample_rate.unwrap() as TFrameIdx,
-            channels: spec.channels.count() as u8,
-            track_id,
-            duration: track.codec_params.n_frames.unwrap() as TFrameIdx,
-            last_frame_idx: 0,
-            is_seekable: is_stream_seekable,
+            decoding,
+            sample_rate,
+            channels,
+            channel_layout: ChannelLayout::from_symphonia(spec.channels),
+            duration: duration as TFrameIdx,
+            loudness: tag_loudness.map(LoudnessHandle::known).unwrap_or_default(),
+            metadata,
+            overview: OverviewHandle::default(),
         })
     }
 
     pub fn origin(&self) -> &Option<StreamOrigin> {
         &self.origin
     }
+
+    pub fn seek_precision(&self) -> SeekPrecision {
+        self.decoding.seek_precision()
+    }
+
+    /// Sets how precisely the source seeks, overriding the precision set for all the sources when it was opened (see `seek::set_seek_precision`).
+    pub fn set_seek_precision(&self, precision: SeekPrecision) {
+        self.decoding.set_seek_precision(precision);
+    }
+
+    /// Sets how far the source decodes ahead of where it's read, overriding what was set for all the sources when it was opened
+    /// (see `decode_ahead::set_decode_ahead_ms`). With 0 it's decoded by whatever reads it.
+    pub fn set_decode_ahead_ms(&self, ms: u32) {
+        self.decoding.set_window(ms as TFrameIdx * self.sample_rate / 1000);
+    }
+
+    /// Whether a worker is decoding the source ahead right now, which it only does while there's something left to decode.
+    pub fn is_decoding_ahead(&self) -> bool {
+        self.decoding.is_working()
+    }
+
+    /// Sets where the overview of the waveform comes from for streams that aren't files (see `analysis::waveform::overview_of_incoming_stream`).
+    pub fn set_overview(&mut self, overview: OverviewHandle) {
+        self.overview = overview;
+    }
 }
 
 impl BaseSource for FormattedStreamSource {
@@ -126,27 +186,117 @@ impl BaseSource for FormattedStreamSource {
     }
 
     fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<super::TSample>> {
-        respond_storage!(self.storage_pile_id, frame_idx);
+        let mut frame = vec![0.0; self.channels as usize];
+
+        (self.read_block(frame_idx, &mut frame) == 1).then_some(frame)
+    }
+
+    fn read_block(&mut self, frame_idx: TFrameIdx, out: &mut [TSample]) -> usize {
+        let channels = self.channels as usize;
+        let frames = out.len() / channels;
+        let mut written = 0;
+        // The frame that was last decoded for, in case the store didn't keep what was decoded.
+        let mut decoded_for = None;
+        // The worker is waited for once a read at most, so that a worker that keeps falling behind doesn't hold up the compositor
+        let mut waited = false;
+        let decodes_ahead = self.decoding.decodes_ahead();
 
-        if frame_idx != self.last_frame_idx + 1 {
-            let seek_time = SeekTo::TimeStamp { ts: frame_idx as u64, track_id: self.track_id };
-            self.reader.seek(SeekMode::Accurate, seek_time).ok()?;
+        if decodes_ahead {
+            self.decoding.set_playhead(frame_idx);
         }
 
-        let next_packet = self.reader.next_packet().ok()?;
-        let decoded = self.decoder.decode(&next_packet).ok()?;
+        while written < frames {
+            let idx = frame_idx + written as TFrameIdx;
 
-        let buf = SampleBuf::from_audio_buf_ref(next_packet.ts as TFrameIdx, &decoded);
-        
-        self.last_frame_idx = buf.start() + buf.frame_count() - 1;
+            // Copying as much as possible from each stored segment while holding the lock once for it
+            let copied = {
+                let mut store = GLOBAL_SEGMENT_STORE.write().unwrap();
+                match store.find(self.storage_pile_id, idx) {
+                    Some(seg) => {
+                        let seg_offset = (idx - seg.frame_idx) as usize;
+                        let n = (seg.frames() as usize - seg_offset).min(frames - written);
+                        let data = &seg.data.fetch()[seg_offset * channels..(seg_offset + n) * channels];
 
-        GLOBAL_SEGMENT_STORE.write().unwrap()
-            .insert(self.storage_pile_id, buf.start(), buf.channels, buf.samples.into_boxed_slice(), !self.is_seekable);
+                        out[written * channels..(written + n) * channels].copy_from_slice(data);
+                        n
+                    },
+                    None => 0
+                }
+            };
 
-        self.get_by_frame_i(frame_idx)
+            if copied == 0 {
+                if self.duration <= idx { break; }
+
+                // Skipped parts of the stream (see `SharedDecoder::holes`) aren't stored, they're silent until they're decoded
+                if let Some(hole_end) = self.decoding.hole_end(idx) {
+                    let n = ((hole_end - idx) as usize).min(frames - written);
+                    out[written * channels..(written + n) * channels].fill(0.0);
+                    written += n;
+                    continue;
+                }
+
+                if !decodes_ahead {
+                    if decoded_for == Some(idx) { break; }
+                    decoded_for = Some(idx);
+
+                    if self.decoding.decode_until(idx).is_none() { break; }
+                } else if self.decoding.is_past_end(idx) {
+                    break;
+                } else if !waited && self.decoding.wait_for(idx, MAX_UNDERRUN_WAIT) {
+                    // The worker caught up (e.g. right after it started or a seek), or found the stream to end before the frame
+                    waited = true;
+                } else {
+                    // The worker is still behind, so the rest is played as silence instead of holding up the compositor any longer
+                    let end = frames.min((self.duration - frame_idx) as usize);
+                    log::debug!("A file source underran, playing {} frames of silence.", end - written);
+                    out[written * channels..end * channels].fill(0.0);
+                    written = end;
+                }
+            } else {
+                written += copied;
+            }
+        }
+
+        written
     }
 
     fn channels(&self) -> u8 {
         self.channels
     }
-}
\ No newline at end of file
+
+    fn channel_layout(&self) -> ChannelLayout {
+        self.channel_layout
+    }
+
+    fn loudness(&self) -> Option<TLufs> {
+        self.loudness.get()
+    }
+
+    fn metadata(&self) -> Option<&TrackMetadata> {
+        (!self.metadata.is_empty()).then_some(&self.metadata)
+    }
+
+    fn waveform_overview(&self) -> Option<Arc<WaveformOverview>> {
+        self.overview.get()
+    }
+
+    fn decode_errors(&self) -> u64 {
+        self.decoding.decode_errors()
+    }
+
+    fn is_faulted(&self) -> bool {
+        self.decoding.is_faulted()
+    }
+
+    fn prepare(&mut self, frame_idx: TFrameIdx) {
+        if self.decoding.decodes_ahead() {
+            self.decoding.set_playhead(frame_idx);
+        }
+    }
+}
+
+impl Drop for FormattedStreamSource {
+    fn drop(&mut self) {
+        self.decoding.stop();
+    }
+}
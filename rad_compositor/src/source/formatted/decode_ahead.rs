@@ -0,0 +1,406 @@
+// Decoding of the formatted streams ahead of where they're read, on a worker thread of each source that is being played,
+// so that a slow disk or a heavy codec doesn't hold up the compositors reading the sources.
+
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rad_storage::GLOBAL_SEGMENT_STORE;
+use rad_storage::segment_store::PileID;
+use symphonia::core::codecs::Decoder;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo};
+
+use crate::source::utils::sample_buf::SampleBuf;
+use crate::source::TFrameIdx;
+
+use super::seek::{PacketIndex, SeekPrecision};
+use super::MAX_CONSECUTIVE_DECODE_ERRORS;
+
+/// How far (in milliseconds) file sources decode ahead of where they're read, unless it's set otherwise (see `set_decode_ahead_ms`).
+pub const DEFAULT_DECODE_AHEAD_MS: u32 = 2000;
+/// How many packets ahead of the stream a frame may be for the packets up to it to be decoded rather than seeking past them.
+const MAX_FORWARD_PACKETS: u64 = 32;
+/// How long a read waits for the worker to decode a frame it's behind on (e.g. right after it started or a seek) before playing silence.
+/// It's short enough for the compositors to stay ahead, as they compute a few hundred milliseconds ahead.
+pub(super) const MAX_UNDERRUN_WAIT: Duration = Duration::from_millis(50);
+
+static DECODE_AHEAD_MS: AtomicU32 = AtomicU32::new(DEFAULT_DECODE_AHEAD_MS);
+
+/// Sets how far the sources opened from now on decode ahead of where they're read.
+/// With 0 there are no workers, and the sources decode on the threads reading them (i.e. the compositors).
+pub fn set_decode_ahead_ms(ms: u32) {
+    DECODE_AHEAD_MS.store(ms, Ordering::Relaxed);
+}
+
+pub fn decode_ahead_ms() -> u32 {
+    DECODE_AHEAD_MS.load(Ordering::Relaxed)
+}
+
+/// What a stream is read and decoded with, only ever used by one thread at a time.
+pub(super) struct StreamDecoder {
+    reader: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    channels: u8,
+    sample_rate: TFrameIdx,
+    is_seekable: bool,
+    /// Where the next packet of the stream starts, `None` when it isn't known (e.g. right after a seek).
+    position: Option<TFrameIdx>,
+    index: PacketIndex,
+    consecutive_errors: u32,
+}
+
+impl StreamDecoder {
+    pub fn new(reader: Box<dyn FormatReader>, decoder: Box<dyn Decoder>, track_id: u32, channels: u8, sample_rate: TFrameIdx, is_seekable: bool) -> Self {
+        StreamDecoder { reader, decoder, track_id, channels, sample_rate, is_seekable, position: None, index: PacketIndex::default(), consecutive_errors: 0 }
+    }
+
+    /// Whether the frame is few enough packets ahead of the stream to decode the packets up to it instead of seeking.
+    fn reaches(&self, frame_idx: TFrameIdx) -> bool {
+        let Some(position) = self.position.filter(|&pos| pos <= frame_idx) else { return false; };
+
+        match self.index.packets_between(position, frame_idx) {
+            Some(packets) => packets <= MAX_FORWARD_PACKETS,
+            // Half a second is about as many packets for most codecs
+            None => frame_idx - position <= self.sample_rate / 2
+        }
+    }
+}
+
+/// Tells the worker where the source is read at.
+struct WorkerSignal {
+    /// `None` until the source is first read or is about to be (see `BaseSource::prepare`),
+    /// so that sources waiting in a queue don't decode (and take up the cache or a thread) long before they play.
+    playhead: Option<TFrameIdx>,
+    /// Whether a worker is running, which it isn't before the playhead is first set nor after it had nothing left to decode.
+    running: bool,
+    stopped: bool,
+}
+
+/// The decoding of a stream into the segment store, shared by its source and the worker decoding it ahead.
+pub(super) struct SharedDecoder {
+    pile_id: PileID,
+    stream: Mutex<StreamDecoder>,
+    /// The number of packets that failed to be read or decoded so far.
+    decode_errors: AtomicU64,
+    /// Set once too many packets failed in a row, after which nothing more is decoded.
+    faulted: AtomicBool,
+    /// Whether seeks land near the frames rather than on them (see `SeekPrecision`).
+    coarse_seeks: AtomicBool,
+    /// The first frame found to be past the end of the stream, `TFrameIdx::MAX` until the end is reached.
+    end: AtomicU64,
+    /// How many frames after the playhead the worker keeps decoded, with 0 the stream is decoded by whatever reads it.
+    window: AtomicU64,
+    /// The length of the stream, which the worker doesn't decode past.
+    duration: TFrameIdx,
+    signal: Mutex<WorkerSignal>,
+    /// Wakes up the worker when the playhead moves or the source is dropped.
+    work_cnd: Condvar,
+    /// Wakes up the reads waiting for the worker whenever it has decoded something.
+    decoded_cnd: Condvar,
+    /// The parts of the stream that were skipped over (by coarse seeks landing after the frame or by the reader losing packets),
+    /// which are played as silence. They're kept out of the segment store so that they're decoded once the stream gets there again,
+    /// and are forgotten on the next seek.
+    holes: Mutex<Vec<(TFrameIdx, TFrameIdx)>>,
+}
+
+impl SharedDecoder {
+    /// Makes the decoding of a stream, which is decoded ahead by a worker keeping the `window` frames after the playhead decoded
+    /// whenever the playhead is set.
+    pub fn new(pile_id: PileID, stream: StreamDecoder, precision: SeekPrecision, window: TFrameIdx, duration: TFrameIdx) -> Arc<Self> {
+        Arc::new(SharedDecoder {
+            pile_id,
+            stream: Mutex::new(stream),
+            decode_errors: AtomicU64::new(0),
+            faulted: AtomicBool::new(false),
+            coarse_seeks: AtomicBool::new(precision == SeekPrecision::Coarse),
+            end: AtomicU64::new(TFrameIdx::MAX),
+            window: AtomicU64::new(window),
+            duration,
+            signal: Mutex::new(WorkerSignal { playhead: None, running: false, stopped: false }),
+            work_cnd: Condvar::new(),
+            decoded_cnd: Condvar::new(),
+            holes: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn decodes_ahead(&self) -> bool {
+        self.window.load(Ordering::Relaxed) != 0
+    }
+
+    /// Sets how many frames the worker keeps decoded after the playhead, the worker stops with 0.
+    pub fn set_window(&self, window: TFrameIdx) {
+        self.window.store(window, Ordering::Relaxed);
+        let _signal = self.signal.lock().unwrap();
+        self.work_cnd.notify_all();
+    }
+
+    pub fn decode_errors(&self) -> u64 {
+        self.decode_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn is_faulted(&self) -> bool {
+        self.faulted.load(Ordering::Relaxed)
+    }
+
+    pub fn seek_precision(&self) -> SeekPrecision {
+        if self.coarse_seeks.load(Ordering::Relaxed) { SeekPrecision::Coarse } else { SeekPrecision::Accurate }
+    }
+
+    pub fn set_seek_precision(&self, precision: SeekPrecision) {
+        self.coarse_seeks.store(precision == SeekPrecision::Coarse, Ordering::Relaxed);
+    }
+
+    /// The end of the hole the frame falls in, if it falls in one.
+    pub fn hole_end(&self, frame_idx: TFrameIdx) -> Option<TFrameIdx> {
+        self.holes.lock().unwrap().iter().find(|(start, end)| (*start..*end).contains(&frame_idx)).map(|(_, end)| *end)
+    }
+
+    fn add_hole(&self, start: TFrameIdx, end: TFrameIdx) {
+        self.holes.lock().unwrap().push((start, end));
+    }
+
+    /// Takes the frames that have been decoded out of the holes.
+    fn fill_holes(&self, start: TFrameIdx, end: TFrameIdx) {
+        let mut holes = self.holes.lock().unwrap();
+        if holes.is_empty() { return; }
+
+        *holes = holes.iter().flat_map(|&(h_start, h_end)| [(h_start, h_end.min(start)), (h_start.max(end), h_end)])
+            .filter(|(h_start, h_end)| h_start < h_end)
+            .collect();
+    }
+
+    /// Whether the frame will never be decoded, as the stream ends before it or it has been given up on.
+    pub fn is_past_end(&self, frame_idx: TFrameIdx) -> bool {
+        self.is_faulted() || self.end.load(Ordering::Relaxed) <= frame_idx
+    }
+
+    /// Starts the worker, which runs until it has nothing left to decode or `stop` is called.
+    fn spawn_worker(self: &Arc<Self>) {
+        let shared = self.clone();
+
+        thread::Builder::new()
+            .name("decode-ahead".into())
+            .spawn(move || shared.work())
+            .unwrap();
+    }
+
+    pub fn stop(&self) {
+        self.signal.lock().unwrap().stopped = true;
+        self.work_cnd.notify_all();
+    }
+
+    /// Whether a worker is decoding the stream ahead right now.
+    pub fn is_working(&self) -> bool {
+        self.signal.lock().unwrap().running
+    }
+
+    /// Moves the playhead the worker decodes ahead of, starting the worker if it isn't running.
+    pub fn set_playhead(self: &Arc<Self>, frame_idx: TFrameIdx) {
+        let mut signal = self.signal.lock().unwrap();
+        if signal.stopped || (signal.playhead == Some(frame_idx) && signal.running) { return; }
+
+        signal.playhead = Some(frame_idx);
+        self.work_cnd.notify_all();
+
+        if !signal.running && self.decodes_ahead() && self.first_missing(frame_idx, self.window_end(frame_idx)).is_some() {
+            signal.running = true;
+            drop(signal);
+            self.spawn_worker();
+        }
+    }
+
+    /// The frame up to which the worker keeps the stream decoded after the playhead.
+    fn window_end(&self, playhead: TFrameIdx) -> TFrameIdx {
+        playhead.saturating_add(self.window.load(Ordering::Relaxed)).min(self.duration)
+    }
+
+    /// Waits up to `timeout` for the worker to decode the frame, and gives whether the frame is stored or will never be.
+    pub fn wait_for(&self, frame_idx: TFrameIdx, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut signal = self.signal.lock().unwrap();
+
+        loop {
+            if self.is_past_end(frame_idx) || self.hole_end(frame_idx).is_some() || GLOBAL_SEGMENT_STORE.write().unwrap().find(self.pile_id, frame_idx).is_some() {
+                return true;
+            }
+            if !signal.running { return false; }
+
+            let Some(left) = deadline.checked_duration_since(Instant::now()) else { return false; };
+            signal = self.decoded_cnd.wait_timeout(signal, left).unwrap().0;
+        }
+    }
+
+    fn work(&self) {
+        let mut signal = self.signal.lock().unwrap();
+
+        loop {
+            let missing = signal.playhead
+                .filter(|_| !signal.stopped && self.decodes_ahead())
+                .map(|playhead| (self.window_end(playhead), self.first_missing(playhead, self.window_end(playhead))));
+
+            match missing {
+                Some((_, Some(frame_idx))) => {
+                    drop(signal);
+                    self.decode_until(frame_idx);
+                    signal = self.signal.lock().unwrap();
+                    self.decoded_cnd.notify_all();
+                },
+                // Sleeping until the playhead moves, which is also when anything dropped from the cache of the segment store is noticed
+                Some((window_end, None)) if window_end < self.duration && !self.is_past_end(window_end) => {
+                    signal = self.work_cnd.wait(signal).unwrap();
+                },
+                // Sources decoded up to their end (or faulted) don't keep a thread, the worker is started again when they're read elsewhere
+                _ => {
+                    signal.running = false;
+                    self.decoded_cnd.notify_all();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// The first frame from `start` on and before `end` that isn't stored and can still be decoded.
+    fn first_missing(&self, start: TFrameIdx, end: TFrameIdx) -> Option<TFrameIdx> {
+        let mut store = GLOBAL_SEGMENT_STORE.write().unwrap();
+        let mut frame_idx = start;
+
+        while frame_idx < end && !self.is_past_end(frame_idx) {
+            match store.find(self.pile_id, frame_idx).map(|seg| seg.frame_idx + seg.frames()).or_else(|| self.hole_end(frame_idx)) {
+                Some(end) => frame_idx = end,
+                None => return Some(frame_idx)
+            }
+        }
+
+        None
+    }
+
+    /// Counts a packet that couldn't be read or decoded, and gives `None` once the stream is given up on.
+    fn record_error(&self, stream: &mut StreamDecoder, err: &SymphoniaError) -> Option<()> {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+        stream.consecutive_errors += 1;
+
+        if MAX_CONSECUTIVE_DECODE_ERRORS <= stream.consecutive_errors {
+            log::error!("Giving up on a stream after {} packets in a row failed, the last one with: {}", stream.consecutive_errors, err);
+            self.faulted.store(true, Ordering::Relaxed);
+            return None;
+        }
+
+        log::warn!("Skipping a packet that failed with: {}", err);
+        Some(())
+    }
+
+    fn store(&self, stream: &StreamDecoder, buf: SampleBuf) {
+        let mut store = GLOBAL_SEGMENT_STORE.write().unwrap();
+        // Decoding forward goes over the packets stored already since the stream was last there
+        if store.find(self.pile_id, buf.start()).is_some() { return; }
+
+        self.fill_holes(buf.start(), buf.start() + buf.frame_count());
+        store.insert(self.pile_id, buf.start(), buf.channels, buf.samples.into_boxed_slice(), !stream.is_seekable);
+    }
+
+    /// Seeks to the packet holding the frame, as precisely as set for the source.
+    fn seek(&self, stream: &mut StreamDecoder, frame_idx: TFrameIdx) -> Option<()> {
+        // Aiming at the start of the packet if it has been indexed, which the readers land on more readily than a frame within it
+        let ts = stream.index.packet_of(frame_idx).map_or(frame_idx, |(start, _)| start);
+
+        stream.position = None;
+        self.holes.lock().unwrap().clear();
+
+        let precision = self.seek_precision();
+        let mode = match precision {
+            SeekPrecision::Accurate => SeekMode::Accurate,
+            SeekPrecision::Coarse => SeekMode::Coarse
+        };
+        let landed = stream.reader.seek(mode, SeekTo::TimeStamp { ts, track_id: stream.track_id }).ok()?.actual_ts;
+        stream.decoder.reset();
+
+        if landed <= frame_idx || precision == SeekPrecision::Accurate { return Some(()); }
+
+        // Coarse seeks may land a little after the frame, the frames skipped over are silent
+        if landed - frame_idx <= stream.sample_rate {
+            self.add_hole(frame_idx, landed);
+        } else {
+            // Too far off to cover up
+            stream.reader.seek(SeekMode::Accurate, SeekTo::TimeStamp { ts, track_id: stream.track_id }).ok()?;
+            stream.decoder.reset();
+        }
+
+        Some(())
+    }
+
+    /// Decodes packets into the segment store until the frame at `frame_idx` is stored,
+    /// seeking first unless the frame is a few packets ahead in the stream.
+    ///
+    /// Packets that fail to decode are stored as silence in their place, so that a corrupt part of a stream only goes quiet.
+    pub fn decode_until(&self, frame_idx: TFrameIdx) -> Option<()> {
+        let res = self.decode_locked(&mut self.stream.lock().unwrap(), frame_idx);
+
+        // The frame couldn't be got to, so the stream ends before it
+        if res.is_none() {
+            self.end.fetch_min(frame_idx, Ordering::Relaxed);
+        }
+
+        res
+    }
+
+    fn decode_locked(&self, stream: &mut StreamDecoder, frame_idx: TFrameIdx) -> Option<()> {
+        if self.is_faulted() { return None; }
+
+        if !stream.reaches(frame_idx) {
+            self.seek(stream, frame_idx)?;
+        }
+
+        loop {
+            let next_packet = match stream.reader.next_packet() {
+                Ok(packet) => packet,
+                // The end of the stream
+                Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => return None,
+                Err(SymphoniaError::ResetRequired) => { stream.decoder.reset(); continue; },
+                Err(e) => { self.record_error(stream, &e)?; continue; }
+            };
+            if next_packet.track_id() != stream.track_id { continue; }
+            stream.index.insert(next_packet.ts as TFrameIdx, next_packet.dur as u32);
+
+            let buf = match stream.decoder.decode(&next_packet) {
+                Ok(decoded) => {
+                    stream.consecutive_errors = 0;
+                    let mut buf = SampleBuf::from_audio_buf_ref(next_packet.ts as TFrameIdx, &decoded);
+
+                    // Not every decoder trims the frames of the encoder delay and padding off the packets by itself
+                    if next_packet.dur < buf.frame_count() {
+                        buf.trim(next_packet.trim_start() as TFrameIdx, next_packet.trim_end() as TFrameIdx);
+                    }
+
+                    buf
+                },
+                Err(SymphoniaError::ResetRequired) => { stream.decoder.reset(); continue; },
+                Err(e) => {
+                    self.record_error(stream, &e)?;
+                    // Concealing the packet with silence of the length it would have had
+                    SampleBuf::new(next_packet.ts as TFrameIdx, vec![0.0; next_packet.dur as usize * stream.channels as usize], stream.channels)
+                }
+            };
+            if buf.frame_count() == 0 { continue; }
+
+            // Readers skip the packets they can't make sense of (e.g. ones failing their checksum), the hole left is played as silence.
+            // Holes of more than a second are taken as jumps in the timestamps rather than lost packets.
+            if let Some(gap_start) = stream.position.filter(|&e| e < buf.start() && buf.start() - e <= stream.sample_rate) {
+                log::warn!("Concealing {} frames lost from a stream with silence.", buf.start() - gap_start);
+                self.decode_errors.fetch_add(1, Ordering::Relaxed);
+
+                self.add_hole(gap_start, buf.start());
+            }
+
+            let end = buf.start() + buf.frame_count();
+            stream.position = Some(end);
+            self.store(stream, buf);
+
+            // The seek may land on a packet before the frame
+            if frame_idx < end { return Some(()); }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// How precisely seeks in the formatted streams land, and an index of the packets read so far that tells when a frame
+// is close enough to decode forward to instead of seeking.
+//
+// The index is partial, as it's grown while the stream is read rather than built up front. Jumping to parts of a stream
+// that haven't been read is left to the seeking of its format reader, which with accurate seeks in some formats (e.g. MP3
+// without a seek table) reads through the stream up to there. Coarse seeks are quick there too, at the cost of landing near the frame.
+
+use std::{fmt::Display, str::FromStr, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::source::TFrameIdx;
+
+static COARSE_SEEKS: AtomicBool = AtomicBool::new(false);
+
+/// How precisely seeking in a stream lands on the frame it's asked for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SeekPrecision {
+    /// Lands exactly on the frame, which some formats (e.g. MP3 without a seek table) can only do by reading the stream from its start.
+    #[default]
+    Accurate,
+    /// Lands on a packet near the frame as quickly as the format allows (e.g. by estimating where it is from the bitrate),
+    /// so that seeking doesn't read through the stream even in files hours long. The frames skipped over by landing after the frame are silent.
+    Coarse,
+}
+
+impl FromStr for SeekPrecision {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "accurate" => Ok(Self::Accurate),
+            "coarse" => Ok(Self::Coarse),
+            _ => Err(())
+        }
+    }
+}
+
+impl Display for SeekPrecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Accurate => write!(f, "accurate"),
+            Self::Coarse => write!(f, "coarse")
+        }
+    }
+}
+
+/// Sets how precisely the file sources seek from now on.
+pub fn set_seek_precision(precision: SeekPrecision) {
+    COARSE_SEEKS.store(precision == SeekPrecision::Coarse, Ordering::Relaxed);
+}
+
+pub fn seek_precision() -> SeekPrecision {
+    if COARSE_SEEKS.load(Ordering::Relaxed) { SeekPrecision::Coarse } else { SeekPrecision::Accurate }
+}
+
+/// Packets of the same length following each other.
+#[derive(Clone, Copy)]
+struct PacketRun {
+    start: TFrameIdx,
+    dur: u32,
+    count: u32,
+}
+
+impl PacketRun {
+    fn end(&self) -> TFrameIdx {
+        self.start + self.dur as TFrameIdx * self.count as TFrameIdx
+    }
+}
+
+/// Where the packets of a stream start, grown as the stream is read.
+/// It only knows the packets that have been read, so it helps with going over what has been played again rather than with jumping ahead.
+///
+/// Packets of the same length following each other take up a single run, so a whole file of packets of a constant length
+/// (e.g. MP3 or AAC) takes next to no memory however long it is.
+#[derive(Default)]
+pub struct PacketIndex {
+    runs: Vec<PacketRun>,
+}
+
+impl PacketIndex {
+    pub fn insert(&mut self, ts: TFrameIdx, dur: u32) {
+        if dur == 0 { return; }
+
+        // The runs before `idx` start at or before the packet
+        let idx = self.runs.partition_point(|run| run.start <= ts);
+
+        if let Some(prev) = idx.checked_sub(1).map(|i| &mut self.runs[i]) {
+            if ts < prev.end() { return; }
+
+            if ts == prev.end() && dur == prev.dur {
+                prev.count += 1;
+
+                // The packet may close the hole between two runs
+                let end = prev.end();
+                if let Some(next) = self.runs.get(idx).copied().filter(|next| next.start == end && next.dur == dur) {
+                    self.runs[idx - 1].count += next.count;
+                    self.runs.remove(idx);
+                }
+
+                return;
+            }
+        }
+
+        match self.runs.get_mut(idx) {
+            Some(next) if next.start == ts + dur as TFrameIdx && next.dur == dur => {
+                next.start = ts;
+                next.count += 1;
+            },
+            _ => self.runs.insert(idx, PacketRun { start: ts, dur, count: 1 })
+        }
+    }
+
+    /// Where the packet holding the frame starts and how long it is, if the packet has been indexed.
+    pub fn packet_of(&self, frame_idx: TFrameIdx) -> Option<(TFrameIdx, u32)> {
+        let run = self.runs[self.runs.partition_point(|run| run.start <= frame_idx).checked_sub(1)?];
+        let nth = (frame_idx - run.start) / run.dur as TFrameIdx;
+
+        (nth < run.count as TFrameIdx).then(|| (run.start + nth * run.dur as TFrameIdx, run.dur))
+    }
+
+    /// The number of packets from the one holding `from` up to (not including) the one holding `to`, if all of them have been indexed.
+    pub fn packets_between(&self, from: TFrameIdx, to: TFrameIdx) -> Option<u64> {
+        let mut idx = self.runs.partition_point(|run| run.start <= from).checked_sub(1)?;
+        let mut pos = self.packet_of(from)?.0;
+        let mut n = 0;
+
+        while pos <= to {
+            let run = self.runs.get(idx).filter(|run| run.start <= pos && pos < run.end())?;
+
+            if to < run.end() {
+                return Some(n + (to - pos) / run.dur as TFrameIdx);
+            }
+
+            n += (run.end() - pos) / run.dur as TFrameIdx;
+            pos = run.end();
+            idx += 1;
+        }
+
+        Some(n)
+    }
+
+    /// The number of packets indexed.
+    pub fn len(&self) -> u64 {
+        self.runs.iter().map(|run| run.count as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// The number of runs the packets are kept in.
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+}
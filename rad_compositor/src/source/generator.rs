@@ -0,0 +1,349 @@
+use std::{f64::consts::PI, fmt::Display, str::FromStr};
+
+use crate::channel_layout::ChannelLayout;
+
+use super::{BaseSource, TFrameIdx, TSample};
+
+/// The sample-rate generators are made at when none is given.
+pub const DEFAULT_GENERATOR_SAMPLE_RATE: TFrameIdx = 48000;
+const DEFAULT_AMPLITUDE: f32 = 0.5;
+/// Sweeps need a duration for knowing how fast to sweep.
+const DEFAULT_SWEEP_SECS: f64 = 10.0;
+/// The number of octaves of the pink noise, each octave being a white noise held for twice as long as the one before.
+const PINK_NOISE_OCTAVES: u64 = 16;
+const CLICK_SECS: f64 = 0.02;
+/// The frequencies of the clicks on the first beat of each bar and on the other beats.
+const CLICK_ACCENT_FREQ: f64 = 1500.0;
+const CLICK_FREQ: f64 = 1000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseColor {
+	White,
+	/// Noise with the same energy in every octave.
+	Pink
+}
+
+impl NoiseColor {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::White => "white",
+			Self::Pink => "pink"
+		}
+	}
+}
+
+impl FromStr for NoiseColor {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"white" => Ok(Self::White),
+			"pink" => Ok(Self::Pink),
+			_ => Err(())
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+	Sine { freq: f64 },
+	/// A logarithmic sweep going from `from` to `to` (in Hz) over the duration of the source.
+	Sweep { from: f64, to: f64 },
+	/// The same seed always makes the same noise.
+	Noise { color: NoiseColor, seed: u64 },
+	Silence,
+	/// A metronome ticking `bpm` times a minute, with the first beat of every `beats` beats accented.
+	Click { bpm: f64, beats: u32 }
+}
+
+/// A uniformly distributed value in [-1, 1) that only depends on its arguments.
+fn noise_at(seed: u64, octave: u64, idx: u64) -> f64 {
+	// SplitMix64
+	let mut z = seed
+		.wrapping_add(octave.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+		.wrapping_add(idx.wrapping_mul(0xD1B5_4A32_D192_ED03));
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	z ^= z >> 31;
+
+	(z >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+}
+
+/// A source making test signals instead of reading them from somewhere, written as `{waveform}[:{key}={value},...]`:
+/// - `sine:freq={hz}`
+/// - `sweep:from={hz},to={hz}` for a logarithmic sweep over the duration (10 seconds if not given)
+/// - `noise[:color={white|pink},seed={seed}]`
+/// - `silence:dur={secs}`
+/// - `click[:bpm={bpm},beats={beats-per-bar}]`
+///
+/// Every generator also takes `amp={amp}`, `dur={secs}` (endless if not given), `layout={layout}`, `rate={hz}`,
+/// and `ch={channel-index}` for only playing on one channel of the layout.
+///
+/// Every frame only depends on its index, so generators can be read from anywhere and always give the same frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratorSrc {
+	waveform: Waveform,
+	sample_rate: TFrameIdx,
+	channel_layout: ChannelLayout,
+	/// The only channel that is played on, all of them if not set.
+	channel: Option<u8>,
+	amplitude: f32,
+	duration: Option<TFrameIdx>
+}
+
+impl GeneratorSrc {
+	/// Makes an endless mono generator.
+	pub fn new(waveform: Waveform, sample_rate: TFrameIdx) -> Self {
+		GeneratorSrc {
+			waveform,
+			sample_rate,
+			channel_layout: ChannelLayout::Mono,
+			channel: None,
+			amplitude: DEFAULT_AMPLITUDE,
+			duration: None
+		}
+	}
+
+	pub fn waveform(&self) -> Waveform {
+		self.waveform
+	}
+
+	pub fn set_amplitude(&mut self, amp: f32) {
+		self.amplitude = amp;
+	}
+
+	/// Makes the generator end after `duration` frames, or never with `None`.
+	pub fn set_duration(&mut self, duration: Option<TFrameIdx>) {
+		self.duration = duration;
+	}
+
+	pub fn set_channel_layout(&mut self, layout: ChannelLayout) {
+		self.channel_layout = layout;
+	}
+
+	/// Plays only on the channel at `channel` of the layout, or on all of them with `None`.
+	pub fn set_channel(&mut self, channel: Option<u8>) {
+		self.channel = channel;
+	}
+
+	/// The value of the frame at `frame_idx` before being amplified.
+	fn value_at(&self, frame_idx: TFrameIdx) -> f64 {
+		let sample_rate = self.sample_rate as f64;
+		let t = frame_idx as f64 / sample_rate;
+
+		match self.waveform {
+			Waveform::Sine { freq } => (2.0 * PI * (freq * t).fract()).sin(),
+			Waveform::Sweep { from, to } => {
+				let secs = self.duration.map_or(DEFAULT_SWEEP_SECS, |d| d as f64 / sample_rate);
+				let rate = (to / from).ln();
+				if rate == 0.0 { return (2.0 * PI * (from * t).fract()).sin(); }
+
+				let cycles = from * secs / rate * ((t / secs * rate).exp() - 1.0);
+				(2.0 * PI * cycles.fract()).sin()
+			},
+			Waveform::Noise { color: NoiseColor::White, seed } => noise_at(seed, 0, frame_idx),
+			Waveform::Noise { color: NoiseColor::Pink, seed } => {
+				// Voss-McCartney, each octave changing half as often as the one before it
+				let sum = (0..=PINK_NOISE_OCTAVES).map(|octave| noise_at(seed, octave, frame_idx >> octave)).sum::<f64>();
+
+				(sum / (PINK_NOISE_OCTAVES + 1) as f64 * 3.0).clamp(-1.0, 1.0)
+			},
+			Waveform::Silence => 0.0,
+			Waveform::Click { bpm, beats } => {
+				let beat_frames = sample_rate * 60.0 / bpm;
+				let beat = (frame_idx as f64 / beat_frames).floor();
+				let pos = (frame_idx as f64 - beat * beat_frames) / sample_rate;
+				if CLICK_SECS <= pos { return 0.0; }
+
+				let freq = if (beat as u64).is_multiple_of(beats as u64) { CLICK_ACCENT_FREQ } else { CLICK_FREQ };
+				let envelope = (1.0 - pos / CLICK_SECS).powi(2);
+
+				envelope * (2.0 * PI * freq * pos).sin()
+			}
+		}
+	}
+
+	fn write_frame(&self, frame_idx: TFrameIdx, frame: &mut [TSample]) {
+		let value = (self.value_at(frame_idx) * self.amplitude as f64) as TSample;
+
+		match self.channel {
+			Some(ch) => {
+				frame.fill(0.0);
+				frame[ch as usize] = value;
+			},
+			None => frame.fill(value)
+		}
+	}
+}
+
+impl BaseSource for GeneratorSrc {
+	fn sample_rate(&self) -> TFrameIdx {
+		self.sample_rate
+	}
+
+	fn duration(&self) -> Option<TFrameIdx> {
+		Some(self.duration.unwrap_or(0))
+	}
+
+	fn current_duration_frames(&self) -> TFrameIdx {
+		self.duration.unwrap_or(0)
+	}
+
+	fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<TSample>> {
+		if self.duration.is_some_and(|d| d <= frame_idx) { return None; }
+
+		let mut frame = vec![0.0; self.channel_layout.channels() as usize];
+		self.write_frame(frame_idx, &mut frame);
+
+		Some(frame)
+	}
+
+	fn read_block(&mut self, frame_idx: TFrameIdx, out: &mut [TSample]) -> usize {
+		let channels = self.channel_layout.channels() as usize;
+		let mut frames = out.len() / channels;
+		if let Some(duration) = self.duration {
+			frames = frames.min(duration.saturating_sub(frame_idx) as usize);
+		}
+
+		for (i, frame) in out.chunks_exact_mut(channels).take(frames).enumerate() {
+			self.write_frame(frame_idx + i as TFrameIdx, frame);
+		}
+
+		frames
+	}
+
+	fn channels(&self) -> u8 {
+		self.channel_layout.channels()
+	}
+
+	fn channel_layout(&self) -> ChannelLayout {
+		self.channel_layout
+	}
+}
+
+impl Display for GeneratorSrc {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let (name, args) = match self.waveform {
+			Waveform::Sine { freq } => ("sine", format!("freq={},", freq)),
+			Waveform::Sweep { from, to } => ("sweep", format!("from={},to={},", from, to)),
+			Waveform::Noise { color, seed } => ("noise", format!("color={},seed={},", color.name(), seed)),
+			Waveform::Silence => ("silence", String::new()),
+			Waveform::Click { bpm, beats } => ("click", format!("bpm={},beats={},", bpm, beats))
+		};
+
+		write!(f, "{}:{}amp={},layout={},rate={}", name, args, self.amplitude, self.channel_layout, self.sample_rate)?;
+
+		if let Some(duration) = self.duration {
+			write!(f, ",dur={}", duration as f64 / self.sample_rate as f64)?;
+		}
+		if let Some(ch) = self.channel {
+			write!(f, ",ch={}", ch)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl FromStr for GeneratorSrc {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, args) = s.split_once(':').unwrap_or((s, ""));
+		let args = args.split(',')
+			.filter(|arg| !arg.is_empty())
+			.map(|arg| arg.split_once('=').ok_or(()))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let num = |v: &str| v.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or(());
+
+		let mut sample_rate = DEFAULT_GENERATOR_SAMPLE_RATE;
+		let mut amplitude = DEFAULT_AMPLITUDE;
+		let mut secs = None;
+		let mut layout = ChannelLayout::Mono;
+		let mut channel = None;
+		// The arguments of the waveform itself
+		let mut rest = Vec::new();
+
+		for (key, val) in args {
+			match key {
+				"rate" => sample_rate = val.parse().ok().filter(|r| 0 < *r).ok_or(())?,
+				"amp" => amplitude = num(val)? as f32,
+				"dur" => secs = Some(num(val).ok().filter(|s| 0.0 < *s).ok_or(())?),
+				"layout" => layout = val.parse()?,
+				"ch" => channel = Some(val.parse::<u8>().map_err(|_| ())?),
+				_ => rest.push((key, val))
+			}
+		}
+
+		if channel.is_some_and(|ch| layout.channels() <= ch) { return Err(()); }
+
+		let waveform = match name {
+			"sine" => {
+				let mut freq = None;
+				for (key, val) in rest {
+					match key {
+						"freq" => freq = Some(num(val)?),
+						_ => { return Err(()); }
+					}
+				}
+
+				Waveform::Sine { freq: freq.filter(|f| 0.0 < *f).ok_or(())? }
+			},
+			"sweep" => {
+				let (mut from, mut to) = (None, None);
+				for (key, val) in rest {
+					match key {
+						"from" => from = Some(num(val)?),
+						"to" => to = Some(num(val)?),
+						_ => { return Err(()); }
+					}
+				}
+
+				let (from, to) = from.zip(to).filter(|(from, to)| 0.0 < *from && 0.0 < *to).ok_or(())?;
+				secs = secs.or(Some(DEFAULT_SWEEP_SECS));
+
+				Waveform::Sweep { from, to }
+			},
+			"noise" => {
+				let (mut color, mut seed) = (NoiseColor::White, 0);
+				for (key, val) in rest {
+					match key {
+						"color" => color = val.parse()?,
+						"seed" => seed = val.parse().map_err(|_| ())?,
+						_ => { return Err(()); }
+					}
+				}
+
+				Waveform::Noise { color, seed }
+			},
+			"silence" => {
+				if !rest.is_empty() || secs.is_none() { return Err(()); }
+
+				Waveform::Silence
+			},
+			"click" => {
+				let (mut bpm, mut beats) = (120.0, 4);
+				for (key, val) in rest {
+					match key {
+						"bpm" => bpm = num(val)?,
+						"beats" => beats = val.parse().map_err(|_| ())?,
+						_ => { return Err(()); }
+					}
+				}
+
+				if bpm <= 0.0 || beats == 0 { return Err(()); }
+
+				Waveform::Click { bpm, beats }
+			},
+			_ => { return Err(()); }
+		};
+
+		let mut res = GeneratorSrc::new(waveform, sample_rate);
+		res.set_amplitude(amplitude);
+		res.set_duration(secs.map(|s| (s * sample_rate as f64).round() as TFrameIdx));
+		res.set_channel_layout(layout);
+		res.set_channel(channel);
+
+		Ok(res)
+	}
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use symphonia::core::meta::{MetadataRevision, StandardTagKey, StandardVisualKey};
+
+use super::TFrameIdx;
+
+/// An image embedded in a file, e.g. the cover of its album.
+#[derive(Debug)]
+pub struct CoverArt {
+	/// The MIME type of the image (e.g. `image/jpeg`).
+	pub media_type: String,
+	pub data: Box<[u8]>
+}
+
+/// What the tags of a track say about it.
+#[derive(Clone, Debug, Default)]
+pub struct TrackMetadata {
+	pub title: Option<String>,
+	pub artist: Option<String>,
+	pub album: Option<String>,
+	pub track_number: Option<u32>,
+	pub cover: Option<Arc<CoverArt>>,
+	/// Every tag of the track as it was found, including the ones above.
+	pub tags: Vec<(String, String)>
+}
+
+impl TrackMetadata {
+	/// Takes in a revision of the metadata of a file, filling in what's not known yet.
+	/// Files may have more than one revision (e.g. an ID3v2 tag before the container and the tags of the container).
+	pub fn add_revision(&mut self, rev: &MetadataRevision) {
+		for tag in rev.tags() {
+			// Some containers (e.g. RIFF) keep the terminators of the strings
+			let value = tag.value.to_string().trim_end_matches('\0').to_owned();
+
+			let field = match tag.std_key {
+				Some(StandardTagKey::TrackTitle) => Some(&mut self.title),
+				Some(StandardTagKey::Artist) => Some(&mut self.artist),
+				Some(StandardTagKey::Album) => Some(&mut self.album),
+				_ => None
+			};
+			if let Some(field) = field {
+				field.get_or_insert_with(|| value.clone());
+			}
+
+			// Track numbers are often written along with the number of tracks, like `3/12`
+			if tag.std_key == Some(StandardTagKey::TrackNumber) && self.track_number.is_none() {
+				self.track_number = value.split('/').next().and_then(|n| n.trim().parse().ok());
+			}
+
+			self.tags.push((tag.key.clone(), value));
+		}
+
+		if self.cover.is_none() {
+			let visuals = rev.visuals();
+			let cover = visuals.iter().find(|v| v.usage == Some(StandardVisualKey::FrontCover)).or(visuals.first());
+
+			self.cover = cover.map(|v| Arc::new(CoverArt { media_type: v.media_type.clone(), data: v.data.clone() }));
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tags.is_empty() && self.cover.is_none()
+	}
+}
+
+/// What a source of a composition is playing, for showing it to the listeners.
+pub struct NowPlaying<'a> {
+	pub metadata: Option<&'a TrackMetadata>,
+	/// The index of the item playing when the source is a queue.
+	pub queue_idx: Option<usize>,
+	/// How far the track has played in frames.
+	pub elapsed: TFrameIdx,
+	/// The length of the track in frames if it's known and it ends.
+	pub length: Option<TFrameIdx>,
+	/// The sample-rate of `elapsed` and `length`.
+	pub sample_rate: TFrameIdx
+}
+
+impl NowPlaying<'_> {
+	pub fn elapsed_secs(&self) -> f64 {
+		self.elapsed as f64 / self.sample_rate as f64
+	}
+
+	pub fn length_secs(&self) -> Option<f64> {
+		self.length.map(|len| len as f64 / self.sample_rate as f64)
+	}
+
+	pub fn remaining_secs(&self) -> Option<f64> {
+		self.length.map(|len| len.saturating_sub(self.elapsed) as f64 / self.sample_rate as f64)
+	}
+}
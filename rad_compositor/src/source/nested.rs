@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use crate::{channel_layout::ChannelLayout, composition::TWrappedCompositionState, compositor::BufferCursor};
+
+use super::{BaseSource, TFrameIdx, TSample};
+
+/// A source playing the output of another composition (a sub-mix), made through `CompositionRegistry::nest`.
+///
+/// The nested composition keeps its own timeline, so the source is live: pausing or seeking the nested composition is heard
+/// through it the same way as through an endpoint, while seeking the composition the source is in doesn't seek the nested one.
+/// While the nested composition is paused the source is silent.
+pub struct NestedSrc {
+	cmp: TWrappedCompositionState,
+	/// Reads the output of the compositor of the nested composition at the sample-rate of the source.
+	cursor: Box<dyn BufferCursor>,
+	sample_rate: TFrameIdx,
+	channel_layout: ChannelLayout,
+	/// The frames read from the nested composition, kept around for when the same frames are read more than once
+	/// (e.g. by the compositors of other sample-rates or by resampling).
+	history: VecDeque<TSample>,
+	/// The index of the first frame in `history`.
+	history_start: TFrameIdx,
+	/// The frames played as silence while the nested compositor was behind, which are dropped from what it computes next.
+	owed: TFrameIdx
+}
+
+impl NestedSrc {
+	pub(crate) fn new(cmp: TWrappedCompositionState, cursor: Box<dyn BufferCursor>, sample_rate: TFrameIdx) -> Self {
+		let channel_layout = cmp.read().unwrap().get_channel_layout();
+
+		NestedSrc {
+			cmp,
+			cursor,
+			sample_rate,
+			channel_layout,
+			history: VecDeque::new(),
+			history_start: 0,
+			owed: 0
+		}
+	}
+
+	/// The composition whose output is played.
+	pub fn composition(&self) -> &TWrappedCompositionState {
+		&self.cmp
+	}
+
+	fn history_end(&self) -> TFrameIdx {
+		self.history_start + (self.history.len() / self.channel_layout.channels() as usize) as TFrameIdx
+	}
+
+	/// Reads from the nested composition until the frame before `frame_idx` is in the history.
+	///
+	/// This runs on the compositor of the outer composition while it holds the lock of it, so the buffers that the nested compositor
+	/// hasn't computed yet (e.g. as it's behind or paused) are never waited for and are silent instead.
+	/// The silence is made up for by dropping as many frames once they're computed, so that the source doesn't lag behind for good.
+	fn fill_history_until(&mut self, frame_idx: TFrameIdx) {
+		let channels = self.channel_layout.channels() as usize;
+
+		// What the nested compositor hasn't got to yet isn't owed anymore, as the source is live again from its latest buffer on
+		while 0 < self.owed {
+			let Some(buf) = self.cursor.try_next_buf() else {
+				self.owed = 0;
+				break;
+			};
+
+			let dropped = (self.owed as usize).min(buf.len() / channels);
+			self.history.extend(buf[dropped * channels..].iter());
+			self.owed -= dropped as TFrameIdx;
+		}
+
+		while self.history_end() < frame_idx {
+			match self.cursor.try_next_buf() {
+				Some(buf) => self.history.extend(buf.iter()),
+				None => {
+					let missing = frame_idx - self.history_end();
+					self.history.extend(std::iter::repeat_n(0.0, missing as usize * channels));
+					self.owed += missing;
+				}
+			}
+		}
+	}
+}
+
+impl BaseSource for NestedSrc {
+	fn sample_rate(&self) -> TFrameIdx {
+		self.sample_rate
+	}
+
+	fn duration(&self) -> Option<TFrameIdx> {
+		Some(0)
+	}
+
+	fn current_duration_frames(&self) -> TFrameIdx {
+		0
+	}
+
+	fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<TSample>> {
+		let mut frame = vec![0.0; self.channel_layout.channels() as usize];
+		self.read_block(frame_idx, &mut frame);
+
+		Some(frame)
+	}
+
+	fn read_block(&mut self, frame_idx: TFrameIdx, out: &mut [TSample]) -> usize {
+		let channels = self.channel_layout.channels() as usize;
+		let frames = out.len() / channels;
+
+		// Jumps (e.g. seeks of the composition the source is in) continue from what the nested composition is playing now.
+		if frame_idx < self.history_start || self.history_end() < frame_idx {
+			self.history.clear();
+			self.history_start = frame_idx;
+		}
+
+		self.fill_history_until(frame_idx + frames as TFrameIdx);
+
+		let start = (frame_idx - self.history_start) as usize * channels;
+		for (o, v) in out[..frames * channels].iter_mut().zip(self.history.range(start..)) {
+			*o = *v;
+		}
+
+		// Keeping a second of the history for the readers that are behind
+		let keep_from = (frame_idx + frames as TFrameIdx).saturating_sub(self.sample_rate).max(self.history_start);
+		self.history.drain(..(keep_from - self.history_start) as usize * channels);
+		self.history_start = keep_from;
+
+		frames
+	}
+
+	fn channels(&self) -> u8 {
+		self.channel_layout.channels()
+	}
+
+	fn channel_layout(&self) -> ChannelLayout {
+		self.channel_layout
+	}
+
+	fn on_idle(&mut self) {
+		// Dropping what has been computed in the meantime, so that it's neither kept in memory nor played late.
+		self.cursor.skip_to_head();
+		self.history.clear();
+		self.owed = 0;
+	}
+}
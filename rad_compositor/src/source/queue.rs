@@ -1,84 +1,735 @@
-use std::collections::LinkedList;
+use std::{fmt::Display, str::FromStr, sync::Arc};
 
-use crate::compositor::approximate_frame_linear;
+use rand::seq::SliceRandom;
 
-use super::{BaseSource, Source, TFrameIdx, TSample};
+use crate::{analysis::waveform::WaveformOverview, channel_layout::{ChannelLayout, MixMatrix}, composition::convert_sample_rates, loudness::{normalization_gain, TLufs}, resampler::{ResampleQuality, Resampler}};
 
+use super::{metadata::TrackMetadata, BaseSource, Source, TFrameIdx, TSample};
+
+/// The normalization gain of a source of a queue, which is 1 until its loudness is known.
+fn normalization_gain_of(target: Option<TLufs>, src: &Source) -> f32 {
+	target.zip(src.loudness()).map_or(1.0, |(target, lufs)| normalization_gain(lufs, target))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RepeatMode {
+	/// The queue ends after its last item.
+	#[default]
+	Off,
+	/// The current item plays over and over again.
+	One,
+	/// The queue starts over from its first item after its last one.
+	All
+}
+
+impl RepeatMode {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Off => "off",
+			Self::One => "one",
+			Self::All => "all"
+		}
+	}
+}
+
+impl Display for RepeatMode {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.name())
+	}
+}
+
+impl FromStr for RepeatMode {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"off" => Ok(Self::Off),
+			"one" => Ok(Self::One),
+			"all" => Ok(Self::All),
+			_ => Err(())
+		}
+	}
+}
+
+/// How the volumes of two items change while one fades into the other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FadeCurve {
+	/// The volumes change in straight lines, which dips in the middle when the items aren't alike.
+	Linear,
+	/// The volumes follow a quarter of a sine and cosine, which keeps the combined power even when the items aren't alike.
+	#[default]
+	EqualPower
+}
+
+impl FadeCurve {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Linear => "linear",
+			Self::EqualPower => "equal-power"
+		}
+	}
+
+	/// The gains of the incoming and the outgoing item at `t` (0 to 1) through the fade.
+	fn gains(self, t: f32) -> (f32, f32) {
+		match self {
+			Self::Linear => (t, 1.0 - t),
+			Self::EqualPower => {
+				let angle = t * std::f32::consts::FRAC_PI_2;
+				(angle.sin(), angle.cos())
+			}
+		}
+	}
+}
+
+const DEFAULT_CROSSFADE_SECS: f32 = 3.0;
+/// How long before the next item starts it's prepared for being read (see `BaseSource::prepare`), so that it follows without a gap.
+const PREPARE_AHEAD_MS: TFrameIdx = 1000;
+
+/// Overlaps the end of each item of a queue with the start of the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crossfade {
+	pub curve: FadeCurve,
+	/// How long the items overlap in seconds, shortened for items shorter than twice of it.
+	pub secs: f32
+}
+
+impl Crossfade {
+	fn frames(&self, sample_rate: TFrameIdx) -> TFrameIdx {
+		(self.secs * sample_rate as f32).round() as TFrameIdx
+	}
+}
+
+impl Display for Crossfade {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:dur={}", self.curve.name(), self.secs)
+	}
+}
+
+/// Written as `linear[:dur={secs}]` or `equal-power[:dur={secs}]`.
+impl FromStr for Crossfade {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, args) = s.split_once(':').unwrap_or((s, ""));
+
+		let curve = match name {
+			"linear" => FadeCurve::Linear,
+			"equal-power" => FadeCurve::EqualPower,
+			_ => { return Err(()); }
+		};
+
+		let mut secs = DEFAULT_CROSSFADE_SECS;
+		for arg in args.split(',').filter(|arg| !arg.is_empty()) {
+			match arg.split_once('=').ok_or(())? {
+				("dur", val) => secs = val.parse::<f32>().ok().filter(|v| v.is_finite() && 0.0 < *v).ok_or(())?,
+				_ => { return Err(()); }
+			}
+		}
+
+		Ok(Crossfade { curve, secs })
+	}
+}
+
+/// A resampler along with the item (by its index and start) it's being used for.
+struct ItemResampler {
+	resampler: Resampler,
+	item: Option<(usize, TFrameIdx)>
+}
+
+impl ItemResampler {
+	fn new(quality: ResampleQuality) -> Self {
+		ItemResampler { resampler: Resampler::new(quality), item: None }
+	}
+}
+
+/// Where a frame of the queue falls.
+enum Located {
+	/// In the item at the index, which starts at the frame.
+	Item(usize, TFrameIdx),
+	/// Before the first item, which starts at the frame.
+	BeforeStart(TFrameIdx),
+	AfterEnd
+}
+
+/// A playlist playing its sources one after another.
+///
+/// The timeline of the queue is made of its items laid out one after another in their order around the current item,
+/// so reading frames from before or after the current item (e.g. on seeks) moves to the items there.
+/// Skipping, jumping and removing the current item start the new current item from the frame that was last read, which
+/// as the compositors compute ahead is heard a bit later. Queues that have ended aren't read by the compositors anymore,
+/// so they start the new current item from the frame they're read from next instead.
+///
+/// With a crossfade each item starts before the one before it ends, except for items started by skipping and the like, which cut in.
 pub struct QueueSrc {
-    sources: LinkedList<Source>,
-    sample_rate: TFrameIdx
+	sources: Vec<Source>,
+	sample_rate: TFrameIdx,
+	channel_layout: ChannelLayout,
+	repeat: RepeatMode,
+	/// Index of the current item, meaningless while the queue is empty.
+	current: usize,
+	/// The frame the current item starts at.
+	anchor: TFrameIdx,
+	/// The frame after the last one read, which is where the queue is playing at.
+	read_end: TFrameIdx,
+	/// Whether the current item starts at the frame the queue is read from next rather than at `anchor`,
+	/// as the compositors stop reading queues that have ended and `read_end` falls behind them.
+	start_on_read: bool,
+	crossfade: Option<Crossfade>,
+	/// The frame an item was last started at by skipping and the like, where it doesn't fade in.
+	cut_at: Option<TFrameIdx>,
+	/// The matrix last used for mixing a source into the layout of the queue, along with the layout of that source.
+	mix_matrix: Option<(ChannelLayout, MixMatrix)>,
+	/// Two resamplers, as while crossfading two items are read at once.
+	resamplers: Box<[ItemResampler; 2]>,
+	/// Scratch space for the frames of sources whose layout is different from the queue.
+	src_buf: Vec<TSample>,
+	/// Scratch space for the frames of the item fading out.
+	fade_buf: Vec<TSample>,
+	/// The loudness each source of the queue gets normalized to, if any.
+	loudness_target: Option<TLufs>
 }
 
 impl QueueSrc {
-    pub fn new(sample_rate: TFrameIdx) -> Self {
-        QueueSrc {
-            sources: LinkedList::new(),
-            sample_rate
-        }
-    }
+	pub fn new(sample_rate: TFrameIdx) -> Self {
+		QueueSrc {
+			sources: Vec::new(),
+			sample_rate,
+			channel_layout: ChannelLayout::Stereo,
+			repeat: RepeatMode::Off,
+			current: 0,
+			anchor: 0,
+			read_end: 0,
+			start_on_read: false,
+			crossfade: None,
+			cut_at: None,
+			mix_matrix: None,
+			resamplers: Box::new([ItemResampler::new(ResampleQuality::default()), ItemResampler::new(ResampleQuality::default())]),
+			src_buf: Vec::new(),
+			fade_buf: Vec::new(),
+			loudness_target: None
+		}
+	}
+
+	/// Sets the layout every source of the queue gets mixed into.
+	pub fn set_channel_layout(&mut self, layout: ChannelLayout) {
+		self.channel_layout = layout;
+		self.mix_matrix = None;
+	}
+
+	pub fn set_resample_quality(&mut self, quality: ResampleQuality) {
+		*self.resamplers = [ItemResampler::new(quality), ItemResampler::new(quality)];
+	}
+
+	/// Normalizes each source of the queue on its own, as they're usually unrelated tracks of different loudness.
+	///
+	/// Note: The queue itself has no loudness (`BaseSource::loudness`) so that it doesn't get normalized again as a whole.
+	pub fn set_loudness_target(&mut self, target: Option<TLufs>) {
+		self.loudness_target = target;
+	}
+
+	pub fn get_loudness_target(&self) -> Option<TLufs> {
+		self.loudness_target
+	}
+
+	pub fn set_repeat(&mut self, repeat: RepeatMode) {
+		self.repeat = repeat;
+	}
+
+	pub fn repeat(&self) -> RepeatMode {
+		self.repeat
+	}
+
+	/// Sets how the items fade into one another, `None` for playing them back to back.
+	pub fn set_crossfade(&mut self, crossfade: Option<Crossfade>) {
+		self.crossfade = crossfade;
+	}
+
+	pub fn crossfade(&self) -> Option<Crossfade> {
+		self.crossfade
+	}
+
+	pub fn sources(&self) -> &[Source] {
+		&self.sources
+	}
+
+	pub fn sources_mut(&mut self) -> &mut [Source] {
+		&mut self.sources
+	}
+
+	pub fn len(&self) -> usize {
+		self.sources.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.sources.is_empty()
+	}
+
+	/// The index of the item that is playing, `None` if the queue is empty or has ended.
+	pub fn current(&self) -> Option<usize> {
+		match self.locate(self.read_end) {
+			Located::Item(idx, _) => Some(idx),
+			Located::BeforeStart(_) => Some(0),
+			Located::AfterEnd => None
+		}
+	}
+
+	/// How far (in frames) the current item has played.
+	pub fn current_position(&self) -> TFrameIdx {
+		self.read_end.saturating_sub(self.anchor)
+	}
+
+	/// The length of the current item in the frames of the queue, if it's known and it ends.
+	pub fn current_len(&self) -> Option<TFrameIdx> {
+		self.current().and_then(|idx| self.item_len(idx))
+	}
+
+	pub fn push(&mut self, src: Source) {
+		let _ = self.insert(self.sources.len(), src);
+	}
+
+	/// Removes the last item.
+	pub fn pop(&mut self) -> Option<Source> {
+		self.remove(self.sources.len().checked_sub(1)?)
+	}
+
+	/// Inserts an item at `idx`, dropping it if the index is out of bounds.
+	///
+	/// If the queue has ended (or was empty) the item starts playing from the frame the queue is read from next.
+	pub fn insert(&mut self, idx: usize, src: Source) -> Option<()> {
+		if self.sources.len() < idx { return None; }
+
+		let has_ended = self.has_ended();
+		let starts_on_read = self.starts_on_read();
+
+		self.sources.insert(idx, src);
+
+		if has_ended {
+			self.start_now(idx);
+			self.start_on_read = starts_on_read;
+		} else if idx <= self.current && 1 < self.sources.len() {
+			self.current += 1;
+		}
+
+		Some(())
+	}
+
+	/// Removes the item at `idx`, if it was the current item the item after it starts playing right away.
+	pub fn remove(&mut self, idx: usize) -> Option<Source> {
+		if self.sources.len() <= idx { return None; }
+
+		let starts_on_read = self.starts_on_read();
+		let src = self.sources.remove(idx);
+
+		if idx < self.current {
+			self.current -= 1;
+		} else if idx == self.current {
+			if self.current == self.sources.len() {
+				// Nothing comes after the removed item unless the queue repeats
+				self.current = 0;
+				if self.repeat != RepeatMode::All { self.end_now(); }
+				else { self.start_now(0); }
+			} else {
+				self.start_now(self.current);
+			}
+
+			// Whether the queue had ended has to be known from before the removal
+			if self.anchor != TFrameIdx::MAX { self.start_on_read = starts_on_read; }
+		}
+
+		Some(src)
+	}
+
+	/// Moves the item at `from` to `to`, the current item keeps playing.
+	pub fn move_item(&mut self, from: usize, to: usize) -> Option<()> {
+		if self.sources.len() <= from || self.sources.len() <= to { return None; }
+
+		let src = self.sources.remove(from);
+		self.sources.insert(to, src);
+
+		if self.current == from {
+			self.current = to;
+		} else if from < self.current && self.current <= to {
+			self.current -= 1;
+		} else if to <= self.current && self.current < from {
+			self.current += 1;
+		}
+
+		Some(())
+	}
+
+	/// Shuffles the items into a random order, moving the current item to the front so that it keeps playing and all the others follow it.
+	pub fn shuffle(&mut self) {
+		if self.sources.is_empty() { return; }
+
+		self.sources.swap(0, self.current);
+		self.sources[1..].shuffle(&mut rand::rng());
+		self.current = 0;
+	}
+
+	/// Starts the item at `idx` right away.
+	pub fn jump_to(&mut self, idx: usize) -> Option<()> {
+		if self.sources.len() <= idx { return None; }
 
-    pub fn push(&mut self, src: Source) {
-        self.sources.push_back(src);
-    }
+		self.start_now(idx);
 
-    pub fn pop(&mut self) -> Option<Source> {
-        self.sources.pop_back()
-    }
+		Some(())
+	}
 
-    pub fn sources(&self) -> &LinkedList<Source> {
-        &self.sources
-    }
+	/// Starts the next item right away (even when repeating one), ending the queue if there's none.
+	pub fn skip_next(&mut self) {
+		if self.sources.is_empty() { return; }
 
-    pub fn sources_mut(&mut self) -> &mut LinkedList<Source> {
-        &mut self.sources
-    }
+		match self.next_idx(self.current, self.skip_repeat()) {
+			Some(idx) => self.start_now(idx),
+			None => self.end_now()
+		}
+	}
+
+	/// Starts the previous item right away, or restarts the first item if there's none.
+	pub fn skip_previous(&mut self) {
+		if self.sources.is_empty() { return; }
+
+		let idx = self.prev_idx(self.current, self.skip_repeat()).unwrap_or(0);
+		self.start_now(idx);
+	}
+
+	/// Skipping moves on from the current item even while repeating it, so repeating one item is skipped like repeating all of them.
+	fn skip_repeat(&self) -> RepeatMode {
+		match self.repeat {
+			RepeatMode::Off => RepeatMode::Off,
+			_ => RepeatMode::All
+		}
+	}
+
+	/// Starts the item at `idx` from the frame the queue is playing at, which for a queue that has ended is the frame it's read from next.
+	fn start_now(&mut self, idx: usize) {
+		self.start_on_read = self.starts_on_read();
+		self.current = idx;
+		self.anchor = self.read_end;
+		self.cut_at = Some(self.read_end);
+	}
+
+	fn end_now(&mut self) {
+		self.anchor = TFrameIdx::MAX;
+		self.start_on_read = false;
+	}
+
+	fn has_ended(&self) -> bool {
+		matches!(self.locate(self.read_end), Located::AfterEnd)
+	}
+
+	/// Whether an item started now starts at the frame the queue is read from next, which is the case once the queue has been read and has ended.
+	/// Queues that haven't been read yet lay their items out from their first frame.
+	fn starts_on_read(&self) -> bool {
+		self.read_end != 0 && self.has_ended()
+	}
+
+	/// The length of an item in the frames of the queue, `None` if it doesn't end or isn't known.
+	fn item_len(&self, idx: usize) -> Option<TFrameIdx> {
+		let src = &self.sources[idx];
+		let len = src.duration().filter(|d| *d != 0).unwrap_or_else(|| src.current_duration_frames());
+
+		(len != 0).then(|| convert_sample_rates(src.sample_rate(), len, self.sample_rate).max(1))
+	}
+
+	fn next_idx(&self, idx: usize, repeat: RepeatMode) -> Option<usize> {
+		match repeat {
+			RepeatMode::One => Some(idx),
+			RepeatMode::All => Some((idx + 1) % self.sources.len()),
+			RepeatMode::Off => (idx + 1 < self.sources.len()).then_some(idx + 1)
+		}
+	}
+
+	fn prev_idx(&self, idx: usize, repeat: RepeatMode) -> Option<usize> {
+		match repeat {
+			RepeatMode::One => Some(idx),
+			RepeatMode::All => Some(idx.checked_sub(1).unwrap_or(self.sources.len() - 1)),
+			RepeatMode::Off => idx.checked_sub(1)
+		}
+	}
+
+	/// How many frames an item of `len` frames overlaps with the item at `next`.
+	fn overlap(&self, len: TFrameIdx, next: usize) -> TFrameIdx {
+		let frames = match self.crossfade {
+			Some(crossfade) => crossfade.frames(self.sample_rate),
+			None => { return 0; }
+		};
+
+		// Items of unknown length can't be overlapped with, as where they end isn't known
+		self.item_len(next).map_or(0, |next_len| frames.min(len / 2).min(next_len / 2))
+	}
+
+	/// The start of the item after the item at `idx` starting at `start`, if it has one.
+	/// The item before it plays up to this frame on its own and fades out after it.
+	fn next_start(&self, idx: usize, start: TFrameIdx) -> Option<(usize, TFrameIdx)> {
+		let len = self.item_len(idx)?;
+		let next = self.next_idx(idx, self.repeat)?;
+
+		let end = start + len;
+		let overlap = if self.cut_at == Some(end) { 0 } else { self.overlap(len, next) };
+
+		Some((next, end - overlap))
+	}
+
+	/// The item before the item at `idx` starting at `start` along with where it starts and how long it fades out over the start.
+	fn prev_item(&self, idx: usize, start: TFrameIdx) -> Option<(usize, TFrameIdx, TFrameIdx)> {
+		let prev = self.prev_idx(idx, self.repeat)?;
+		let len = self.item_len(prev)?;
+
+		let overlap = if self.cut_at == Some(start) { 0 } else { self.overlap(len, idx) };
+
+		Some((prev, (start + overlap).checked_sub(len)?, overlap))
+	}
+
+	/// Finds the item the frame falls in by walking the items from the current one.
+	/// While crossfading the frame falls in the item fading in.
+	fn locate(&self, frame_idx: TFrameIdx) -> Located {
+		// An anchor of `TFrameIdx::MAX` means the queue was ended by skipping or removing
+		if self.sources.is_empty() || self.anchor == TFrameIdx::MAX { return Located::AfterEnd; }
+
+		let (mut idx, mut start) = (self.current, self.anchor);
+
+		if start <= frame_idx {
+			loop {
+				match (self.next_start(idx, start), self.item_len(idx)) {
+					(Some((next, next_start)), _) if next_start <= frame_idx => { (idx, start) = (next, next_start); },
+					(None, Some(len)) if start + len <= frame_idx => { return Located::AfterEnd; },
+					_ => { return Located::Item(idx, start); }
+				}
+			}
+		}
+
+		loop {
+			match self.prev_idx(idx, self.repeat) {
+				Some(prev) => match self.prev_item(idx, start) {
+					Some((_, prev_start, _)) => {
+						(idx, start) = (prev, prev_start);
+						if start <= frame_idx { return Located::Item(idx, start); }
+					},
+					// Items that don't end (or start before the queue) take up all the frames before the ones after them
+					None => { return Located::Item(prev, frame_idx.min(start)); }
+				},
+				None => { return Located::BeforeStart(start); }
+			}
+		}
+	}
+
+	/// Prepares the item after the one the frame falls in once it's about to start.
+	fn prepare_next(&mut self, frame_idx: TFrameIdx) {
+		let Located::Item(idx, start) = self.locate(frame_idx) else { return; };
+		let Some((next, next_start)) = self.next_start(idx, start) else { return; };
+
+		// An item repeating itself is still being read where it's at
+		if next != idx && next_start <= frame_idx + PREPARE_AHEAD_MS * self.sample_rate / 1000 {
+			self.sources[next].prepare(0);
+		}
+	}
+
+	/// Picks the resampler for an item, keeping the one used for `other` (the other item of a crossfade) as it is.
+	fn resampler_for(&mut self, item: (usize, TFrameIdx), other: Option<(usize, TFrameIdx)>) -> usize {
+		if let Some(i) = self.resamplers.iter().position(|r| r.item == Some(item)) { return i; }
+
+		let i = if self.resamplers[0].item.is_some() && self.resamplers[0].item == other { 1 } else { 0 };
+		self.resamplers[i].resampler.reset();
+		self.resamplers[i].item = Some(item);
+
+		i
+	}
+
+	/// Reads the frames of the item at `idx` starting at `frame_idx` (relative to the item) into `out` in the layout of the queue.
+	/// `other` is the other item of a crossfade if the item is being crossfaded.
+	fn read_item(&mut self, idx: usize, start: TFrameIdx, frame_idx: TFrameIdx, other: Option<(usize, TFrameIdx)>, out: &mut [TSample]) -> usize {
+		let resampler = if self.sources[idx].sample_rate() != self.sample_rate { self.resampler_for((idx, start), other) } else { 0 };
+		let src = &mut self.sources[idx];
+		let gain = normalization_gain_of(self.loudness_target, src);
+		let src_layout = src.channel_layout();
+		let src_channels = src_layout.channels() as usize;
+		let frames = out.len() / self.channel_layout.channels() as usize;
+
+		let read_into = |src: &mut Source, resampler: &mut Resampler, sample_rate: TFrameIdx, buf: &mut [TSample]| {
+			if src.sample_rate() == sample_rate {
+				src.read_block(frame_idx, buf)
+			} else {
+				resampler.resample_block(src, sample_rate, frame_idx, 0, buf)
+			}
+		};
+
+		let read = if src_layout == self.channel_layout {
+			read_into(src, &mut self.resamplers[resampler].resampler, self.sample_rate, out)
+		} else {
+			if self.src_buf.len() < frames * src_channels {
+				self.src_buf.resize(frames * src_channels, 0.0);
+			}
+
+			let read = read_into(src, &mut self.resamplers[resampler].resampler, self.sample_rate, &mut self.src_buf[..frames * src_channels]);
+
+			if self.mix_matrix.as_ref().is_none_or(|(layout, _)| *layout != src_layout) {
+				self.mix_matrix = Some((src_layout, MixMatrix::between(src_layout, self.channel_layout)));
+			}
+
+			let out = &mut out[..read * self.channel_layout.channels() as usize];
+			out.fill(0.0);
+			self.mix_matrix.as_ref().unwrap().1.apply_block_add(&self.src_buf[..read * src_channels], out, 1.0);
+
+			read
+		};
+
+		if gain != 1.0 {
+			for v in out[..read * self.channel_layout.channels() as usize].iter_mut() { *v *= gain; }
+		}
+
+		read
+	}
+
+	/// The crossfade the frame of the item at `idx` falls in, as the item fading out, where it starts, the length of the fade and its curve.
+	fn fade_at(&self, idx: usize, start: TFrameIdx, frame_idx: TFrameIdx) -> Option<(usize, TFrameIdx, TFrameIdx, FadeCurve)> {
+		let curve = self.crossfade?.curve;
+		let (prev, prev_start, overlap) = self.prev_item(idx, start)?;
+
+		(frame_idx < start + overlap).then_some((prev, prev_start, overlap, curve))
+	}
+
+	/// Mixes the end of the item fading out into the frames of the item at `idx` starting at `frame_idx`.
+	fn fade_in(&mut self, idx: usize, start: TFrameIdx, frame_idx: TFrameIdx, fade: (usize, TFrameIdx, TFrameIdx, FadeCurve), out: &mut [TSample]) {
+		let (prev, prev_start, overlap, curve) = fade;
+		let channels = self.channel_layout.channels() as usize;
+		let frames = ((start + overlap - frame_idx) as usize).min(out.len() / channels);
+
+		let mut fade_buf = std::mem::take(&mut self.fade_buf);
+		fade_buf.clear();
+		fade_buf.resize(frames * channels, 0.0);
+
+		// Items ending early leave the rest of the fade silent
+		self.read_item(prev, prev_start, frame_idx - prev_start, Some((idx, start)), &mut fade_buf);
+
+		for (i, (frame, prev_frame)) in out.chunks_exact_mut(channels).zip(fade_buf.chunks_exact(channels)).enumerate() {
+			let t = (frame_idx - start + i as TFrameIdx) as f32 / overlap as f32;
+			let (in_gain, out_gain) = curve.gains(t);
+
+			for (v, prev_v) in frame.iter_mut().zip(prev_frame.iter()) {
+				*v = *v * in_gain + *prev_v * out_gain;
+			}
+		}
+
+		self.fade_buf = fade_buf;
+	}
 }
 
 impl BaseSource for QueueSrc {
-    fn sample_rate(&self) -> TFrameIdx { self.sample_rate }
-
-    fn current_duration_frames(&self) -> TFrameIdx {
-        let mut dur = 0;
-        
-        for src in self.sources.iter() {
-            dur += src.current_duration_frames();
-        }
-
-        dur
-    }
-
-    fn duration(&self) -> Option<TFrameIdx> {
-        let mut dur = 0;
-
-        for src in self.sources.iter() {
-            match src.duration() {
-                Some(d) => dur += d,
-                None => { return None; }
-            }
-        }
-
-        Some(dur)
-    }
-
-    fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<TSample>> {
-        let mut offset: TFrameIdx = 0;
-        for src in self.sources.iter_mut() {
-            let frame = if src.sample_rate() == self.sample_rate {
-                src.get_by_frame_i(frame_idx - offset as TFrameIdx)
-            } else {
-                approximate_frame_linear(src, self.sample_rate, frame_idx - offset, 0)
-            };
-            
-            if frame.is_some() { return frame; }
-            
-            offset += src.duration().unwrap();
-        }
-
-        None
-    }
-
-    // TODO
-    fn channels(&self) -> u8 {
-        2
-    }
-}
\ No newline at end of file
+	fn sample_rate(&self) -> TFrameIdx { self.sample_rate }
+
+	fn current_duration_frames(&self) -> TFrameIdx {
+		self.duration().unwrap_or(0)
+	}
+
+	/// The frame the queue ends at, never ending (0) while repeating, while empty, or while an item waits to be started by the next read.
+	/// The queue has to keep being read in those cases, so that the items added to it start where the queue is playing at.
+	fn duration(&self) -> Option<TFrameIdx> {
+		if self.repeat != RepeatMode::Off || self.sources.is_empty() || self.start_on_read { return Some(0); }
+		if self.anchor == TFrameIdx::MAX { return Some(self.read_end); }
+
+		let (mut idx, mut start) = (self.current, self.anchor);
+		while let Some(next) = self.next_start(idx, start) {
+			(idx, start) = next;
+		}
+
+		Some(start + self.item_len(idx)?)
+	}
+
+	fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<TSample>> {
+		let mut frame = vec![0.0; self.channels() as usize];
+
+		(self.read_block(frame_idx, &mut frame) == 1).then_some(frame)
+	}
+
+	fn read_block(&mut self, frame_idx: TFrameIdx, out: &mut [TSample]) -> usize {
+		let channels = self.channels() as usize;
+		let frames = out.len() / channels;
+		let mut written = 0;
+		// Items that end before their duration says are skipped, but only once each so that a queue of such items can't loop forever
+		let mut ended_early = 0;
+
+		if self.start_on_read {
+			self.anchor = frame_idx;
+			self.cut_at = Some(frame_idx);
+			self.start_on_read = false;
+		}
+
+		while written < frames && ended_early <= self.sources.len() {
+			let idx = frame_idx + written as TFrameIdx;
+			let rest = &mut out[written * channels..frames * channels];
+
+			let read = match self.locate(idx) {
+				Located::Item(item_idx, start) => {
+					self.current = item_idx;
+					self.anchor = start;
+
+					let fade = self.fade_at(item_idx, start, idx);
+					let until_next = self.next_start(item_idx, start).map_or(frames - written, |(_, next)| (next - idx) as usize);
+					let rest_len = until_next.min(frames - written) * channels;
+					let read = self.read_item(item_idx, start, idx - start, fade.map(|(prev, prev_start, _, _)| (prev, prev_start)), &mut rest[..rest_len]);
+
+					if read == 0 {
+						ended_early += 1;
+						match self.next_idx(item_idx, self.repeat) {
+							Some(next) => { self.current = next; self.anchor = idx; self.cut_at = Some(idx); },
+							None => { self.anchor = TFrameIdx::MAX; }
+						}
+					}
+
+					if let Some(fade) = fade {
+						self.fade_in(item_idx, start, idx, fade, &mut rest[..read * channels]);
+					}
+
+					read
+				},
+				Located::BeforeStart(start) => {
+					let silent = ((start - idx) as usize).min(frames - written);
+					rest[..silent * channels].fill(0.0);
+
+					silent
+				},
+				Located::AfterEnd => break
+			};
+
+			written += read;
+		}
+
+		// Where the queue is playing at even when it has ended, so that the items added afterwards start there
+		self.read_end = frame_idx + frames as TFrameIdx;
+		self.prepare_next(self.read_end);
+
+		written
+	}
+
+	fn channels(&self) -> u8 {
+		self.channel_layout.channels()
+	}
+
+	fn channel_layout(&self) -> ChannelLayout {
+		self.channel_layout
+	}
+
+	/// The metadata of the current item.
+	fn metadata(&self) -> Option<&TrackMetadata> {
+		self.sources.get(self.current()?)?.metadata()
+	}
+
+	/// The waveform of the current item.
+	fn waveform_overview(&self) -> Option<Arc<WaveformOverview>> {
+		self.sources.get(self.current()?)?.waveform_overview()
+	}
+
+	fn prepare(&mut self, frame_idx: TFrameIdx) {
+		if let Located::Item(idx, start) = self.locate(frame_idx) {
+			let src = &mut self.sources[idx];
+			let src_frame_idx = convert_sample_rates(self.sample_rate, frame_idx - start, src.sample_rate());
+			src.prepare(src_frame_idx);
+		}
+	}
+
+	/// The errors of all the items, faulted items are skipped like the ones ending early rather than faulting the queue.
+	fn decode_errors(&self) -> u64 {
+		self.sources.iter().map(|s| s.decode_errors()).sum()
+	}
+}
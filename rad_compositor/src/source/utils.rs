@@ -1,2 +1,4 @@
+pub mod decode;
+pub mod hint;
 pub mod sample_buf;
 pub mod dyn_buf;
\ No newline at end of file
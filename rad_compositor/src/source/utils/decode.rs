@@ -0,0 +1,44 @@
+use symphonia::core::{codecs::DecoderOptions, errors::Error, formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint};
+
+use crate::{channel_layout::ChannelLayout, source::{TFrameIdx, TSample}};
+
+use super::sample_buf::SampleBuf;
+
+/// Decodes all of the default track of a stream, handing each decoded block of interleaved frames to `on_block`
+/// along with the sample-rate and the channel layout of the track. This is for the analyses that need the whole track (e.g. its loudness),
+/// so it takes as long as decoding all of it does.
+///
+/// Corrupt packets are skipped the same way as they would be while playing, `None` is returned if the stream can't be decoded at all.
+pub fn decode_all(mss: MediaSourceStream, hint: &Hint, mut on_block: impl FnMut(TFrameIdx, ChannelLayout, &[TSample])) -> Option<()> {
+	let mut format = symphonia::default::get_probe()
+		.format(hint, mss, &FormatOptions { enable_gapless: true, ..Default::default() }, &MetadataOptions::default()).ok()?
+		.format;
+
+	let track = format.default_track()?.clone();
+	let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default()).ok()?;
+
+	loop {
+		let packet = match format.next_packet() {
+			Ok(packet) => packet,
+			Err(Error::IoError(_)) => break,
+			Err(_) => { return None; }
+		};
+		if packet.track_id() != track.id { continue; }
+
+		let decoded = match decoder.decode(&packet) {
+			Ok(decoded) => decoded,
+			Err(Error::DecodeError(_)) => continue,
+			Err(_) => { return None; }
+		};
+
+		let spec = *decoded.spec();
+		let mut buf = SampleBuf::from_audio_buf_ref(0, &decoded);
+		if packet.dur < buf.frame_count() {
+			buf.trim(packet.trim_start() as TFrameIdx, packet.trim_end() as TFrameIdx);
+		}
+
+		on_block(spec.rate as TFrameIdx, ChannelLayout::from_symphonia(spec.channels), &buf.samples);
+	}
+
+	Some(())
+}
@@ -30,6 +30,7 @@ impl DataLock {
         let mut lock = self.lock.lock().unwrap();
 
         lock.eof = true;
+        self.cnd.notify_all();
     }
 }
 
@@ -55,10 +56,8 @@ impl io::Read for DynFmtBuf {
         let data_buf = &data.bufs[self.idx];
         let bytes_read = min(data_buf.len() - self.buf_idx, buf.len());
 
-        for i in 0..bytes_read {
-            buf[i] = data_buf[self.buf_idx];
-            self.buf_idx += 1;
-        }
+        buf[..bytes_read].copy_from_slice(&data_buf[self.buf_idx..self.buf_idx + bytes_read]);
+        self.buf_idx += bytes_read;
 
         if self.buf_idx == data_buf.len() {
             self.idx += 1;
@@ -81,7 +80,7 @@ impl io::Seek for DynFmtBuf {
                     return Err(io::ErrorKind::UnexpectedEof.into());
                 }
 
-                self.seek_from_end((offset * -1) as u64)
+                self.seek_from_end(-offset as u64)
             }
         }
     }
@@ -92,6 +91,12 @@ impl io::Seek for DynFmtBuf {
     }
 }
 
+impl Default for DynFmtBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DynFmtBuf {
     pub fn new() -> Self {
         DynFmtBuf {
@@ -102,6 +107,16 @@ impl DynFmtBuf {
         }
     }
 
+    /// Another reader of the data of a buffer, which reads it from the start independently of the other readers.
+    pub fn reading(data_lock: Arc<DataLock>) -> Self {
+        DynFmtBuf {
+            data_lock,
+            current_pos: 0,
+            buf_idx: 0,
+            idx: 0
+        }
+    }
+
     pub fn data_lock(&self) -> Arc<DataLock> {
         self.data_lock.clone()
     }
@@ -109,9 +124,8 @@ impl DynFmtBuf {
     pub fn seek_from_start(&mut self, start: u64) -> io::Result<u64> {
         let lock = self.data_lock.lock.lock().unwrap();
 
-        let mut set_idx = 0;
         let mut pos = 0;
-        for buf in &lock.bufs {
+        for (set_idx, buf) in lock.bufs.iter().enumerate() {
             if (0..buf.len() as u64).contains(&(start - pos)) {
                 self.idx = set_idx;
                 self.buf_idx = (start - pos) as usize;
@@ -120,7 +134,6 @@ impl DynFmtBuf {
                 return Ok(start);
             }
             
-            set_idx += 1;
             pos += buf.len() as u64;
         }
 
@@ -130,7 +143,7 @@ impl DynFmtBuf {
     pub fn seek_from_end(&mut self, offset: u64) -> io::Result<u64> {
         let lock = self.data_lock.lock.lock().unwrap();
 
-        if lock.size == 0 || (lock.size as u64) < offset {
+        if lock.size == 0 || lock.size < offset {
             return Err(io::ErrorKind::UnexpectedEof.into());
         }
 
@@ -142,7 +155,7 @@ impl DynFmtBuf {
                 self.buf_idx = (offset - (lock.size - pos)) as usize;
                 self.current_pos = lock.size - offset;
 
-                return Ok(self.current_pos as u64);
+                return Ok(self.current_pos);
             }
             
             set_idx -= 1;
@@ -158,7 +171,7 @@ impl MediaSource for DynFmtBuf {
     fn byte_len(&self) -> Option<u64> {
         let lock =  self.data_lock.lock.lock().unwrap();
         if lock.eof {
-            return Some(lock.size as u64);
+            return Some(lock.size);
         }
 
         None
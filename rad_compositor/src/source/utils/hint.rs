@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use symphonia::core::probe::Hint;
+
+/// The formats that can be told apart by their MIME type, as the extension symphonia knows them by.
+const MIME_TYPE_EXTENSIONS: &[(&str, &str)] = &[
+	("audio/mpeg", "mp3"),
+	("audio/mp3", "mp3"),
+	("audio/flac", "flac"),
+	("audio/x-flac", "flac"),
+	("audio/wav", "wav"),
+	("audio/wave", "wav"),
+	("audio/x-wav", "wav"),
+	("audio/vnd.wave", "wav"),
+	("audio/aiff", "aiff"),
+	("audio/x-aiff", "aiff"),
+	("audio/ogg", "ogg"),
+	("audio/vorbis", "ogg"),
+	("application/ogg", "ogg"),
+	("audio/mp4", "m4a"),
+	("audio/m4a", "m4a"),
+	("audio/x-m4a", "m4a"),
+	("audio/aac", "aac"),
+	("audio/aacp", "aac"),
+	("audio/x-aac", "aac")
+];
+
+/// A probe hint from the extension of the file at `path`.
+pub fn hint_of_path(path: &Path) -> Hint {
+	let mut hint = Hint::new();
+	if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+		hint.with_extension(ext);
+	}
+
+	hint
+}
+
+/// A probe hint from a MIME type (e.g. the `Content-Type` of an upload), leaving out its parameters such as `; codecs=...`.
+pub fn hint_of_mime_type(mime_type: &str) -> Hint {
+	let mime_type = mime_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+	let mut hint = Hint::new();
+
+	if let Some((_, ext)) = MIME_TYPE_EXTENSIONS.iter().find(|(mime, _)| *mime == mime_type) {
+		hint.with_extension(ext);
+	}
+	hint.mime_type(&mime_type);
+
+	hint
+}
+
+/// The extensions of the formats that can be played, which depend on the codec features the crate was built with.
+pub fn supported_extensions() -> Vec<&'static str> {
+	let mut extensions = Vec::new();
+
+	if cfg!(feature = "mp3") { extensions.extend(["mp1", "mp2", "mp3"]); }
+	if cfg!(feature = "flac") { extensions.push("flac"); }
+	if cfg!(feature = "wav") { extensions.push("wav"); }
+	if cfg!(feature = "aiff") { extensions.extend(["aiff", "aif", "aifc"]); }
+	if cfg!(feature = "vorbis") { extensions.extend(["ogg", "oga"]); }
+	if cfg!(feature = "aac") { extensions.push("aac"); }
+	if cfg!(feature = "aac") || cfg!(feature = "alac") { extensions.extend(["m4a", "mp4"]); }
+
+	extensions
+}
@@ -51,21 +51,24 @@ impl SampleBuf {
 		self.start_idx..self.end()
 	}
 
+	/// Drops frames from the start and the end, the start index is left as it is.
+	pub fn trim(&mut self, start_frames: TFrameIdx, end_frames: TFrameIdx) {
+		let channels = self.channels as usize;
+		let end = self.samples.len().saturating_sub(end_frames as usize * channels);
+
+		self.samples.truncate(end);
+		self.samples.drain(..(start_frames as usize * channels).min(end));
+	}
+
 	pub fn from_audio_buf_ref(start_i: TFrameIdx, audio_buf_ref: &AudioBufferRef) -> SampleBuf {
-		let f32_buf;
-		
-		match audio_buf_ref {
-            AudioBufferRef::U8(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::U16(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::U24(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::U32(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::S8(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::S16(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::S24(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::S32(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-            AudioBufferRef::F32(buf) => { return Self::from_audio_buf(start_i, &buf); },
-            AudioBufferRef::F64(buf) => { f32_buf = buf.make_equivalent::<f32>() },
-        };
+		let f32_buf = match audio_buf_ref {
+			AudioBufferRef::F32(buf) => { return Self::from_audio_buf(start_i, buf); },
+			other => {
+				let mut f32_buf = other.make_equivalent::<f32>();
+				other.convert(&mut f32_buf);
+				f32_buf
+			}
+		};
 
 		Self::from_audio_buf(start_i, &f32_buf)
 	}
@@ -77,11 +80,11 @@ impl SampleBuf {
 
 		let mut buf = Vec::with_capacity(samples);
 
-		let channel_bufs = (0..channels).into_iter().map(|ch_i| samp_buf.chan(ch_i)).collect::<Vec<_>>();
+		let channel_bufs = (0..channels).map(|ch_i| samp_buf.chan(ch_i)).collect::<Vec<_>>();
 
 		for frame_i in 0..frames {
-			for ch_i in 0..channels {
-				buf.push(channel_bufs[ch_i][frame_i]);
+			for ch_buf in channel_bufs.iter() {
+				buf.push(ch_buf[frame_i]);
 			}
 		}
 
@@ -116,9 +119,11 @@ pub fn queue_from_directory(path: &Path, sample_rate: TFrameIdx, depth: u8) -> O
 	for file_path in file_paths {
 		log::debug!("Reading '{}'", file_path.display());
 
-		let src = FormattedStreamSource::open_path(file_path)?;
-
-		queue.push(src.into());
+		// Files that aren't audio (e.g. cover art) are left out instead of failing the whole queue
+		match FormattedStreamSource::open_path(file_path.clone()) {
+			Ok(src) => queue.push(src.into()),
+			Err(err) => log::warn!("Skipping '{}': {}", file_path.display(), err)
+		}
 	}
 		
 	Some(queue)
@@ -0,0 +1,123 @@
+use std::{io::Cursor, thread, time::Duration};
+
+use rad_compositor::{analysis::{spectrum::{analyze, capture, is_valid_size}, waveform::{overview_of_path, overview_of_stream}}, compositor::ring::RingWriter, source::{generator::GeneratorSrc, BaseSource}};
+use symphonia::core::probe::Hint;
+
+const SAMPLE_RATE: u32 = 48000;
+
+/// A mono sine of full scale right on the frequency of the bin 100 of a spectrum of 4096 frames.
+fn sine_block(frames: usize) -> Vec<f32> {
+	let mut block = vec![0.0; frames];
+	format!("sine:freq={},amp=1,rate=48000", 100.0 * SAMPLE_RATE as f64 / 4096.0).parse::<GeneratorSrc>().unwrap().read_block(0, &mut block);
+
+	block
+}
+
+#[test]
+fn sines_show_up_in_their_bins() {
+	let spectrum = analyze(&sine_block(4096), 1, SAMPLE_RATE as u64);
+	assert_eq!(spectrum.magnitudes_db.len(), 2049);
+	assert!((spectrum.bin_hz() - 11.71875).abs() < 1e-6);
+
+	let loudest = (0..spectrum.magnitudes_db.len()).max_by(|a, b| spectrum.magnitudes_db[*a].total_cmp(&spectrum.magnitudes_db[*b])).unwrap();
+	assert_eq!(loudest, 100);
+	assert!(spectrum.magnitudes_db[100].abs() < 0.1);
+	assert!(spectrum.magnitudes_db[300] < -80.0);
+
+	for size in [0, 32, 1000, 65536] {
+		assert!(!is_valid_size(size), "{}", size);
+	}
+}
+
+#[test]
+fn spectrums_are_captured_from_the_next_buffers() {
+	let block = sine_block(4096);
+	let mut writer = RingWriter::new(16, 1024);
+	writer.push(&[0.0; 1024]);
+	let mut cursor = writer.ring().cursor();
+
+	assert!(capture(&mut cursor, 1, SAMPLE_RATE as u64, 4096, Duration::from_millis(10)).is_none());
+
+	let producer = thread::spawn(move || {
+		for chunk in block.chunks_exact(1024) {
+			thread::sleep(Duration::from_millis(5));
+			writer.push(chunk);
+		}
+	});
+
+	let spectrum = capture(&mut cursor, 1, SAMPLE_RATE as u64, 4096, Duration::from_secs(5)).unwrap();
+	producer.join().unwrap();
+
+	assert!(spectrum.magnitudes_db[100].abs() < 0.1);
+}
+
+/// A mono 16-bit WAV file of `samples`.
+fn wav(samples: &[i16]) -> Vec<u8> {
+	let mut body = b"WAVEfmt ".to_vec();
+	body.extend(16u32.to_le_bytes());
+	body.extend(1u16.to_le_bytes());
+	body.extend(1u16.to_le_bytes());
+	body.extend(SAMPLE_RATE.to_le_bytes());
+	body.extend((SAMPLE_RATE * 2).to_le_bytes());
+	body.extend(2u16.to_le_bytes());
+	body.extend(16u16.to_le_bytes());
+	body.extend(b"data");
+	body.extend((samples.len() as u32 * 2).to_le_bytes());
+	body.extend(samples.iter().flat_map(|s| s.to_le_bytes()));
+
+	let mut wav = b"RIFF".to_vec();
+	wav.extend((body.len() as u32).to_le_bytes());
+	wav.extend(body);
+
+	wav
+}
+
+#[test]
+fn overviews_keep_the_peaks() {
+	// Swinging up to 0.5 for the first half and down to -0.25 for the second, which are both 24 buckets of 512 frames
+	let frames = 2 * 24 * 512;
+	let samples = (0..frames).map(|i| match (i < frames / 2, i % 2) {
+		(true, 0) => 16384,
+		(false, 0) => -8192,
+		_ => 0
+	}).collect::<Vec<_>>();
+
+	let overview = overview_of_stream(Box::new(Cursor::new(wav(&samples))), &Hint::new()).unwrap();
+	assert_eq!(overview.frames, frames as u64);
+	assert!((overview.duration_secs() - frames as f64 / SAMPLE_RATE as f64).abs() < 1e-9);
+
+	assert_eq!(overview.peaks(2), [(0.0, 0.5), (-0.25, 0.0)]);
+	assert_eq!(overview.peaks(3)[1], (-0.25, 0.5));
+	// There are no more parts than there are buckets
+	assert_eq!(overview.peaks(1000).len(), 48);
+}
+
+#[test]
+fn overviews_of_one_sided_waveforms_dont_reach_zero() {
+	// Staying between 0.25 and 0.5 all along
+	let samples = (0..4 * 512).map(|i| if i % 2 == 0 { 8192 } else { 16384 }).collect::<Vec<_>>();
+
+	let overview = overview_of_stream(Box::new(Cursor::new(wav(&samples))), &Hint::new()).unwrap();
+	assert_eq!(overview.peaks(2), [(0.25, 0.5), (0.25, 0.5)]);
+}
+
+#[test]
+fn overviews_of_files_are_made_once_asked_for() {
+	let path = std::env::temp_dir().join(format!("rad-waveform-{}.wav", std::process::id()));
+	std::fs::write(&path, wav(&[16384; 4 * 512])).unwrap();
+
+	let handle = overview_of_path(&path);
+	// Nothing is decoded until the overview is asked for
+	thread::sleep(Duration::from_millis(100));
+	assert!(handle.get().is_none());
+
+	let mut overview = None;
+	for _ in 0..100 {
+		overview = handle.get();
+		if overview.is_some() { break; }
+		thread::sleep(Duration::from_millis(20));
+	}
+
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(overview.unwrap().peaks(1), [(0.5, 0.5)]);
+}
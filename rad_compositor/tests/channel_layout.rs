@@ -0,0 +1,68 @@
+use rad_compositor::channel_layout::{ChannelLayout, MixMatrix};
+
+const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[test]
+fn mono_plays_at_full_level_on_both_sides() {
+	let matrix = MixMatrix::between(ChannelLayout::Mono, ChannelLayout::Stereo);
+	assert_eq!(matrix.coeffs(), [1.0, 1.0]);
+
+	let matrix = MixMatrix::between(ChannelLayout::Mono, ChannelLayout::Quad);
+	assert_eq!(matrix.coeffs(), [1.0, 1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn mono_goes_to_the_center_speaker_when_there_is_one() {
+	let matrix = MixMatrix::between(ChannelLayout::Mono, ChannelLayout::FivePointOne);
+	assert_eq!(matrix.coeffs(), [0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn stereo_is_folded_into_mono_at_minus_3db() {
+	let matrix = MixMatrix::between(ChannelLayout::Stereo, ChannelLayout::Mono);
+	assert_eq!(matrix.coeffs(), [MINUS_3DB, MINUS_3DB]);
+}
+
+#[test]
+fn surround_is_downmixed_to_stereo() {
+	let matrix = MixMatrix::between(ChannelLayout::FivePointOne, ChannelLayout::Stereo);
+
+	// FL, FR, C, LFE, RL, RR for each of L and R, with the center and rear folded in at -3dB and the low frequency channel dropped
+	assert_eq!(matrix.coeffs(), [
+		1.0, 0.0, MINUS_3DB, 0.0, MINUS_3DB, 0.0,
+		0.0, 1.0, MINUS_3DB, 0.0, 0.0, MINUS_3DB,
+	]);
+}
+
+#[test]
+fn matching_layouts_mix_one_to_one() {
+	for layout in [ChannelLayout::Mono, ChannelLayout::Stereo, ChannelLayout::SevenPointOne, ChannelLayout::Discrete(3)] {
+		assert_eq!(MixMatrix::between(layout, layout), MixMatrix::identity(layout.channels()));
+	}
+}
+
+#[test]
+fn discrete_channels_are_mapped_by_their_index() {
+	let matrix = MixMatrix::between(ChannelLayout::Discrete(3), ChannelLayout::Stereo);
+	assert_eq!(matrix.coeffs(), [
+		1.0, 0.0, 0.0,
+		0.0, 1.0, 0.0,
+	]);
+}
+
+#[test]
+fn blocks_are_mixed_and_added() {
+	let matrix = MixMatrix::between(ChannelLayout::Stereo, ChannelLayout::Mono);
+	let input = [0.5, 0.5, 1.0, -1.0];
+	let mut output = [1.0, 0.0];
+
+	matrix.apply_block_add(&input, &mut output, 2.0);
+	assert_eq!(output, [1.0 + 2.0 * MINUS_3DB, 0.0]);
+}
+
+#[test]
+fn custom_matrices_need_a_coefficient_for_each_pair_of_channels() {
+	assert!(MixMatrix::new(2, 1, vec![0.5, 0.5]).is_some());
+	assert!(MixMatrix::new(2, 1, vec![0.5]).is_none());
+	assert!(MixMatrix::new(0, 1, vec![]).is_none());
+}
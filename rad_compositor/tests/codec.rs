@@ -0,0 +1,114 @@
+mod common;
+
+use common::fixture;
+#[cfg(any(feature = "wav", feature = "aiff", feature = "flac", feature = "alac"))]
+use common::tone_sample;
+use rad_compositor::source::{error::SourceError, formatted::FormattedStreamSource};
+#[cfg(any(feature = "wav", feature = "aiff", feature = "flac", feature = "alac", feature = "mp3", feature = "vorbis", feature = "aac"))]
+use rad_compositor::source::{BaseSource, TFrameIdx};
+#[cfg(feature = "flac")]
+use rad_compositor::source::utils::hint::{hint_of_mime_type, supported_extensions};
+
+/// The fixtures are 44.1 kHz stereo, the tones are 4608 frames of a sawtooth in the left channel and its inverse in the right one.
+#[cfg(any(feature = "wav", feature = "aiff", feature = "flac", feature = "alac", feature = "mp3", feature = "vorbis", feature = "aac"))]
+const SAMPLE_RATE: TFrameIdx = 44100;
+#[cfg(any(feature = "wav", feature = "aiff", feature = "flac", feature = "alac"))]
+const TONE_FRAMES: usize = 4608;
+
+/// Opens the fixture and checks that it's decoded into `frames` frames of 44.1 kHz stereo.
+/// It's decoded on the thread reading it, so that every read gives the decoded frames rather than the silence played while a worker is behind.
+#[cfg(any(feature = "wav", feature = "aiff", feature = "flac", feature = "alac", feature = "mp3", feature = "vorbis", feature = "aac"))]
+fn open(name: &str, frames: TFrameIdx) -> FormattedStreamSource {
+	let src = FormattedStreamSource::open_path(fixture(name)).unwrap_or_else(|err| panic!("'{}' couldn't be opened: {}", name, err));
+	src.set_decode_ahead_ms(0);
+
+	assert_eq!(src.sample_rate(), SAMPLE_RATE, "{}", name);
+	assert_eq!(src.channels(), 2, "{}", name);
+	assert_eq!(src.duration(), Some(frames), "{}", name);
+
+	src
+}
+
+/// Lossless formats give back exactly the samples that were encoded, `frames` is the length the format reader reports.
+#[cfg(any(feature = "wav", feature = "aiff", feature = "flac", feature = "alac"))]
+fn assert_tone(name: &str, frames: TFrameIdx) {
+	let mut src = open(name, frames);
+
+	let mut out = vec![0.0; TONE_FRAMES * 2];
+	assert_eq!(src.read_block(0, &mut out), TONE_FRAMES, "{}", name);
+
+	for (i, frame) in out.chunks_exact(2).enumerate() {
+		assert_eq!(frame, [tone_sample(i), -tone_sample(i)], "{} at {}", name, i);
+	}
+}
+
+/// The lossy fixtures are silence, which is all that can be encoded without an encoder at hand.
+#[cfg(any(feature = "mp3", feature = "vorbis", feature = "aac"))]
+fn assert_silence(name: &str, frames: TFrameIdx) {
+	let mut src = open(name, frames);
+
+	let mut out = vec![1.0; 1024 * 2];
+	assert_eq!(src.read_block(0, &mut out), 1024, "{}", name);
+	assert!(out.iter().all(|v| v.abs() < 1e-6), "{}", name);
+}
+
+#[cfg(feature = "wav")]
+#[test]
+fn wav_is_decoded() {
+	assert_tone("tone.wav", TONE_FRAMES as TFrameIdx);
+}
+
+#[cfg(feature = "aiff")]
+#[test]
+fn aiff_is_decoded() {
+	// The reader of symphonia counts the offset and block size fields in front of the samples as 2 more frames
+	assert_tone("tone.aiff", TONE_FRAMES as TFrameIdx + 2);
+}
+
+#[cfg(feature = "flac")]
+#[test]
+fn flac_is_decoded() {
+	assert_tone("tone.flac", TONE_FRAMES as TFrameIdx);
+}
+
+#[cfg(feature = "alac")]
+#[test]
+fn alac_is_decoded() {
+	assert_tone("tone_alac.m4a", TONE_FRAMES as TFrameIdx);
+}
+
+#[cfg(feature = "mp3")]
+#[test]
+fn mp3_is_decoded() {
+	assert_silence("silence.mp3", 20 * 1152);
+}
+
+#[cfg(feature = "vorbis")]
+#[test]
+fn vorbis_is_decoded() {
+	assert_silence("silence.ogg", 36 * 128);
+}
+
+#[cfg(feature = "aac")]
+#[test]
+fn aac_is_decoded() {
+	assert_silence("silence_aac.m4a", 5 * 1024);
+}
+
+#[cfg(feature = "flac")]
+#[test]
+fn streams_are_probed_with_their_content_type() {
+	let file = std::fs::File::open(fixture("tone.flac")).unwrap();
+	let src = FormattedStreamSource::open_stream(Box::new(file), &hint_of_mime_type("audio/x-flac; charset=binary"), None).unwrap();
+	assert_eq!(src.duration(), Some(TONE_FRAMES as TFrameIdx));
+
+	assert!(supported_extensions().contains(&"flac"));
+}
+
+#[test]
+fn failures_to_open_are_reported() {
+	assert!(matches!(FormattedStreamSource::open_path(fixture("missing.wav")), Err(SourceError::NotFound)));
+
+	let garbage = std::io::Cursor::new(vec![0x5a_u8; 4096]);
+	assert!(matches!(FormattedStreamSource::open_stream(Box::new(garbage), &Default::default(), None), Err(SourceError::UnsupportedFormat)));
+}
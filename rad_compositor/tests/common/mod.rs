@@ -0,0 +1,12 @@
+use std::path::PathBuf;
+
+pub fn fixture(name: &str) -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// The sawtooth of the tone fixtures in the left channel, the right one being its inverse.
+/// The tones are only in the lossless formats, as encoding the lossy ones takes an encoder.
+#[cfg(any(feature = "wav", feature = "aiff", feature = "flac", feature = "alac"))]
+pub fn tone_sample(i: usize) -> f32 {
+	((i as i32 * 37) % 2000 - 1000) as f32 * 8.0 / 32768.0
+}
@@ -0,0 +1,144 @@
+use rad_compositor::{channel_layout::ChannelLayout, composition::{CompositionState, TSrcID}, compositor::{compute_frames, MixState}, resampler::ResampleQuality, source::{generator::GeneratorSrc, Source, TFrameIdx}};
+
+fn sine(freq: u32) -> Source {
+	format!("sine:freq={},rate=48000", freq).parse::<GeneratorSrc>().unwrap().into()
+}
+
+#[test]
+fn sources_get_ids_of_their_own() {
+	let mut cmp_a = CompositionState::new("ids-a".into(), 1.0, ChannelLayout::Mono);
+	let mut cmp_b = CompositionState::new("ids-b".into(), 1.0, ChannelLayout::Mono);
+
+	let first = cmp_a.push_src_offset(sine(100), 0);
+	let second = cmp_a.push_src_offset(sine(200), 0);
+	// IDs are unique across compositions too
+	let other = cmp_b.push_src_offset(sine(300), 0);
+
+	assert_ne!(first, second);
+	assert_ne!(first, other);
+	assert_ne!(second, other);
+
+	assert_eq!(cmp_a.sources().iter().map(|s| s.id()).collect::<Vec<_>>(), [first, second]);
+	assert_eq!(cmp_a.find_src(second).unwrap().id(), second);
+	assert!(cmp_a.find_src(other).is_none());
+}
+
+#[test]
+fn removed_sources_keep_the_rest_and_their_ids() {
+	let mut cmp = CompositionState::new("removal".into(), 1.0, ChannelLayout::Mono);
+	let ids = [100, 200, 300].map(|freq| cmp.push_src_offset(sine(freq), 0));
+
+	let removed = cmp.remove_src(ids[1]).unwrap();
+	assert_eq!(removed.id(), ids[1]);
+	assert!(cmp.find_src(ids[1]).is_none());
+	assert!(cmp.remove_src(ids[1]).is_none());
+
+	// The sources after the removed one keep their IDs, and IDs aren't handed out again
+	assert_eq!(cmp.sources().iter().map(|s| s.id()).collect::<Vec<_>>(), [ids[0], ids[2]]);
+	let added = cmp.push_src_offset(sine(400), 0);
+	assert!(!ids.contains(&added));
+}
+
+#[test]
+fn removed_sources_stop_contributing() {
+	let mut cmp = CompositionState::new("silenced".into(), 1.0, ChannelLayout::Mono);
+	let id = cmp.push_src_offset(sine(1000), 0);
+
+	let mut mix = MixState::new(48000, ChannelLayout::Mono, 1.0, 0, ResampleQuality::default());
+	let mut buf = vec![0.0; 1024];
+	compute_frames(cmp.sources_mut(), &mut mix, 0, &mut buf);
+	assert!(buf.iter().any(|v| *v != 0.0));
+
+	cmp.remove_src(id);
+	compute_frames(cmp.sources_mut(), &mut mix, 1024, &mut buf);
+	assert!(buf.iter().all(|v| *v == 0.0));
+}
+
+/// The first blocks of the composition, mixed without ramps.
+fn render(cmp: &mut CompositionState) -> Vec<f32> {
+	let mut mix = MixState::new(48000, ChannelLayout::Mono, 1.0, 0, ResampleQuality::default());
+	let mut out = vec![0.0; 4 * 1024];
+
+	for (i, block) in out.chunks_exact_mut(1024).enumerate() {
+		compute_frames(cmp.sources_mut(), &mut mix, i as TFrameIdx * 1024, block);
+	}
+
+	out
+}
+
+/// The first blocks of a composition of only the sine of `freq`.
+fn render_alone(freq: u32) -> Vec<f32> {
+	let mut cmp = CompositionState::new("alone".into(), 1.0, ChannelLayout::Mono);
+	cmp.push_src_offset(sine(freq), 0);
+
+	render(&mut cmp)
+}
+
+#[test]
+fn muted_sources_are_left_out() {
+	let mut cmp = CompositionState::new("mute".into(), 1.0, ChannelLayout::Mono);
+	let muted = cmp.push_src_offset(sine(100), 0);
+	cmp.push_src_offset(sine(200), 0);
+
+	assert_eq!(cmp.set_src_muted(muted, true), Some(()));
+	assert_eq!(render(&mut cmp), render_alone(200));
+
+	cmp.set_src_muted(muted, false);
+	assert_ne!(render(&mut cmp), render_alone(200));
+
+	assert_eq!(cmp.set_src_muted(TSrcID::MAX, true), None);
+}
+
+#[test]
+fn only_soloed_sources_are_heard() {
+	let mut cmp = CompositionState::new("solo".into(), 1.0, ChannelLayout::Mono);
+	cmp.push_src_offset(sine(100), 0);
+	let soloed = cmp.push_src_offset(sine(200), 0);
+	cmp.push_src_offset(sine(300), 0);
+
+	cmp.set_src_solo(soloed, true);
+	assert_eq!(render(&mut cmp), render_alone(200));
+
+	// A muted source stays silent even when soloed
+	cmp.set_src_muted(soloed, true);
+	assert!(render(&mut cmp).iter().all(|v| *v == 0.0));
+}
+
+#[test]
+fn paused_sources_resume_where_they_were_paused() {
+	let mut cmp = CompositionState::new("src-pause".into(), 1.0, ChannelLayout::Mono);
+	let id = cmp.push_src_offset(sine(100), 0);
+
+	// The clock of the composition stands still while it's paused, which keeps the times below exact
+	cmp.set_paused(true);
+	cmp.set_time_millis(1000);
+	cmp.set_src_paused(id, true);
+	assert!(cmp.find_src(id).unwrap().composition_data.is_paused());
+	assert!(render(&mut cmp).iter().all(|v| *v == 0.0));
+
+	cmp.set_time_millis(1500);
+	cmp.set_src_paused(id, false);
+
+	// The source starts half a second later, so that it goes on from where it was paused
+	let data = &cmp.find_src(id).unwrap().composition_data;
+	assert!(!data.is_paused());
+	assert_eq!(data.frame_offset, 24000);
+}
+
+#[test]
+fn paused_sources_stay_put_through_seeks() {
+	let mut cmp = CompositionState::new("src-pause-seek".into(), 1.0, ChannelLayout::Mono);
+	let id = cmp.push_src_offset(sine(100), 0);
+
+	cmp.set_paused(true);
+	cmp.set_time_millis(1000);
+	cmp.set_src_paused(id, true);
+
+	// Seeking the composition back doesn't take the paused source with it
+	cmp.set_time_millis(250);
+	assert_eq!(cmp.now_playing(id).unwrap().elapsed, 48000);
+
+	cmp.set_src_paused(id, false);
+	assert_eq!(cmp.find_src(id).unwrap().composition_data.frame_offset, -36000);
+	assert_eq!(cmp.now_playing(id).unwrap().elapsed, 48000);
+}
@@ -0,0 +1,109 @@
+#[cfg(any(feature = "wav", feature = "alac"))]
+mod common;
+
+#[cfg(any(feature = "wav", feature = "alac"))]
+use std::{thread::sleep, time::{Duration, Instant}};
+
+#[cfg(any(feature = "wav", feature = "alac"))]
+use common::{fixture, tone_sample};
+#[cfg(any(feature = "wav", feature = "alac"))]
+use rad_compositor::source::{formatted::{decode_ahead::DEFAULT_DECODE_AHEAD_MS, FormattedStreamSource}, BaseSource};
+#[cfg(feature = "wav")]
+use rad_compositor::source::{queue::QueueSrc, Source};
+
+/// The tone fixtures are 4608 frames long.
+#[cfg(feature = "wav")]
+const TONE_FRAMES: usize = 4608;
+
+/// Opens the fixture decoding it ahead with the default window, whatever the other tests set for all the sources.
+#[cfg(any(feature = "wav", feature = "alac"))]
+fn open(name: &str) -> FormattedStreamSource {
+	let src = FormattedStreamSource::open_path(fixture(name)).unwrap();
+	src.set_decode_ahead_ms(DEFAULT_DECODE_AHEAD_MS);
+
+	src
+}
+
+#[cfg(any(feature = "wav", feature = "alac"))]
+fn is_tone(frames: &[f32], start: usize) -> bool {
+	frames.chunks_exact(2).enumerate().all(|(i, frame)| frame == [tone_sample(start + i), -tone_sample(start + i)])
+}
+
+/// Waits for the condition to hold for up to 5 seconds, and gives whether it did.
+#[cfg(any(feature = "wav", feature = "alac"))]
+fn eventually(cond: impl Fn() -> bool) -> bool {
+	let deadline = Instant::now() + Duration::from_secs(5);
+	while !cond() && Instant::now() < deadline {
+		sleep(Duration::from_millis(5));
+	}
+
+	cond()
+}
+
+#[cfg(feature = "wav")]
+#[test]
+fn sources_read_what_the_worker_decoded() {
+	let mut src = open("tone.wav");
+
+	// Reading from the middle first, so that the worker has to seek there and back.
+	// The reads wait for the worker rather than playing silence while it starts.
+	let mut out = vec![0.0; 1000 * 2];
+	for start in [3000, 0, 1500] {
+		assert_eq!(src.read_block(start as u64, &mut out), 1000);
+		assert!(is_tone(&out, start), "from {}", start);
+	}
+
+	// The frames past the end are never decoded, nor played as silence
+	assert_eq!(src.read_block(4108, &mut out), 500);
+	assert!(is_tone(&out[..500 * 2], 4108));
+}
+
+#[cfg(feature = "wav")]
+#[test]
+fn workers_stop_once_the_source_is_decoded_to_its_end() {
+	let mut src = open("tone.wav");
+	assert!(!src.is_decoding_ahead());
+
+	// The whole fixture fits in the window, so the worker has nothing left to do once it's decoded
+	let mut out = vec![0.0; 16 * 2];
+	src.read_block(0, &mut out);
+	assert!(eventually(|| !src.is_decoding_ahead()));
+
+	// Reading what's decoded already doesn't start it again
+	src.read_block(1000, &mut out);
+	assert!(!src.is_decoding_ahead());
+}
+
+#[cfg(feature = "wav")]
+#[test]
+fn queued_items_follow_each_other_without_a_gap() {
+	let mut queue = QueueSrc::new(44100);
+	queue.push(Source::File(open("tone.wav")));
+	queue.push(Source::File(open("tone.wav")));
+
+	// The next item is decoded while the one before it plays, so that it starts right away
+	let mut out = vec![0.0; 512 * 2];
+	for start in (0..2 * TONE_FRAMES).step_by(512) {
+		assert_eq!(queue.read_block(start as u64, &mut out), 512);
+		assert!(is_tone(&out, start % TONE_FRAMES), "from {}", start);
+	}
+}
+
+#[cfg(feature = "alac")]
+#[test]
+fn the_worker_decodes_ahead_of_the_playhead() {
+	// Only the first packet and the fourth one are valid, the rest of `faulty_alac.m4a` is corrupt
+	let mut src = open("faulty_alac.m4a");
+
+	// Nothing is decoded before the source is first read
+	sleep(Duration::from_millis(50));
+	assert_eq!(src.decode_errors(), 0);
+
+	let mut out = vec![0.0; 16 * 2];
+	assert_eq!(src.read_block(0, &mut out), 16);
+	assert!(is_tone(&out, 0));
+
+	// The corrupt packets are reached without reading any further, after which the worker stops
+	assert!(eventually(|| src.is_faulted()));
+	assert!(eventually(|| !src.is_decoding_ahead()));
+}
@@ -0,0 +1,59 @@
+use std::sync::{atomic::AtomicBool, Arc, Mutex};
+
+use rad_compositor::{adapter::AdapterHandle, channel_layout::ChannelLayout, composition::CompositionState, event::{Event, EventBus, GLOBAL_EVENT_BUS, SUBSCRIBER_BACKLOG}, source::generator::GeneratorSrc};
+
+/// The events published so far that are about the composition `cmp_id`, as the tests run side by side on the same bus.
+fn events_of(events: &std::sync::mpsc::Receiver<Event>, cmp_id: &str) -> Vec<Event> {
+	events.try_iter().filter(|e| e.cmp_id() == Some(cmp_id)).collect()
+}
+
+#[test]
+fn compositions_announce_their_changes() {
+	let events = GLOBAL_EVENT_BUS.subscribe();
+	let mut cmp = CompositionState::new("events".into(), 1.0, ChannelLayout::Mono);
+
+	let src_id = cmp.push_src_offset("sine:freq=1000".parse::<GeneratorSrc>().unwrap().into(), 0);
+	cmp.set_paused(true);
+	// Pausing twice changes nothing
+	cmp.set_paused(true);
+	cmp.set_time_millis(1500);
+	cmp.set_paused(false);
+	cmp.remove_src(src_id);
+
+	let cmp_id = "events".to_owned();
+	assert_eq!(events_of(&events, "events"), [
+		Event::SourceAdded { cmp_id: cmp_id.clone(), src_id, kind: "generator".into() },
+		Event::CompositionPaused { cmp_id: cmp_id.clone() },
+		Event::CompositionSeeked { cmp_id: cmp_id.clone(), time_ms: 1500 },
+		Event::CompositionResumed { cmp_id: cmp_id.clone() },
+		Event::SourceRemoved { cmp_id, src_id }
+	]);
+}
+
+#[test]
+fn adapters_announce_being_closed_once() {
+	let events = GLOBAL_EVENT_BUS.subscribe();
+	let mut adapter = AdapterHandle::new("events-ap".into(), "udp".into(), Arc::new(Mutex::new(String::new())), Arc::new(AtomicBool::new(false)));
+	let mut other_handle = adapter.clone();
+
+	adapter.close();
+	other_handle.close();
+	assert!(other_handle.is_closed());
+
+	let closed = events.try_iter().filter(|e| matches!(e, Event::AdapterClosed { adapter_id } if adapter_id == "events-ap")).count();
+	assert_eq!(closed, 1);
+}
+
+#[test]
+fn subscribers_falling_behind_get_dropped() {
+	// A bus of its own, as the events of the other tests would count towards the backlog
+	let bus = EventBus::default();
+	let events = bus.subscribe();
+
+	for time_ms in 0..=SUBSCRIBER_BACKLOG as u64 {
+		bus.publish(Event::CompositionSeeked { cmp_id: "lagging".into(), time_ms });
+	}
+
+	// What fit in the backlog is still received, then the subscriber finds itself disconnected
+	assert_eq!(events.iter().count(), SUBSCRIBER_BACKLOG);
+}
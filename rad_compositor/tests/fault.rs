@@ -0,0 +1,137 @@
+#[cfg(any(feature = "alac", feature = "flac"))]
+mod common;
+
+#[cfg(feature = "alac")]
+use std::{sync::{Arc, RwLock}, time::Duration};
+
+#[cfg(any(feature = "alac", feature = "flac"))]
+use common::{fixture, tone_sample};
+#[cfg(any(feature = "alac", feature = "flac"))]
+use rad_compositor::source::{formatted::FormattedStreamSource, BaseSource};
+#[cfg(feature = "alac")]
+use rad_compositor::{channel_layout::ChannelLayout, cmp_reg::CompositionRegistry, composition::CompositionState, compositor::{compute_frames, MixState}, event::{Event, GLOBAL_EVENT_BUS}, resampler::ResampleQuality, source::{formatted::MAX_CONSECUTIVE_DECODE_ERRORS, generator::GeneratorSrc, TFrameIdx}};
+#[cfg(feature = "flac")]
+use rad_compositor::source::utils::hint::hint_of_path;
+
+/// `faulty_alac.m4a` is 24 packets of 256 frames of which the second, third and the ones after the fourth are corrupt.
+#[cfg(feature = "alac")]
+const PACKET_FRAMES: usize = 256;
+
+/// Opens the fixture decoding it on the thread reading it, so that the errors are counted as the packets are read rather than ahead of it.
+#[cfg(feature = "alac")]
+fn open(name: &str) -> FormattedStreamSource {
+	let src = FormattedStreamSource::open_path(fixture(name)).unwrap();
+	src.set_decode_ahead_ms(0);
+
+	src
+}
+
+#[cfg(any(feature = "alac", feature = "flac"))]
+fn assert_tone(frames: &[f32], first_frame: usize) {
+	for (i, frame) in frames.chunks_exact(2).enumerate() {
+		assert_eq!(frame, [tone_sample(first_frame + i), -tone_sample(first_frame + i)], "at {}", first_frame + i);
+	}
+}
+
+#[cfg(feature = "alac")]
+#[test]
+fn corrupt_packets_are_concealed_with_silence() {
+	let mut src = open("faulty_alac.m4a");
+
+	let mut out = vec![1.0; 4 * PACKET_FRAMES * 2];
+	assert_eq!(src.read_block(0, &mut out), 4 * PACKET_FRAMES);
+
+	assert_tone(&out[..PACKET_FRAMES * 2], 0);
+	assert!(out[PACKET_FRAMES * 2..3 * PACKET_FRAMES * 2].iter().all(|v| *v == 0.0));
+	assert_tone(&out[3 * PACKET_FRAMES * 2..], 3 * PACKET_FRAMES);
+
+	assert_eq!(src.decode_errors(), 2);
+	assert!(!src.is_faulted());
+}
+
+#[cfg(feature = "alac")]
+#[test]
+fn sources_failing_in_a_row_get_faulted() {
+	let mut src = open("faulty_alac.m4a");
+
+	// The packets failing before the source gives up are still played as silence
+	let expected = 4 + MAX_CONSECUTIVE_DECODE_ERRORS as usize - 1;
+	let mut out = vec![1.0; 24 * PACKET_FRAMES * 2];
+	assert_eq!(src.read_block(0, &mut out), expected * PACKET_FRAMES);
+
+	assert_eq!(src.decode_errors(), 2 + MAX_CONSECUTIVE_DECODE_ERRORS as u64);
+	assert!(src.is_faulted());
+
+	// Nothing more is decoded from then on
+	assert_eq!(src.read_block(expected as TFrameIdx * PACKET_FRAMES as TFrameIdx, &mut out), 0);
+}
+
+#[cfg(feature = "flac")]
+#[test]
+fn packets_skipped_by_the_reader_are_concealed_with_silence() {
+	// The second frame gets a corrupt subframe, failing its checksum so that the reader skips it
+	let mut data = std::fs::read(fixture("tone.flac")).unwrap();
+	let frame_len = 8 + 2 * (1 + 1152 * 2) + 2;
+	data[42 + frame_len + 8] = 0x7e;
+
+	let path = fixture("tone.flac");
+	let mut src = FormattedStreamSource::open_stream(Box::new(std::io::Cursor::new(data)), &hint_of_path(&path), None).unwrap();
+	src.set_decode_ahead_ms(0);
+
+	let mut out = vec![1.0; 4608 * 2];
+	assert_eq!(src.read_block(0, &mut out), 4608);
+
+	assert_tone(&out[..1152 * 2], 0);
+	assert!(out[1152 * 2..2304 * 2].iter().all(|v| *v == 0.0));
+	assert_tone(&out[2304 * 2..], 2304);
+
+	assert_eq!(src.decode_errors(), 1);
+	assert!(!src.is_faulted());
+}
+
+#[cfg(feature = "alac")]
+#[test]
+fn faulted_sources_leave_the_rest_of_the_composition_playing() {
+	let mut cmp = CompositionState::new("fault".into(), 1.0, ChannelLayout::Stereo);
+	let faulty_id = cmp.push_src_offset(open("faulty_alac.m4a").into(), 0);
+	let sine_id = cmp.push_src_offset("sine:freq=1000,rate=44100".parse::<GeneratorSrc>().unwrap().into(), 0);
+	let events = GLOBAL_EVENT_BUS.subscribe();
+
+	let mut mix = MixState::new(44100, ChannelLayout::Stereo, 1.0, 20, ResampleQuality::default());
+	let mut buf = vec![0.0; 1024 * 2];
+	for i in 0..8 {
+		compute_frames(cmp.sources_mut(), &mut mix, i * 1024, &mut buf);
+		cmp.fault_sources(&mix.take_faulted());
+	}
+
+	assert!(cmp.find_src(faulty_id).unwrap().composition_data.is_faulted());
+	assert!(!cmp.find_src(sine_id).unwrap().composition_data.is_faulted());
+	assert!(buf.iter().any(|v| *v != 0.0));
+
+	let faulted = events.try_iter().filter(|e| matches!(e, Event::SourceFaulted { cmp_id, src_id } if cmp_id == "fault" && *src_id == faulty_id)).count();
+	assert_eq!(faulted, 1);
+}
+
+#[cfg(feature = "alac")]
+#[test]
+fn compositor_threads_fault_sources() {
+	let mut cmp = CompositionState::new("fault-thread".into(), 1.0, ChannelLayout::Stereo);
+	let faulty_id = cmp.push_src_offset(open("faulty_alac.m4a").into(), 0);
+	let cmp = Arc::new(RwLock::new(cmp));
+	let events = GLOBAL_EVENT_BUS.subscribe();
+
+	let mut reg = CompositionRegistry::new();
+	reg.push_composition(cmp.clone());
+	let mut cursor = reg.get_cursor("fault-thread", 44100, 1024).unwrap();
+
+	// The source gives up within its first 24 packets, which are a few buffers long
+	let is_faulted = || cmp.read().unwrap().find_src(faulty_id).unwrap().composition_data.is_faulted();
+	for _ in 0..100 {
+		if is_faulted() { break; }
+		cursor.read_timeout(Duration::from_secs(5)).unwrap();
+	}
+	assert!(is_faulted());
+
+	let faulted = events.try_iter().filter(|e| matches!(e, Event::SourceFaulted { cmp_id, src_id } if cmp_id == "fault-thread" && *src_id == faulty_id)).count();
+	assert_eq!(faulted, 1);
+}
@@ -0,0 +1,103 @@
+use rad_compositor::{channel_layout::ChannelLayout, composition::{CompositionState, FinishPolicy, TSrcID}, compositor::{compute_frames, MixState}, event::{Event, GLOBAL_EVENT_BUS}, resampler::ResampleQuality, source::{generator::GeneratorSrc, BaseSource, TFrameIdx}};
+
+const BUF_SIZE: usize = 1024;
+/// The frames in each buffer of a mono compositor.
+const BUF_FRAMES: TFrameIdx = BUF_SIZE as TFrameIdx;
+
+/// A composition with a mono sine lasting two buffers.
+fn composition(policy: FinishPolicy) -> (CompositionState, TSrcID) {
+	let mut cmp = CompositionState::new("finish".into(), 1.0, ChannelLayout::Mono);
+	let src = format!("sine:freq=1000,rate=48000,dur={}", 2.0 * BUF_FRAMES as f64 / 48000.0).parse::<GeneratorSrc>().unwrap();
+	let id = cmp.push_src_offset(src.into(), 0);
+	cmp.find_src_mut(id).unwrap().composition_data.finish_policy = policy;
+
+	(cmp, id)
+}
+
+/// Computes the buffers one after another from the start, applying the finish policies after each of them like the compositors do.
+fn compute(cmp: &mut CompositionState, buffers: usize) -> Vec<[f32; BUF_SIZE]> {
+	let mut mix = MixState::new(48000, ChannelLayout::Mono, 1.0, 20, ResampleQuality::default());
+
+	(0..buffers).map(|i| {
+		let mut buf = [0.0; BUF_SIZE];
+		compute_frames(cmp.sources_mut(), &mut mix, i as TFrameIdx * BUF_FRAMES, &mut buf);
+		cmp.finish_sources(&mix.take_finished());
+
+		buf
+	}).collect()
+}
+
+fn is_silent(buf: &[f32]) -> bool {
+	buf.iter().all(|v| *v == 0.0)
+}
+
+#[test]
+fn finished_sources_get_removed() {
+	let (mut cmp, id) = composition(FinishPolicy::Remove);
+	let events = GLOBAL_EVENT_BUS.subscribe();
+
+	let bufs = compute(&mut cmp, 4);
+	assert!(!is_silent(&bufs[1]));
+	assert!(is_silent(&bufs[2]));
+	assert!(cmp.find_src(id).is_none());
+
+	let finished = events.try_iter().filter(|e| matches!(e, Event::SourceFinished { src_id, .. } if *src_id == id)).count();
+	assert_eq!(finished, 1);
+}
+
+#[test]
+fn held_sources_stay_finished() {
+	let (mut cmp, id) = composition(FinishPolicy::Hold);
+
+	let bufs = compute(&mut cmp, 4);
+	assert!(is_silent(&bufs[3]));
+	assert!(cmp.find_src(id).unwrap().composition_data.is_finished());
+
+	// Seeking back makes it play again
+	cmp.set_time_millis(0);
+	assert!(!cmp.find_src(id).unwrap().composition_data.is_finished());
+}
+
+#[test]
+fn looping_sources_start_over() {
+	let (mut cmp, id) = composition(FinishPolicy::Loop(1));
+	let bufs = compute(&mut cmp, 6);
+
+	let mut expected = "sine:freq=1000,rate=48000".parse::<GeneratorSrc>().unwrap();
+	let mut first = vec![0.0; BUF_SIZE];
+	expected.read_block(0, &mut first);
+
+	assert_eq!(&bufs[2][..], &first[..]);
+	assert!(!is_silent(&bufs[3]));
+	assert!(is_silent(&bufs[4]));
+	assert!(cmp.find_src(id).unwrap().composition_data.is_finished());
+
+	let (mut cmp, id) = composition(FinishPolicy::LoopForever);
+	let bufs = compute(&mut cmp, 12);
+	assert_eq!(&bufs[10][..], &first[..]);
+	assert!(!cmp.find_src(id).unwrap().composition_data.is_finished());
+}
+
+#[test]
+fn policies_survive_being_listed() {
+	for policy in [FinishPolicy::Remove, FinishPolicy::Hold, FinishPolicy::Loop(3), FinishPolicy::LoopForever] {
+		assert_eq!(policy.to_string().parse::<FinishPolicy>(), Ok(policy));
+	}
+
+	for spec in ["", "loop:", "loop:-1", "hold:2", "stop"] {
+		assert!(spec.parse::<FinishPolicy>().is_err(), "{}", spec);
+	}
+}
+
+#[test]
+fn paused_sources_dont_finish() {
+	let (mut cmp, id) = composition(FinishPolicy::Remove);
+	cmp.set_paused(true);
+	cmp.set_src_paused(id, true);
+
+	// The composition goes on past the end of the source, which stays where it got paused
+	compute(&mut cmp, 4);
+	let data = &cmp.find_src(id).unwrap().composition_data;
+	assert!(data.is_paused());
+	assert!(!data.is_finished());
+}
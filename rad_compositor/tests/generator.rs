@@ -0,0 +1,84 @@
+use rad_compositor::{channel_layout::ChannelLayout, composition::CompositionState, compositor::{compute_frames, MixState}, resampler::ResampleQuality, source::{generator::GeneratorSrc, BaseSource, TFrameIdx, TSample}};
+
+const BUF_SIZE: usize = 1024;
+
+fn read(src: &mut GeneratorSrc, frame_idx: TFrameIdx, frames: usize) -> (usize, Vec<TSample>) {
+	let mut out = vec![0.0; frames * src.channels() as usize];
+	let read = src.read_block(frame_idx, &mut out);
+
+	(read, out)
+}
+
+#[test]
+fn specs_survive_being_listed() {
+	for spec in ["sine:freq=440", "sweep:from=20,to=20000,dur=5", "noise:color=pink,seed=7", "silence:dur=1.5", "click:bpm=90,beats=3,layout=stereo,ch=1"] {
+		let src = spec.parse::<GeneratorSrc>().unwrap();
+		assert_eq!(src.to_string().parse::<GeneratorSrc>().unwrap(), src, "{}", spec);
+	}
+}
+
+#[test]
+fn invalid_specs_are_rejected() {
+	for spec in ["sine", "sine:freq=-1", "silence", "sweep:from=20", "noise:color=blue", "click:bpm=0", "sine:freq=1,ch=1", "square:freq=1"] {
+		assert!(spec.parse::<GeneratorSrc>().is_err(), "{}", spec);
+	}
+}
+
+#[test]
+fn frames_only_depend_on_their_index() {
+	for spec in ["sine:freq=997", "sweep:from=20,to=20000,dur=2", "noise:color=white,seed=3", "noise:color=pink,seed=3", "click:bpm=120"] {
+		let mut src = spec.parse::<GeneratorSrc>().unwrap();
+
+		let (_, block) = read(&mut src, 1000, 256);
+		let (_, again) = read(&mut src, 1000, 256);
+		assert_eq!(block, again, "{}", spec);
+
+		for (i, v) in block.iter().enumerate() {
+			assert_eq!(src.get_by_frame_i(1000 + i as TFrameIdx).unwrap()[0], *v, "{}", spec);
+			assert!(v.abs() <= 0.5, "{}", spec);
+		}
+	}
+}
+
+#[test]
+fn generators_end_after_their_duration() {
+	let mut src = "sine:freq=1000,dur=0.01,rate=48000".parse::<GeneratorSrc>().unwrap();
+	assert_eq!(src.duration(), Some(480));
+
+	let (read_frames, _) = read(&mut src, 400, 256);
+	assert_eq!(read_frames, 80);
+	assert!(src.get_by_frame_i(480).is_none());
+}
+
+#[test]
+fn clicks_land_on_the_beats() {
+	let mut src = "click:bpm=120,rate=48000".parse::<GeneratorSrc>().unwrap();
+	let (_, block) = read(&mut src, 0, 48000);
+
+	// A beat every half a second, each click lasting 20 ms
+	for (i, v) in block.iter().enumerate() {
+		if i % 24000 >= 960 { assert_eq!(*v, 0.0, "{}", i); }
+	}
+	assert!(block[10..960].iter().any(|v| v.abs() > 0.1));
+	assert!(block[24010..24960].iter().any(|v| v.abs() > 0.1));
+}
+
+#[test]
+fn the_compositor_outputs_a_generator_untouched() {
+	let mut cmp = CompositionState::new("test".into(), 1.0, ChannelLayout::Stereo);
+	cmp.push_src_offset("sine:freq=1000,layout=stereo,rate=48000".parse::<GeneratorSrc>().unwrap().into(), 0);
+
+	let mut mix = MixState::new(48000, ChannelLayout::Stereo, 1.0, 20, ResampleQuality::default());
+	let mut out = [0.0; BUF_SIZE];
+	let mut expected = "sine:freq=1000,layout=stereo,rate=48000".parse::<GeneratorSrc>().unwrap();
+
+	// The same buffer is used for every block, as the compositors do
+	for offset in [4800, 4800 + BUF_SIZE as TFrameIdx / 2] {
+		compute_frames(cmp.sources_mut(), &mut mix, offset, &mut out);
+		let (_, expected) = read(&mut expected, offset, BUF_SIZE / 2);
+
+		for (o, e) in out.iter().zip(expected.iter()) {
+			assert!((o - e).abs() < 1e-6, "from {}", offset);
+		}
+	}
+}
@@ -0,0 +1,65 @@
+use rad_compositor::{channel_layout::ChannelLayout, composition::CompositionState, compositor::{compute_frames, level::{Levels, LEVEL_FLOOR_DB}, MixState}, resampler::ResampleQuality, source::{generator::GeneratorSrc, TFrameIdx}};
+
+const BUF_SIZE: usize = 1024;
+const BUF_FRAMES: TFrameIdx = BUF_SIZE as TFrameIdx;
+
+/// Computes `buffers` mono buffers of `cmp` and gives the levels of the last one.
+fn levels_after(cmp: &mut CompositionState, buffers: usize) -> (Levels, Vec<Levels>) {
+	let mut mix = MixState::new(48000, ChannelLayout::Mono, 1.0, 20, ResampleQuality::default());
+	let mut levels = None;
+
+	for i in 0..buffers {
+		compute_frames(cmp.sources_mut(), &mut mix, i as TFrameIdx * BUF_FRAMES, &mut [0.0; BUF_SIZE]);
+		levels = Some(mix.take_levels());
+	}
+
+	let (output, sources) = levels.unwrap();
+	(output, sources.into_iter().map(|(_, levels)| levels).collect())
+}
+
+fn assert_near(value: f32, expected: f32) {
+	assert!((value - expected).abs() < 0.1, "{} isn't {}", value, expected);
+}
+
+#[test]
+fn full_scale_sines_are_measured() {
+	let mut cmp = CompositionState::new("levels".into(), 1.0, ChannelLayout::Mono);
+	cmp.push_src_offset("sine:freq=1000,amp=1,rate=48000".parse::<GeneratorSrc>().unwrap().into(), 0);
+
+	let (output, sources) = levels_after(&mut cmp, 10);
+	assert_near(output.max_peak_db(), 0.0);
+	assert_near(output.rms_db[0], -3.01);
+	// A full scale sine of 1 kHz in a single channel reads -3.01 LUFS
+	assert_near(output.short_term_lufs.unwrap(), -3.01);
+	assert_eq!(sources, [output]);
+}
+
+#[test]
+fn sources_are_metered_before_their_amplification() {
+	let mut cmp = CompositionState::new("levels-amp".into(), 1.0, ChannelLayout::Mono);
+	let id = cmp.push_src_offset("sine:freq=1000,amp=0.5,rate=48000".parse::<GeneratorSrc>().unwrap().into(), 0);
+	cmp.find_src_mut(id).unwrap().composition_data.amplification = 0.5;
+
+	let (output, sources) = levels_after(&mut cmp, 10);
+	assert_near(output.max_peak_db(), -12.04);
+	assert_near(sources[0].max_peak_db(), -6.02);
+	assert_near(sources[0].short_term_lufs.unwrap() - output.short_term_lufs.unwrap(), 6.02);
+
+	// Muted sources aren't read at all
+	cmp.find_src_mut(id).unwrap().composition_data.muted = true;
+	let (output, sources) = levels_after(&mut cmp, 1);
+	assert_eq!(output.peak_db, [LEVEL_FLOOR_DB]);
+	assert_eq!(sources[0].peak_db, [LEVEL_FLOOR_DB]);
+}
+
+#[test]
+fn nothing_is_silence() {
+	let mut cmp = CompositionState::new("levels-empty".into(), 1.0, ChannelLayout::Mono);
+
+	let (output, sources) = levels_after(&mut cmp, 1);
+	assert_eq!(output, Levels { short_term_lufs: None, ..Levels::silent(1) });
+	assert!(sources.is_empty());
+
+	let (output, _) = levels_after(&mut cmp, 5);
+	assert_eq!(output.short_term_lufs, Some(LEVEL_FLOOR_DB));
+}
@@ -0,0 +1,86 @@
+use rad_compositor::{channel_layout::ChannelLayout, composition::CompositionState, source::{formatted::FormattedStreamSource, queue::QueueSrc, BaseSource}};
+
+const SAMPLE_RATE: u32 = 48000;
+
+/// Writes a second of mono 16-bit silence to a WAV file with a RIFF INFO list of `tags`.
+fn write_wav(name: &str, tags: &[(&[u8; 4], &str)]) -> std::path::PathBuf {
+	let frames = SAMPLE_RATE;
+
+	let mut info = b"INFO".to_vec();
+	for (id, value) in tags {
+		let mut value = value.as_bytes().to_vec();
+		value.push(0);
+		if value.len() % 2 == 1 { value.push(0); }
+
+		info.extend(*id);
+		info.extend((value.len() as u32).to_le_bytes());
+		info.extend(value);
+	}
+
+	let mut body = b"WAVEfmt ".to_vec();
+	body.extend(16u32.to_le_bytes());
+	body.extend(1u16.to_le_bytes());
+	body.extend(1u16.to_le_bytes());
+	body.extend(SAMPLE_RATE.to_le_bytes());
+	body.extend((SAMPLE_RATE * 2).to_le_bytes());
+	body.extend(2u16.to_le_bytes());
+	body.extend(16u16.to_le_bytes());
+	body.extend(b"LIST");
+	body.extend((info.len() as u32).to_le_bytes());
+	body.extend(info);
+	body.extend(b"data");
+	body.extend((frames * 2).to_le_bytes());
+	body.extend(vec![0; frames as usize * 2]);
+
+	let mut wav = b"RIFF".to_vec();
+	wav.extend((body.len() as u32).to_le_bytes());
+	wav.extend(body);
+
+	let path = std::env::temp_dir().join(format!("rad-metadata-{}-{}.wav", std::process::id(), name));
+	std::fs::write(&path, wav).unwrap();
+
+	path
+}
+
+#[test]
+fn tags_are_read_from_files() {
+	let path = write_wav("tags", &[(b"INAM", "A Title"), (b"IART", "An Artist"), (b"IPRD", "An Album"), (b"IPRT", "3/12"), (b"ICMT", "A Comment")]);
+	let src = FormattedStreamSource::open_path(path.clone()).unwrap();
+	std::fs::remove_file(path).unwrap();
+
+	let metadata = src.metadata().unwrap();
+	assert_eq!(metadata.title.as_deref(), Some("A Title"));
+	assert_eq!(metadata.artist.as_deref(), Some("An Artist"));
+	assert_eq!(metadata.album.as_deref(), Some("An Album"));
+	assert_eq!(metadata.track_number, Some(3));
+	assert!(metadata.tags.iter().any(|(_, value)| value == "A Comment"));
+}
+
+#[test]
+fn queues_report_their_current_item() {
+	let first = write_wav("first", &[(b"INAM", "First")]);
+	let second = write_wav("second", &[(b"INAM", "Second")]);
+
+	let mut queue = QueueSrc::new(SAMPLE_RATE as u64);
+	queue.push(FormattedStreamSource::open_path(first.clone()).unwrap().into());
+	queue.push(FormattedStreamSource::open_path(second.clone()).unwrap().into());
+	std::fs::remove_file(first).unwrap();
+	std::fs::remove_file(second).unwrap();
+
+	let mut cmp = CompositionState::new("test".into(), 1.0, ChannelLayout::Stereo);
+	let id = cmp.push_src_offset(queue.into(), 0);
+
+	let np = cmp.now_playing(id).unwrap();
+	assert_eq!(np.metadata.and_then(|m| m.title.as_deref()), Some("First"));
+	assert_eq!(np.queue_idx, Some(0));
+
+	let mut out = vec![0.0; 2 * 1000];
+	let src = &mut cmp.sources_mut()[0].src;
+	src.read_block(SAMPLE_RATE as u64 + 500, &mut out);
+
+	let np = cmp.now_playing(id).unwrap();
+	assert_eq!(np.metadata.and_then(|m| m.title.as_deref()), Some("Second"));
+	assert_eq!(np.queue_idx, Some(1));
+	assert_eq!(np.elapsed, 1500);
+	assert_eq!(np.remaining_secs(), Some((SAMPLE_RATE - 1500) as f64 / SAMPLE_RATE as f64));
+}
@@ -0,0 +1,45 @@
+use std::{sync::{Arc, RwLock}, time::Duration};
+
+use rad_compositor::{channel_layout::ChannelLayout, cmp_reg::{CompositionRegistry, NestError}, composition::CompositionState, source::generator::GeneratorSrc};
+
+fn registry_of(ids: &[&str]) -> CompositionRegistry {
+	let mut reg = CompositionRegistry::new();
+	for id in ids {
+		reg.push_composition(Arc::new(RwLock::new(CompositionState::new((*id).into(), 1.0, ChannelLayout::Mono))));
+	}
+
+	reg
+}
+
+#[test]
+fn nested_compositions_are_heard_in_the_outer_one() {
+	let mut reg = registry_of(&["outer", "inner"]);
+	let src = "sine:freq=440,rate=48000".parse::<GeneratorSrc>().unwrap();
+	reg.find_composition("inner").unwrap().write().unwrap().push_src_offset(src.into(), 0);
+
+	reg.nest("outer", "inner", 48000, 1024).unwrap();
+	let mut cursor = reg.get_cursor("outer", 48000, 1024).unwrap();
+
+	// The outer compositor doesn't wait for the inner one, so the first buffers may be silent until the inner one catches up
+	let is_heard = (0..200).any(|_| {
+		let buf = cursor.read_timeout(Duration::from_secs(5)).unwrap();
+		buf.iter().any(|v| 0.1 < v.abs())
+	});
+	assert!(is_heard);
+}
+
+#[test]
+fn compositions_cant_end_up_inside_of_themselves() {
+	let mut reg = registry_of(&["a", "b", "c"]);
+
+	assert_eq!(reg.nest("a", "a", 48000, 1024), Err(NestError::Cycle));
+
+	reg.nest("a", "b", 48000, 1024).unwrap();
+	reg.nest("b", "c", 48000, 1024).unwrap();
+	assert_eq!(reg.nest("b", "a", 48000, 1024), Err(NestError::Cycle));
+	assert_eq!(reg.nest("c", "a", 48000, 1024), Err(NestError::Cycle));
+
+	// The same composition may be nested in several others
+	assert!(reg.nest("a", "c", 48000, 1024).is_ok());
+	assert_eq!(reg.nest("a", "missing", 48000, 1024), Err(NestError::NotFound));
+}
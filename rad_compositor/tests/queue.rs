@@ -0,0 +1,242 @@
+use rad_compositor::{channel_layout::ChannelLayout, composition::{CompositionState, FinishPolicy, TSrcID}, compositor::{compute_frames, MixState}, resampler::ResampleQuality, source::{generator::GeneratorSrc, queue::{Crossfade, QueueSrc, RepeatMode}, BaseSource, Source, TFrameIdx, TSample}};
+
+const SAMPLE_RATE: TFrameIdx = 48000;
+/// The length of every item, 10 ms.
+const ITEM_LEN: TFrameIdx = 480;
+
+/// A mono sine lasting `ITEM_LEN` frames, told apart from the other items by its frequency.
+fn item(freq: u32) -> Source {
+	format!("sine:freq={},dur=0.01,rate=48000", freq).parse::<GeneratorSrc>().unwrap().into()
+}
+
+fn queue_of(freqs: &[u32]) -> QueueSrc {
+	let mut queue = QueueSrc::new(SAMPLE_RATE);
+	queue.set_channel_layout(ChannelLayout::Mono);
+	for freq in freqs {
+		queue.push(item(*freq));
+	}
+
+	queue
+}
+
+/// The frequencies of the items in the order they're in.
+fn freqs(queue: &QueueSrc) -> Vec<String> {
+	queue.sources().iter().map(|src| match src {
+		Source::Generator(g) => g.to_string().split(',').next().unwrap().to_owned(),
+		_ => unreachable!()
+	}).collect()
+}
+
+fn read(queue: &mut QueueSrc, frame_idx: TFrameIdx, frames: usize) -> (usize, Vec<TSample>) {
+	let mut out = vec![0.0; frames];
+	let read = queue.read_block(frame_idx, &mut out);
+
+	(read, out)
+}
+
+/// Whether `block` is what the item of `freq` outputs from its frame `from` on.
+fn is_item(block: &[TSample], freq: u32, from: TFrameIdx) -> bool {
+	let mut expected = vec![0.0; block.len()];
+	item(freq).read_block(from, &mut expected);
+
+	block.iter().zip(expected.iter()).all(|(b, e)| (b - e).abs() < 1e-6)
+}
+
+#[test]
+fn items_play_one_after_another() {
+	let mut queue = queue_of(&[100, 200, 300]);
+	assert_eq!(queue.duration(), Some(3 * ITEM_LEN));
+
+	let (read_frames, block) = read(&mut queue, 400, 200);
+	assert_eq!(read_frames, 200);
+	assert!(is_item(&block[..80], 100, 400));
+	assert!(is_item(&block[80..], 200, 0));
+	assert_eq!(queue.current(), Some(1));
+
+	let (read_frames, _) = read(&mut queue, 3 * ITEM_LEN - 10, 100);
+	assert_eq!(read_frames, 10);
+	assert_eq!(queue.current(), None);
+}
+
+#[test]
+fn skipping_starts_items_where_the_queue_is_playing() {
+	let mut queue = queue_of(&[100, 200, 300]);
+	read(&mut queue, 0, 100);
+
+	queue.skip_next();
+	assert_eq!(queue.current(), Some(1));
+	let (_, block) = read(&mut queue, 100, 100);
+	assert!(is_item(&block, 200, 0));
+
+	queue.skip_previous();
+	let (_, block) = read(&mut queue, 200, 100);
+	assert!(is_item(&block, 100, 0));
+
+	queue.jump_to(2);
+	let (_, block) = read(&mut queue, 300, 100);
+	assert!(is_item(&block, 300, 0));
+	assert_eq!(queue.current_position(), 100);
+
+	// Skipping past the last item ends the queue
+	queue.skip_next();
+	assert_eq!(read(&mut queue, 400, 100).0, 0);
+}
+
+#[test]
+fn repeating_never_ends() {
+	let mut queue = queue_of(&[100, 200]);
+
+	queue.set_repeat(RepeatMode::One);
+	assert_eq!(queue.duration(), Some(0));
+	let (_, block) = read(&mut queue, ITEM_LEN, 100);
+	assert!(is_item(&block, 100, 0));
+
+	// The repeated item started at `ITEM_LEN`, so the last item plays from `2 * ITEM_LEN` on
+	queue.set_repeat(RepeatMode::All);
+	let (read_frames, block) = read(&mut queue, 2 * ITEM_LEN, 100);
+	assert_eq!(read_frames, 100);
+	assert!(is_item(&block, 200, 0));
+	let (_, block) = read(&mut queue, 3 * ITEM_LEN, 100);
+	assert!(is_item(&block, 100, 0));
+}
+
+#[test]
+fn editing_keeps_the_current_item_playing() {
+	let mut queue = queue_of(&[100, 200, 300]);
+	read(&mut queue, ITEM_LEN, 100);
+	assert_eq!(queue.current(), Some(1));
+
+	assert!(queue.insert(0, item(400)).is_some());
+	assert_eq!(queue.current(), Some(2));
+	assert!(queue.insert(5, item(500)).is_none());
+
+	queue.move_item(2, 0);
+	assert_eq!(freqs(&queue), ["sine:freq=200", "sine:freq=400", "sine:freq=100", "sine:freq=300"]);
+	assert_eq!(queue.current(), Some(0));
+
+	queue.remove(3);
+	let (_, block) = read(&mut queue, ITEM_LEN + 100, 100);
+	assert!(is_item(&block, 200, 100));
+
+	// Removing the current item starts the one after it
+	queue.remove(0);
+	let (_, block) = read(&mut queue, ITEM_LEN + 200, 100);
+	assert!(is_item(&block, 400, 0));
+
+	queue.shuffle();
+	assert_eq!(queue.current(), Some(0));
+	assert_eq!(freqs(&queue)[0], "sine:freq=400");
+}
+
+#[test]
+fn items_added_to_an_ended_queue_start_right_away() {
+	let mut queue = queue_of(&[100]);
+	read(&mut queue, 0, 1000);
+	assert_eq!(queue.current(), None);
+
+	queue.push(item(200));
+	let (read_frames, block) = read(&mut queue, 1000, 100);
+	assert_eq!(read_frames, 100);
+	assert!(is_item(&block, 200, 0));
+}
+
+/// The frames in each block the compositors of the tests below mix.
+const BLOCK: usize = 256;
+
+/// A composition with the queue as its only source, held once it finishes.
+fn composition_of(queue: QueueSrc) -> (CompositionState, TSrcID) {
+	let mut cmp = CompositionState::new("queue".into(), 1.0, ChannelLayout::Mono);
+	let id = cmp.push_src_offset(queue.into(), 0);
+	cmp.find_src_mut(id).unwrap().composition_data.finish_policy = FinishPolicy::Hold;
+
+	(cmp, id)
+}
+
+fn queue_in(cmp: &mut CompositionState, id: TSrcID) -> &mut QueueSrc {
+	match cmp.find_src_mut(id).map(|s| &mut s.src) {
+		Some(Source::Queue(queue)) => queue,
+		_ => unreachable!()
+	}
+}
+
+/// Mixes the blocks of the composition starting from the block `from`, applying the finish policies after each of them like the compositors do.
+fn mix_blocks(cmp: &mut CompositionState, mix: &mut MixState, from: usize, blocks: usize) -> Vec<TSample> {
+	let mut out = vec![0.0; blocks * BLOCK];
+
+	for (i, block) in out.chunks_exact_mut(BLOCK).enumerate() {
+		compute_frames(cmp.sources_mut(), mix, ((from + i) * BLOCK) as TFrameIdx, block);
+		cmp.finish_sources(&mix.take_finished());
+	}
+
+	out
+}
+
+#[test]
+fn items_added_to_a_finished_queue_are_heard_from_their_start() {
+	let (mut cmp, id) = composition_of(queue_of(&[100]));
+	let mut mix = MixState::new(SAMPLE_RATE, ChannelLayout::Mono, 1.0, 0, ResampleQuality::default());
+
+	// The queue ends in the second block, after which the compositor stops reading it
+	mix_blocks(&mut cmp, &mut mix, 0, 4);
+	assert!(cmp.find_src(id).unwrap().composition_data.is_finished());
+
+	queue_in(&mut cmp, id).push(item(200));
+	cmp.forget_src_finished(id);
+
+	let block = mix_blocks(&mut cmp, &mut mix, 4, 1);
+	assert!(is_item(&block, 200, 0));
+	assert!(!cmp.find_src(id).unwrap().composition_data.is_finished());
+}
+
+#[test]
+fn drained_queues_wait_for_items() {
+	let (mut cmp, id) = composition_of(queue_of(&[100]));
+	let mut mix = MixState::new(SAMPLE_RATE, ChannelLayout::Mono, 1.0, 0, ResampleQuality::default());
+
+	mix_blocks(&mut cmp, &mut mix, 0, 1);
+	queue_in(&mut cmp, id).remove(0);
+
+	// An empty queue never finishes, as it may still be given items
+	assert!(mix_blocks(&mut cmp, &mut mix, 1, 3).iter().all(|v| *v == 0.0));
+	assert!(!cmp.find_src(id).unwrap().composition_data.is_finished());
+
+	queue_in(&mut cmp, id).push(item(200));
+	let block = mix_blocks(&mut cmp, &mut mix, 4, 1);
+	assert!(is_item(&block, 200, 0));
+}
+
+#[test]
+fn crossfades_overlap_the_items() {
+	let mut queue = queue_of(&[100, 200, 300]);
+	queue.set_crossfade(Some("linear:dur=0.002".parse().unwrap()));
+	let fade = 96;
+	assert_eq!(queue.duration(), Some(3 * ITEM_LEN - 2 * fade));
+
+	let fade_start = ITEM_LEN - fade;
+	let (_, block) = read(&mut queue, fade_start - 10, fade as usize + 20);
+	assert!(is_item(&block[..10], 100, fade_start - 10));
+	assert!(is_item(&block[fade as usize + 10..], 200, fade));
+
+	let (mut from, mut to) = (vec![0.0; 1], vec![0.0; 1]);
+	item(100).read_block(fade_start + fade / 2, &mut from);
+	item(200).read_block(fade / 2, &mut to);
+	assert!((block[10 + fade as usize / 2] - (from[0] + to[0]) / 2.0).abs() < 1e-6);
+	assert_eq!(queue.current(), Some(1));
+
+	// Skipping cuts right in
+	queue.skip_next();
+	let (_, block) = read(&mut queue, ITEM_LEN + 10, 100);
+	assert!(is_item(&block, 300, 0));
+}
+
+#[test]
+fn crossfade_specs_survive_being_listed() {
+	for spec in ["linear:dur=2", "equal-power:dur=0.5"] {
+		let crossfade = spec.parse::<Crossfade>().unwrap();
+		assert_eq!(crossfade.to_string().parse::<Crossfade>().unwrap(), crossfade, "{}", spec);
+	}
+
+	for spec in ["off", "linear:dur=0", "equal-power:dur=-1", "cubic:dur=1", "linear:len=1"] {
+		assert!(spec.parse::<Crossfade>().is_err(), "{}", spec);
+	}
+}
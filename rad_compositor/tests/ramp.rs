@@ -0,0 +1,106 @@
+use rad_compositor::{channel_layout::ChannelLayout, composition::CompositionState, compositor::{compute_frames, ramp::Ramp, MixState}, resampler::ResampleQuality, source::{generator::GeneratorSrc, BaseSource, TFrameIdx}};
+
+const SAMPLE_RATE: TFrameIdx = 48000;
+/// 10 ms, 480 frames.
+const RAMP_MS: u32 = 10;
+const RAMP_FRAMES: usize = 480;
+const SINE: &str = "sine:freq=997,rate=48000";
+
+fn sine_at(frame_idx: TFrameIdx, frames: usize) -> Vec<f32> {
+	let mut out = vec![0.0; frames];
+	SINE.parse::<GeneratorSrc>().unwrap().read_block(frame_idx, &mut out);
+
+	out
+}
+
+fn composition() -> (CompositionState, MixState) {
+	let mut cmp = CompositionState::new("ramp".into(), 1.0, ChannelLayout::Mono);
+	cmp.push_src_offset(SINE.parse::<GeneratorSrc>().unwrap().into(), 0);
+
+	(cmp, MixState::new(SAMPLE_RATE, ChannelLayout::Mono, 1.0, RAMP_MS, ResampleQuality::default()))
+}
+
+/// The gain each frame of `out` has been given relative to `reference`, where the reference is loud enough to tell.
+fn gains(out: &[f32], reference: &[f32]) -> Vec<(usize, f32)> {
+	out.iter().zip(reference).enumerate().filter(|(_, (_, r))| 0.1 < r.abs()).map(|(i, (o, r))| (i, o / r)).collect()
+}
+
+fn assert_close(a: f32, b: f32, what: usize) {
+	assert!((a - b).abs() < 1e-3, "{} != {} at {}", a, b, what);
+}
+
+#[test]
+fn ramps_reach_their_targets_linearly() {
+	let mut ramp = Ramp::new(1.0);
+	assert!(ramp.is_settled());
+
+	ramp.set_target(0.0, 4);
+	assert_eq!(ramp.frames_left(), 4);
+	assert_eq!([ramp.advance(), ramp.advance()], [0.75, 0.5]);
+
+	// Setting the same target again doesn't restart the ramp
+	ramp.set_target(0.0, 4);
+	assert_eq!(ramp.frames_left(), 2);
+	assert_eq!([ramp.advance(), ramp.advance(), ramp.advance()], [0.25, 0.0, 0.0]);
+	assert!(ramp.is_settled());
+	assert_eq!(ramp.frames_left(), 0);
+}
+
+#[test]
+fn gain_changes_are_ramped() {
+	let (mut cmp, mut mix) = composition();
+	let mut out = vec![0.0; 1024];
+	compute_frames(cmp.sources_mut(), &mut mix, 0, &mut out);
+
+	let id = cmp.sources()[0].id();
+	cmp.find_src_mut(id).unwrap().composition_data.amplification = 0.5;
+	compute_frames(cmp.sources_mut(), &mut mix, 1024, &mut out);
+
+	for (i, gain) in gains(&out, &sine_at(1024, 1024)) {
+		let expected = if i < RAMP_FRAMES { 1.0 - 0.5 * (i + 1) as f32 / RAMP_FRAMES as f32 } else { 0.5 };
+		assert_close(gain, expected, i);
+	}
+}
+
+#[test]
+fn pauses_fade_out_and_resumes_fade_in() {
+	let (mut cmp, mut mix) = composition();
+	let mut out = vec![0.0; 1024];
+
+	mix.fade_out();
+	compute_frames(cmp.sources_mut(), &mut mix, 0, &mut out);
+	assert!(mix.is_faded_out());
+
+	for (i, gain) in gains(&out, &sine_at(0, 1024)) {
+		assert_close(gain, (1.0 - (i + 1) as f32 / RAMP_FRAMES as f32).max(0.0), i);
+	}
+
+	mix.fade_in();
+	compute_frames(cmp.sources_mut(), &mut mix, 1024, &mut out);
+
+	for (i, gain) in gains(&out, &sine_at(1024, 1024)) {
+		assert_close(gain, ((i + 1) as f32 / RAMP_FRAMES as f32).min(1.0), i);
+	}
+}
+
+#[test]
+fn seeks_fade_out_of_the_old_position_into_the_new_one() {
+	let (mut cmp, mut mix) = composition();
+	let mut out = vec![0.0; 2048];
+	compute_frames(cmp.sources_mut(), &mut mix, 0, &mut out[..1024]);
+
+	// Seeking from frame 1024 to frame 20000
+	mix.begin_seek(1024, 20000);
+	compute_frames(cmp.sources_mut(), &mut mix, 20000, &mut out);
+
+	// The old position fades out first
+	for (i, gain) in gains(&out[..RAMP_FRAMES], &sine_at(1024, RAMP_FRAMES)) {
+		assert_close(gain, 1.0 - (i + 1) as f32 / RAMP_FRAMES as f32, i);
+	}
+
+	// Then the new one fades in, from where the fade-out left it
+	let fade_in = &out[RAMP_FRAMES..];
+	for (i, gain) in gains(fade_in, &sine_at(20000 + RAMP_FRAMES as TFrameIdx, fade_in.len())) {
+		assert_close(gain, ((i + 1) as f32 / RAMP_FRAMES as f32).min(1.0), i);
+	}
+}
@@ -0,0 +1,159 @@
+use std::f64::consts::PI;
+
+use rad_compositor::{resampler::{ResampleQuality, Resampler}, source::{BaseSource, TFrameIdx, TSample}};
+
+/// A mono sine wave which can be read at any frame.
+struct SineSrc {
+	sample_rate: TFrameIdx,
+	freq: f64,
+	amplitude: f64,
+}
+
+impl BaseSource for SineSrc {
+	fn sample_rate(&self) -> TFrameIdx { self.sample_rate }
+	fn duration(&self) -> Option<TFrameIdx> { Some(0) }
+	fn current_duration_frames(&self) -> TFrameIdx { 0 }
+	fn channels(&self) -> u8 { 1 }
+
+	fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<TSample>> {
+		let t = frame_idx as f64 / self.sample_rate as f64;
+		Some(vec![(self.amplitude * (2.0 * PI * self.freq * t).sin()) as TSample])
+	}
+}
+
+/// Resamples a sine wave and returns the output, skipping the first frames so that the kernel is fully inside the signal.
+fn resample_sine(quality: ResampleQuality, src_rate: TFrameIdx, into_rate: TFrameIdx, freq: f64, frames: usize) -> Vec<f64> {
+	let mut src = SineSrc { sample_rate: src_rate, freq, amplitude: 0.5 };
+	let mut resampler = Resampler::new(quality);
+	let skip = 256;
+
+	(skip..skip + frames as TFrameIdx)
+		.map(|i| resampler.resample(&mut src, into_rate, i, 0).unwrap()[0] as f64)
+		.collect()
+}
+
+/// Amplitude of the `freq` component of `signal` using the Goertzel algorithm over a Hann window.
+fn tone_amplitude(signal: &[f64], sample_rate: TFrameIdx, freq: f64) -> f64 {
+	let n = signal.len();
+	let w = 2.0 * PI * freq / sample_rate as f64;
+	let coeff = 2.0 * w.cos();
+	let (mut s1, mut s2) = (0.0, 0.0);
+	let mut window_sum = 0.0;
+
+	for (i, v) in signal.iter().enumerate() {
+		let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / (n - 1) as f64).cos();
+		window_sum += window;
+
+		let s0 = v * window + coeff * s1 - s2;
+		s2 = s1;
+		s1 = s0;
+	}
+
+	let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+	2.0 * power.sqrt() / window_sum
+}
+
+fn to_db(v: f64) -> f64 {
+	20.0 * v.log10()
+}
+
+#[test]
+fn sinc_passband_is_flat() {
+	for quality in [ResampleQuality::SincFast, ResampleQuality::SincBest] {
+		for freq in [100.0, 1000.0, 5000.0, 10000.0, 15000.0] {
+			let out = resample_sine(quality, 44100, 48000, freq, 8192);
+			let gain_db = to_db(tone_amplitude(&out, 48000, freq) / 0.5);
+
+			assert!(gain_db.abs() < 0.1, "{} at {} Hz has a gain of {:.3} dB", quality.name(), freq, gain_db);
+		}
+	}
+}
+
+#[test]
+fn sinc_suppresses_images_when_upsampling() {
+	// A 20 kHz tone at 44.1 kHz has an image at 24.1 kHz which folds back to 23.9 kHz in 48 kHz.
+	let image_freq = 48000.0 - (44100.0 - 20000.0);
+
+	let linear = resample_sine(ResampleQuality::Linear, 44100, 48000, 20000.0, 8192);
+	let linear_image_db = to_db(tone_amplitude(&linear, 48000, image_freq) / 0.5);
+
+	for (quality, max_image_db) in [(ResampleQuality::SincFast, -40.0), (ResampleQuality::SincBest, -60.0)] {
+		let out = resample_sine(quality, 44100, 48000, 20000.0, 8192);
+		let image_db = to_db(tone_amplitude(&out, 48000, image_freq) / 0.5);
+
+		assert!(image_db < max_image_db, "{} leaves an image at {:.1} dB", quality.name(), image_db);
+		assert!(image_db < linear_image_db - 20.0, "{} isn't much better than linear ({:.1} dB vs {:.1} dB)", quality.name(), image_db, linear_image_db);
+	}
+}
+
+#[test]
+fn sinc_suppresses_aliasing_when_downsampling() {
+	// A 30 kHz tone at 96 kHz is above the Nyquist frequency of 48 kHz and would alias to 18 kHz.
+	let alias_freq = 48000.0 - 30000.0;
+
+	let linear = resample_sine(ResampleQuality::Linear, 96000, 48000, 30000.0, 8192);
+	let linear_alias_db = to_db(tone_amplitude(&linear, 48000, alias_freq) / 0.5);
+
+	for (quality, max_alias_db) in [(ResampleQuality::SincFast, -50.0), (ResampleQuality::SincBest, -80.0)] {
+		let out = resample_sine(quality, 96000, 48000, 30000.0, 8192);
+		let alias_db = to_db(tone_amplitude(&out, 48000, alias_freq) / 0.5);
+
+		assert!(alias_db < max_alias_db, "{} aliases at {:.1} dB", quality.name(), alias_db);
+		assert!(alias_db < linear_alias_db - 20.0, "{} isn't much better than linear ({:.1} dB vs {:.1} dB)", quality.name(), alias_db, linear_alias_db);
+	}
+}
+
+#[test]
+fn same_rate_keeps_the_signal() {
+	for quality in [ResampleQuality::Linear, ResampleQuality::Cubic, ResampleQuality::SincFast, ResampleQuality::SincBest] {
+		let mut src = SineSrc { sample_rate: 48000, freq: 1000.0, amplitude: 0.5 };
+		let mut resampler = Resampler::new(quality);
+
+		for i in 256..1256 {
+			let expected = src.get_by_frame_i(i).unwrap()[0];
+			let got = resampler.resample(&mut src, 48000, i, 0).unwrap()[0];
+
+			assert!((expected - got).abs() < 1e-3, "{} changed frame {} from {} to {}", quality.name(), i, expected, got);
+		}
+	}
+}
+
+/// A mono source of a constant value ending after `frames` frames.
+struct ConstSrc {
+	frames: TFrameIdx,
+}
+
+impl BaseSource for ConstSrc {
+	fn sample_rate(&self) -> TFrameIdx { 44100 }
+	fn duration(&self) -> Option<TFrameIdx> { Some(self.frames) }
+	fn current_duration_frames(&self) -> TFrameIdx { self.frames }
+	fn channels(&self) -> u8 { 1 }
+
+	fn get_by_frame_i(&mut self, frame_idx: TFrameIdx) -> Option<Vec<TSample>> {
+		(frame_idx < self.frames).then(|| vec![0.5])
+	}
+}
+
+#[test]
+fn the_edges_of_sources_keep_their_level() {
+	for quality in [ResampleQuality::Linear, ResampleQuality::Cubic, ResampleQuality::SincFast, ResampleQuality::SincBest] {
+		let mut src = ConstSrc { frames: 1000 };
+		let mut resampler = Resampler::new(quality);
+
+		let mut out = vec![0.0; 2000];
+		let read = resampler.resample_block(&mut src, 48000, 0, 0, &mut out);
+		assert!((1085..=1089).contains(&read), "{} read {} frames", quality.name(), read);
+
+		for (i, v) in out[..read].iter().enumerate() {
+			assert!((v - 0.5).abs() < 1e-3, "{} gave {} at {}", quality.name(), v, i);
+		}
+	}
+}
+
+#[test]
+fn frames_before_the_start_of_sources_are_clamped() {
+	let mut src = ConstSrc { frames: 1000 };
+	let mut resampler = Resampler::new(ResampleQuality::SincFast);
+
+	assert_eq!(resampler.resample(&mut src, 48000, 0, 10), Some(vec![0.5]));
+}
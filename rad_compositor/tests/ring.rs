@@ -0,0 +1,85 @@
+use std::{sync::{Arc, RwLock}, thread, time::Duration};
+
+use rad_compositor::{channel_layout::ChannelLayout, cmp_reg::CompositionRegistry, composition::CompositionState, compositor::ring::{ReadError, RingWriter}};
+
+/// A buffer of 4 samples all holding `v`.
+fn buf_of(v: f32) -> [f32; 4] {
+	[v; 4]
+}
+
+#[test]
+fn cursors_read_from_the_latest_buffer_on() {
+	let mut writer = RingWriter::new(4, 4);
+	writer.push(&buf_of(1.0));
+	writer.push(&buf_of(2.0));
+
+	let mut cursor = writer.ring().cursor();
+	let mut other = cursor.clone();
+	assert_eq!(writer.ring().readers(), 2);
+
+	assert_eq!(cursor.try_read(), Ok(&buf_of(2.0)[..]));
+	assert_eq!(cursor.try_read(), Err(ReadError::Empty));
+	assert_eq!(cursor.read_timeout(Duration::from_millis(10)), Err(ReadError::Timeout));
+
+	// Each cursor reads at its own pace
+	writer.push(&buf_of(3.0)[..2]);
+	assert_eq!(cursor.try_read(), Ok(&buf_of(3.0)[..2]));
+	assert_eq!(other.try_read(), Ok(&buf_of(2.0)[..]));
+
+	other.skip_to_head();
+	assert_eq!(other.try_read(), Err(ReadError::Empty));
+
+	drop(other);
+	assert_eq!(writer.ring().readers(), 1);
+}
+
+#[test]
+fn slow_readers_are_told_about_what_they_missed() {
+	let mut writer = RingWriter::new(4, 4);
+	writer.push(&buf_of(0.0));
+	let mut cursor = writer.ring().cursor();
+
+	for i in 1..10 {
+		writer.push(&buf_of(i as f32));
+	}
+
+	assert_eq!(cursor.try_read(), Err(ReadError::Overrun { missed: 9 }));
+	// The cursor goes on from the latest buffer
+	assert_eq!(cursor.try_read(), Ok(&buf_of(9.0)[..]));
+}
+
+#[test]
+fn waiting_readers_are_woken_up() {
+	let mut writer = RingWriter::new(4, 4);
+	let mut cursor = writer.ring().cursor();
+
+	let producer = thread::spawn(move || {
+		thread::sleep(Duration::from_millis(20));
+		writer.push(&buf_of(1.0));
+		thread::sleep(Duration::from_millis(20));
+	});
+
+	assert_eq!(cursor.read(), Ok(&buf_of(1.0)[..]));
+
+	// The ring is closed once the writer is gone
+	producer.join().unwrap();
+	assert_eq!(cursor.read(), Err(ReadError::Closed));
+	assert_eq!(cursor.read_timeout(Duration::from_secs(5)), Err(ReadError::Closed));
+}
+
+#[test]
+fn compositors_are_shared_by_readers_of_the_same_block_size() {
+	let mut reg = CompositionRegistry::new();
+	reg.push_composition(Arc::new(RwLock::new(CompositionState::new("ring".into(), 1.0, ChannelLayout::Stereo))));
+
+	let mut small = reg.get_cursor("ring", 48000, 256).unwrap();
+	let same = reg.get_cursor("ring", 48000, 256).unwrap();
+	// Buffers hold whole frames
+	let mut odd = reg.get_cursor("ring", 48000, 1001).unwrap();
+
+	assert!(Arc::ptr_eq(small.ring(), same.ring()));
+	assert!(!Arc::ptr_eq(small.ring(), odd.ring()));
+	assert_eq!(small.read_timeout(Duration::from_secs(5)).unwrap().len(), 256);
+	assert_eq!(odd.read_timeout(Duration::from_secs(5)).unwrap().len(), 1000);
+	assert!(reg.get_cursor("missing", 48000, 256).is_none());
+}
@@ -0,0 +1,115 @@
+#[cfg(any(feature = "flac", feature = "wav", feature = "alac", feature = "mp3"))]
+mod common;
+
+#[cfg(any(feature = "flac", feature = "wav", feature = "alac", feature = "mp3"))]
+use common::fixture;
+#[cfg(any(feature = "flac", feature = "wav", feature = "alac"))]
+use common::tone_sample;
+use rad_compositor::source::formatted::seek::{PacketIndex, SeekPrecision};
+#[cfg(any(feature = "flac", feature = "wav", feature = "alac", feature = "mp3"))]
+use rad_compositor::source::{formatted::FormattedStreamSource, BaseSource};
+
+/// Opens the fixture decoding on the reading thread, so that every read decodes (and seeks) right away.
+#[cfg(any(feature = "flac", feature = "wav", feature = "alac", feature = "mp3"))]
+fn open(name: &str, precision: SeekPrecision) -> FormattedStreamSource {
+	let src = FormattedStreamSource::open_path(fixture(name)).unwrap();
+	src.set_decode_ahead_ms(0);
+	assert_eq!(src.seek_precision(), SeekPrecision::Accurate);
+
+	src.set_seek_precision(precision);
+	src
+}
+
+/// Reads 1000 frames at each of the starts and checks that they're the tone.
+#[cfg(any(feature = "flac", feature = "wav", feature = "alac"))]
+fn assert_tone_at(src: &mut FormattedStreamSource, starts: &[usize]) {
+	let mut out = vec![0.0; 1000 * 2];
+
+	for &start in starts {
+		assert_eq!(src.read_block(start as u64, &mut out), 1000, "from {}", start);
+
+		for (i, frame) in out.chunks_exact(2).enumerate() {
+			assert_eq!(frame, [tone_sample(start + i), -tone_sample(start + i)], "at {}", start + i);
+		}
+	}
+}
+
+#[test]
+fn the_index_keeps_packets_of_a_length_in_runs() {
+	let mut index = PacketIndex::default();
+	assert!(index.is_empty());
+
+	// Read from the middle first, then from the start up to it
+	for n in (10..20).chain(0..10) {
+		index.insert(n * 1152, 1152);
+	}
+	// A shorter last packet, and the same one read again
+	index.insert(20 * 1152, 100);
+	index.insert(20 * 1152, 100);
+
+	assert_eq!(index.len(), 21);
+	assert_eq!(index.run_count(), 2);
+
+	assert_eq!(index.packet_of(0), Some((0, 1152)));
+	assert_eq!(index.packet_of(12 * 1152 + 5), Some((12 * 1152, 1152)));
+	assert_eq!(index.packet_of(20 * 1152 + 99), Some((20 * 1152, 100)));
+	assert_eq!(index.packet_of(20 * 1152 + 100), None);
+
+	assert_eq!(index.packets_between(0, 0), Some(0));
+	assert_eq!(index.packets_between(5, 3 * 1152), Some(3));
+	assert_eq!(index.packets_between(0, 20 * 1152 + 50), Some(20));
+	assert_eq!(index.packets_between(0, 21 * 1152), None);
+
+	// Packets that haven't been read leave a hole
+	index.insert(30 * 1152, 1152);
+	assert_eq!(index.run_count(), 3);
+	assert_eq!(index.packet_of(25 * 1152), None);
+	assert_eq!(index.packets_between(0, 30 * 1152), None);
+}
+
+#[cfg(feature = "flac")]
+#[test]
+fn frames_a_little_ahead_are_decoded_to_without_seeking() {
+	// The packets of `tone.flac` are 1152 frames long
+	let mut src = open("tone.flac", SeekPrecision::Accurate);
+	assert_tone_at(&mut src, &[0, 2000, 500, 3500]);
+}
+
+#[cfg(feature = "wav")]
+#[test]
+fn seeks_land_on_the_frame() {
+	for precision in [SeekPrecision::Accurate, SeekPrecision::Coarse] {
+		let mut src = open("tone.wav", precision);
+		assert_tone_at(&mut src, &[3000, 0, 1500]);
+	}
+}
+
+#[cfg(feature = "alac")]
+#[test]
+fn seeks_land_on_the_frame_within_a_packet() {
+	// The first packet of `tone_alac.m4a` is 4096 frames long, the second one holds the rest
+	for precision in [SeekPrecision::Accurate, SeekPrecision::Coarse] {
+		let mut src = open("tone_alac.m4a", precision);
+		assert_tone_at(&mut src, &[3600, 100, 2500, 0]);
+	}
+}
+
+#[cfg(feature = "mp3")]
+#[test]
+fn coarse_seeks_read_the_whole_block() {
+	let mut src = open("silence.mp3", SeekPrecision::Coarse);
+
+	let mut out = vec![1.0; 1024 * 2];
+	for start in [18000, 0, 9000, 4000] {
+		assert_eq!(src.read_block(start, &mut out), 1024, "from {}", start);
+		assert!(out.iter().all(|v| v.abs() < 1e-6), "from {}", start);
+	}
+}
+
+#[test]
+fn precisions_are_parsed() {
+	assert_eq!("accurate".parse(), Ok(SeekPrecision::Accurate));
+	assert_eq!("coarse".parse(), Ok(SeekPrecision::Coarse));
+	assert_eq!("exact".parse::<SeekPrecision>(), Err(()));
+	assert_eq!(SeekPrecision::Coarse.to_string(), "coarse");
+}
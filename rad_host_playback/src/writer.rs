@@ -1,23 +1,26 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{sync::{atomic::AtomicBool, Arc}, time::Duration};
 
-use rad_compositor::{compositor::CompositionBufferNode, source::TSample};
+use rad_compositor::{compositor::ring::{ReadError, RingCursor}, source::TSample};
+
+/// How long to wait for the next buffer before checking whether the playback has been closed.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
 
 pub struct Writer {
 	channels: u16,
     sample_rate: u32,
 	is_closed: Arc<AtomicBool>,
 	buf_idx: usize,
-	cmp_node: Arc<CompositionBufferNode<1024>>
+	cursor: RingCursor
 }
 
 impl Writer {
-	pub fn new(sample_rate: u32, channels: u16, cmp_node: Arc<CompositionBufferNode<1024>>, is_closed: Arc<AtomicBool>) -> Self {
+	pub fn new(sample_rate: u32, channels: u16, cursor: RingCursor, is_closed: Arc<AtomicBool>) -> Self {
 		Writer {
 			channels,
 			sample_rate,
 			is_closed,
 			buf_idx: 0,
-			cmp_node,
+			cursor,
 		}
 	}
 }
@@ -33,15 +36,21 @@ impl Iterator for Writer {
 	type Item = TSample;
 
 	fn next(&mut self) -> Option<TSample> {
-		if self.cmp_node.buf().len() == self.buf_idx {
-			self.buf_idx = 0;
-			self.cmp_node = self.cmp_node.next();
+		use std::sync::atomic::Ordering;
+
+		while self.cursor.buf().len() == self.buf_idx {
+			if self.is_closed.load(Ordering::Relaxed) { return None; }
+
+			match self.cursor.read_timeout(READ_TIMEOUT) {
+				Ok(_) => self.buf_idx = 0,
+				Err(ReadError::Closed) => return None,
+				Err(_) => {}
+			}
 		}
 
-		use std::sync::atomic::Ordering;
 		if self.is_closed.load(Ordering::Relaxed) { return None; }
 
-		let res = self.cmp_node.buf()[self.buf_idx];
+		let res = self.cursor.buf()[self.buf_idx];
 		self.buf_idx += 1;
 
 		Some(res)
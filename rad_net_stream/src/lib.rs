@@ -5,5 +5,5 @@ mod utils;
 mod udp;
 mod simple_http;
 
-pub use udp::init_udp_adapter;
+pub use udp::{init_udp_adapter, MAX_UDP_BLOCK_SIZE};
 pub use simple_http::init_simple_http_adapter;
\ No newline at end of file
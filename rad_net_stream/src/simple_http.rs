@@ -1,6 +1,6 @@
-use std::{io::{BufRead, Read, Write}, net::{SocketAddr, TcpListener, TcpStream}, slice::from_raw_parts, sync::{atomic::AtomicBool, Arc, Mutex}, thread};
+use std::{io::{BufRead, Read, Write}, mem::size_of, net::{SocketAddr, TcpListener, TcpStream}, sync::{atomic::AtomicBool, Arc, Mutex}, thread, time::Duration};
 
-use rad_compositor::{adapter::AdapterHandle, cmp_reg::CompositionRegistry, compositor::CompositionBufferNode, source::TFrameIdx};
+use rad_compositor::{adapter::AdapterHandle, cmp_reg::CompositionRegistry, compositor::ring::{ReadError, RingCursor}, source::TFrameIdx};
 use crate::utils::wav::gen_wav_header;
 
 fn net_err_log(err: std::io::Error) { log::debug!("[ap.simple_http] Network error occurred: {err:?}"); }
@@ -14,17 +14,16 @@ macro_rules! net_err_handle {
 
 const AP_TYPE: &str = "net-simple-http";
 
-/// Size of each buffer in bytes
-const BUF_SIZE: usize = 2048;
-const BUF_SIZE_HEX: &str = "1000"; // format!("{:x}", BUF_SIZE)
 
-type TCmpNode = Arc<CompositionBufferNode<1024>>;
-type TCmpReg = Arc<Mutex<CompositionRegistry<1024>>>;
+type TCmpReg = Arc<Mutex<CompositionRegistry>>;
+
+/// How long to wait for the next buffer of the composition before waiting again, as paused compositions don't compute anything.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
 
 const HTTP_INITIAL_MSG: &str = "HTTP/1.1 200 OK\r\nContent-Type: audio/wav\r\nConnection: keep-alive\r\nKeep-Alive: timeout=5\r\nTransfer-Encoding: chunked\r\n\r\n";
 
 // TODO: Optimize
-pub fn stream_as_wav(mut cmp_node: TCmpNode, sample_rate: TFrameIdx, channels: u8, mut st: TcpStream) {
+pub fn stream_as_wav(mut cursor: RingCursor, sample_rate: TFrameIdx, channels: u8, mut st: TcpStream) {
     // Streams the data using http chunked streaming method
     // Reference: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Transfer-Encoding
     // Reference: Analyzing the same thing done in https://github.com/Arman-sm/Atmosphere via wireshark 
@@ -37,8 +36,8 @@ pub fn stream_as_wav(mut cmp_node: TCmpNode, sample_rate: TFrameIdx, channels: u
     let mut buf = Vec::new();
 
     buf.reserve_exact(
-        HTTP_INITIAL_MSG.as_bytes().len() + 
-        buf_len_hex.as_bytes().len() + 
+        HTTP_INITIAL_MSG.len() + 
+        buf_len_hex.len() + 
         wav_header.len() +
         2 // The '\r\n' at the ent
     );
@@ -46,27 +45,33 @@ pub fn stream_as_wav(mut cmp_node: TCmpNode, sample_rate: TFrameIdx, channels: u
     buf.extend_from_slice(HTTP_INITIAL_MSG.as_bytes());
     buf.extend_from_slice(buf_len_hex.as_bytes());
     buf.append(&mut wav_header);
-    buf.push('\r' as u8);
-    buf.push('\n' as u8);
+    buf.push(b'\r');
+    buf.push(b'\n');
 
     st.write_all(&buf).unwrap();
     st.flush().unwrap();
 
     // Sending the actual audio
     loop {
-        let buf_f32: &[f32; 1024] = cmp_node.buf();
-
-        let mut audio_i16 = [0i16; BUF_SIZE / 2];
-        for (i, v) in buf_f32.iter().enumerate() {
-            audio_i16[i] = (v.min(1.0).max(-1.0) * i16::MAX as f32) as i16;
+        let buf_f32 = match cursor.read_timeout(READ_TIMEOUT) {
+            Ok(buf) => buf,
+            Err(ReadError::Closed) => {
+                // Ending the chunked response as no more audio is coming
+                net_err_handle!(st.write_all(b"0\r\n\r\n"));
+                return;
+            },
+            Err(_) => continue
+        };
+        // Size of the audio in bytes
+        let audio_size = buf_f32.len() * size_of::<i16>();
+        let audio_size_hex = format!("{:x}\r\n", audio_size);
+
+        let mut buf = Vec::with_capacity(audio_size + audio_size_hex.len() + "\r\n".len());
+
+        buf.extend_from_slice(audio_size_hex.as_bytes());
+        for v in buf_f32.iter() {
+            buf.extend_from_slice(&((v.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
         }
-
-        let audio_bytes = unsafe { from_raw_parts(audio_i16.as_ptr() as *const u8, BUF_SIZE) };
-        
-        let mut buf = Vec::with_capacity(BUF_SIZE + BUF_SIZE_HEX.as_bytes().len() + "\r\n".as_bytes().len() * 2);
-
-        buf.extend_from_slice(format!("{:x}\r\n", BUF_SIZE).as_bytes());
-        buf.extend_from_slice(audio_bytes);
         buf.extend_from_slice("\r\n".as_bytes());
         
         if let Err(e) = st.write_all(&buf) {
@@ -75,14 +80,12 @@ pub fn stream_as_wav(mut cmp_node: TCmpNode, sample_rate: TFrameIdx, channels: u
         }
 
         st.flush().unwrap();
-
-        cmp_node = cmp_node.next();
     }
 }
 
 const HTTP_200_RESPONSE: &str = "HTTP/1.1 200 OK\r\n";
 const HTTP_400_RESPONSE: &str = "HTTP/1.1 400 Bad Request\r\n";
-fn handle_conn(mut st: TcpStream, sample_rate: TFrameIdx, channels: u8, cmp_id: &str, cmp_reg: TCmpReg) {
+fn handle_conn(mut st: TcpStream, sample_rate: TFrameIdx, channels: u8, block_size: usize, cmp_id: &str, cmp_reg: TCmpReg) {
     macro_rules! static_file_serve {
         ($path:expr, $mime_type:expr) => {{
             log::debug!("[ap.simple_http] Sending '{}'.", $path);
@@ -96,11 +99,11 @@ fn handle_conn(mut st: TcpStream, sample_rate: TFrameIdx, channels: u8, cmp_id:
     }
 
     let mut buf = [0u8; 4096];
-    st.read(&mut buf).unwrap();
+    let read_len = st.read(&mut buf).unwrap();
     
     log::debug!("[ap.simple_http] Parsing incoming http request.");
 
-    let req_line = match buf.lines().next() {
+    let req_line = match buf[..read_len].lines().next() {
         Some(Ok(line)) => line,
         _ => {
             log::debug!("[ap.simple_http] Couldn't read the request line of the request.");
@@ -124,13 +127,12 @@ fn handle_conn(mut st: TcpStream, sample_rate: TFrameIdx, channels: u8, cmp_id:
         "/audio.wav" => {
             log::debug!("[ap.simple_http] Sending the audio as wav.");
 
-            let mut node = 
-                cmp_reg.lock().unwrap().get_active_buf(cmp_id, sample_rate)
-                    .expect(&format!("[ap.simple_http] Wasn't able to obtain active buffer for composition '{}'.", cmp_id));
-            CompositionBufferNode::set_to_live(&mut node, sample_rate, channels);
+            let cursor = 
+                cmp_reg.lock().unwrap().get_cursor(cmp_id, sample_rate, block_size)
+                    .unwrap_or_else(|| panic!("[ap.simple_http] Wasn't able to obtain a cursor for composition '{}'.", cmp_id));
             
             thread::spawn(move || {
-                stream_as_wav(node, sample_rate, channels, st);
+                stream_as_wav(cursor, sample_rate, channels, st);
             });
         },
         "/"            => { static_file_serve!("./simple_http_static/index.html", "text/html"); },
@@ -143,12 +145,11 @@ fn handle_conn(mut st: TcpStream, sample_rate: TFrameIdx, channels: u8, cmp_id:
             // TODO: Limit the size of the printed log in case req_path is too long.
             log::debug!("[ap.simple_http] The requested URL '{}' is invalid.", req_path);
             net_err_handle!(st.write_all(HTTP_400_RESPONSE.as_bytes()));
-            return;
         }
     }
 }
 
-pub fn init_simple_http_adapter(id: String, sample_rate: TFrameIdx, channels: u8, bind_addr: SocketAddr, cmp_id: String, cmp_reg: TCmpReg) -> AdapterHandle {
+pub fn init_simple_http_adapter(id: String, sample_rate: TFrameIdx, channels: u8, block_size: usize, bind_addr: SocketAddr, cmp_id: String, cmp_reg: TCmpReg) -> AdapterHandle {
     let socket = TcpListener::bind(bind_addr).unwrap();
     let status = Arc::new(Mutex::new("Established".to_owned()));
     let is_closed = Arc::new(AtomicBool::new(false));
@@ -163,7 +164,7 @@ pub fn init_simple_http_adapter(id: String, sample_rate: TFrameIdx, channels: u8
             if is_closed.load(Ordering::Relaxed) { return; }
             match incoming {
                 Ok(st) => {
-                    handle_conn(st, sample_rate, channels, &cmp_id, cmp_reg.clone());
+                    handle_conn(st, sample_rate, channels, block_size, &cmp_id, cmp_reg.clone());
                 },
                 Err(e) => {
                     log::error!("[ap.simple_http] Connection failure happened in adapter '{_id}' with error '{:?}'.", e);
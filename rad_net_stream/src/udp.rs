@@ -1,36 +1,54 @@
-use std::{mem::size_of, net::{SocketAddr, UdpSocket}, slice::from_raw_parts, sync::{atomic::AtomicBool, Arc, Mutex}, thread::{self}};
+use std::{mem::{size_of, size_of_val}, net::{SocketAddr, UdpSocket}, slice::from_raw_parts, sync::{atomic::AtomicBool, Arc, Mutex}, thread::{self}, time::Duration};
 
-use rad_compositor::{adapter::AdapterHandle, compositor::CompositionBufferNode};
+use rad_compositor::{adapter::AdapterHandle, compositor::ring::{ReadError, RingCursor}};
 
-// TODO: Allow customization of the buffer size, channel count, and sample rate.
+// TODO: Allow customization of the channel count and sample rate.
 
-/// Size of each buffer in bytes
-const BUF_SIZE: usize  = 4096;
 // const CHANNELS: usize  = 2;
 
-pub fn init_udp_adapter(id: String, bind_addr: SocketAddr, dest_addr: SocketAddr, mut cmp_node: Arc<CompositionBufferNode<{BUF_SIZE / size_of::<f32>()}>>) -> AdapterHandle {
+/// The largest payload of a UDP datagram over IPv4.
+const MAX_DATAGRAM_SIZE: usize = 65507;
+/// The largest block size (in samples) the adapter can send, as every buffer is sent as a datagram of its own.
+pub const MAX_UDP_BLOCK_SIZE: usize = MAX_DATAGRAM_SIZE / size_of::<f32>();
+
+/// How long to wait for the next buffer before checking whether the adapter has been closed.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Sends each buffer read by the cursor to `dest_addr` as a datagram of its own.
+///
+/// Caution: The block size of the cursor has to be at most `MAX_UDP_BLOCK_SIZE`, which is checked by the configuration beforehand.
+pub fn init_udp_adapter(id: String, bind_addr: SocketAddr, dest_addr: SocketAddr, mut cursor: RingCursor) -> AdapterHandle {
+    assert!(cursor.ring().block_size() <= MAX_UDP_BLOCK_SIZE, "The buffers of the compositor don't fit in UDP datagrams.");
+
     let socket = UdpSocket::bind(bind_addr).unwrap();
     let status = Arc::new(Mutex::new("Established".to_owned()));
     let is_closed = Arc::new(AtomicBool::new(false));
 
     let _is_closed = is_closed.clone();
+    let _status = status.clone();
     thread::Builder::new().name(format!("ap-udp-{id}")).spawn(move || loop {
         use std::sync::atomic::Ordering;
 
         if is_closed.load(Ordering::Relaxed) { return; }
 
-        let buf: &[f32; BUF_SIZE / size_of::<f32>()] = cmp_node.buf();
-    
+        let buf = match cursor.read_timeout(READ_TIMEOUT) {
+            Ok(buf) => buf,
+            Err(ReadError::Closed) => {
+                *_status.lock().unwrap() = "Compositor stopped".to_owned();
+                return;
+            },
+            // Paused compositions don't compute anything and the readers that fell behind go on from the latest buffer
+            Err(_) => continue
+        };
+
         let socket_res = socket.send_to(
-            unsafe { from_raw_parts(buf.as_ptr() as *const _, buf.len() * 4) },
+            unsafe { from_raw_parts(buf.as_ptr() as *const _, size_of_val(buf)) },
             dest_addr
         );
         
         if let Err(e) = socket_res {
             eprintln!("UDP send failed: {:?}", e)
         }
-        
-        cmp_node = cmp_node.next();
     }).unwrap();
 
     AdapterHandle::new(id, "net-udp".to_owned(), status.clone(), _is_closed)
@@ -1,5 +1,7 @@
-use rad_compositor::{composition::CompositionState, source::{formatted::{FormattedStreamSource, StreamOrigin}, utils::dyn_buf::DynFmtBuf}};
-use actix_web::{get, post, web, HttpResponse, Responder};
+use std::{sync::Arc, time::Duration};
+
+use rad_compositor::{analysis::{spectrum::{self, Spectrum}, waveform::overview_of_incoming_stream}, channel_layout::MixMatrix, cmp_reg::NestError, composition::{is_valid_amplification, CompositionSrc, CompositionState, FinishPolicy, TSrcID}, compositor::{is_valid_block_size, level::Levels, limiter::LimiterConfig, DEFAULT_BLOCK_SIZE}, effect::EffectChain, source::{error::SourceError, formatted::{FormattedStreamSource, StreamOrigin}, generator::GeneratorSrc, metadata::TrackMetadata, queue::{Crossfade, QueueSrc, RepeatMode}, utils::{dyn_buf::{DataLock, DynFmtBuf}, hint::hint_of_mime_type}, BaseSource, Source, TFrameIdx}};
+use actix_web::{delete, get, http::header, post, web, HttpRequest, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use futures::StreamExt;
 
@@ -25,15 +27,114 @@ macro_rules! find_cmp_write {
     };
 }
 
+/// This is the representational type of the sources of a composition that is going to be exposed through the API.
+#[derive(Deserialize, Serialize)]
+struct SerdeSrc {
+    pub id: TSrcID,
+    pub kind: String,
+    pub frame_offset: i64,
+    pub amplification: f32,
+    pub muted: bool,
+    pub solo: bool,
+    pub is_paused: bool,
+    pub effects: Vec<String>,
+    /// The integrated loudness (in LUFS) once it's known.
+    pub loudness: Option<f32>,
+    pub normalization_gain: f32,
+    pub finish_policy: String,
+    pub finished: bool,
+    /// The number of packets that failed to decode and were played as silence instead.
+    pub decode_errors: u64,
+    /// A faulted source failed too often to be played anymore, it's silent until it's removed.
+    pub faulted: bool
+}
+
+impl SerdeSrc {
+    fn from_src(value: &CompositionSrc) -> Self {
+        SerdeSrc {
+            id: value.id(),
+            kind: value.src.kind().to_owned(),
+            frame_offset: value.composition_data.frame_offset,
+            amplification: value.composition_data.amplification,
+            muted: value.composition_data.muted,
+            solo: value.composition_data.solo,
+            is_paused: value.composition_data.is_paused(),
+            effects: value.composition_data.effects.specs(),
+            loudness: value.src.loudness(),
+            normalization_gain: value.composition_data.normalization_gain(),
+            finish_policy: value.composition_data.finish_policy.to_string(),
+            finished: value.composition_data.is_finished(),
+            decode_errors: value.src.decode_errors(),
+            faulted: value.composition_data.is_faulted()
+        }
+    }
+}
+
+/// The limiter of a composition along with how much it has turned the output down (in dB).
+#[derive(Deserialize, Serialize)]
+struct SerdeLimiter {
+    pub config: String,
+    pub gain_reduction_db: f32,
+    pub max_gain_reduction_db: f32
+}
+
+impl SerdeLimiter {
+    fn from_state(value: &CompositionState) -> Self {
+        let gain_reduction = value.get_gain_reduction();
+
+        SerdeLimiter {
+            config: value.get_limiter().to_string(),
+            gain_reduction_db: gain_reduction.current_db,
+            max_gain_reduction_db: gain_reduction.max_db
+        }
+    }
+}
+
+/// The levels of a source or the output of a composition, in dBFS and LUFS.
+#[derive(Deserialize, Serialize)]
+struct SerdeLevels {
+    pub peak_db: Vec<f32>,
+    pub rms_db: Vec<f32>,
+    pub short_term_lufs: Option<f32>
+}
+
+impl SerdeLevels {
+    fn from_levels(value: &Levels) -> Self {
+        SerdeLevels {
+            peak_db: value.peak_db.clone(),
+            rms_db: value.rms_db.clone(),
+            short_term_lufs: value.short_term_lufs
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct SerdeSrcLevels {
+    pub src_id: TSrcID,
+    #[serde(flatten)]
+    pub levels: SerdeLevels
+}
+
+/// The levels of a composition for drawing its meters, none until a compositor has computed it.
+#[derive(Deserialize, Serialize)]
+struct SerdeCompositionLevels {
+    pub output: Option<SerdeLevels>,
+    pub sources: Vec<SerdeSrcLevels>
+}
+
 /// This is the representational type of the composition state that is going to be exposed through the API.
 #[derive(Deserialize, Serialize)]
 struct SerdeCompositor {
     pub id: String,
 	pub is_paused: bool,
 	pub channels: usize,
-	// pub sources: Vec<CompositionSrc>,
+	pub channel_layout: String,
 	pub amplification: f32,
-    pub time: f32
+	pub effects: Vec<String>,
+	pub limiter: SerdeLimiter,
+	pub loudness_target: Option<f32>,
+    pub time: f32,
+	pub sources: Vec<SerdeSrc>
 }
 
 impl SerdeCompositor {
@@ -42,8 +143,13 @@ impl SerdeCompositor {
             id: value.get_id().clone(),
             is_paused: value.is_paused(),
             channels: value.get_channels().into(),
+            channel_layout: value.get_channel_layout().to_string(),
             amplification: value.get_amplification(),
-            time: value.get_time_sec() as f32
+            effects: value.get_effects().specs(),
+            limiter: SerdeLimiter::from_state(value),
+            loudness_target: value.get_loudness_target(),
+            time: value.get_time_sec() as f32,
+            sources: value.sources().iter().map(SerdeSrc::from_src).collect()
         }
     }
 }
@@ -95,6 +201,694 @@ pub async fn get_info_json(_cmp_id: web::Path<(String,)>, data: web::Data<State>
     HttpResponse::Ok().json(SerdeCompositor::from_state(&cmp))  
 }
 
+#[get("/{cmp_id}/src")]
+pub async fn get_sources(_cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    HttpResponse::Ok().json(cmp.sources().iter().map(SerdeSrc::from_src).collect::<Vec<_>>())
+}
+
+#[delete("/{cmp_id}/src/{src_id}")]
+pub async fn remove_source(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match cmp.remove_src(src_id) {
+        Some(_) => HttpResponse::Ok().body("OK\n"),
+        None => HttpResponse::NotFound().body("NOT FOUND\n")
+    }
+}
+
+#[post("/{cmp_id}/src/{src_id}/amp")]
+pub async fn set_source_amp(body: web::Bytes, _path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let amp = match String::from_utf8(body.to_vec()).ok().and_then(|st| st.parse::<f32>().ok()).filter(|amp| is_valid_amplification(*amp)) {
+        Some(v) => v,
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID AMPLIFICATION )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match cmp.find_src_mut(src_id) {
+        Some(cmp_src) => { cmp_src.composition_data.amplification = amp; },
+        None => { return HttpResponse::NotFound().body("NOT FOUND\n"); }
+    }
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+/// A custom mixing matrix with a row of coefficients for each channel of the composition, no coefficients means the default up/down-mix.
+#[derive(Deserialize)]
+struct SerdeMixMatrix {
+    pub coeffs: Option<Vec<f32>>
+}
+
+#[post("/{cmp_id}/src/{src_id}/matrix")]
+pub async fn set_source_matrix(body: web::Json<SerdeMixMatrix>, _path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    let out_channels = cmp.get_channels();
+
+    let cmp_src = match cmp.find_src_mut(src_id) {
+        Some(s) => s,
+        None => { return HttpResponse::NotFound().body("NOT FOUND\n"); }
+    };
+
+    let matrix = match body.into_inner().coeffs {
+        Some(coeffs) => match MixMatrix::new(cmp_src.src.channels(), out_channels, coeffs) {
+            Some(m) => Some(m),
+            None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID MATRIX SIZE )\n"); }
+        },
+        None => None
+    };
+
+    cmp_src.composition_data.mix_matrix = matrix;
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+#[get("/{cmp_id}/src/{src_id}/effects")]
+pub async fn get_source_effects(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    match cmp.find_src(src_id) {
+        Some(cmp_src) => HttpResponse::Ok().json(cmp_src.composition_data.effects.specs()),
+        None => HttpResponse::NotFound().body("NOT FOUND\n")
+    }
+}
+
+/// Replaces the effects of a source with the ones in the body (a list of effect specs, e.g. `["hp:freq=80"]`).
+#[post("/{cmp_id}/src/{src_id}/effects")]
+pub async fn set_source_effects(body: web::Json<Vec<String>>, _path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let chain = match EffectChain::parse(body.iter().map(|e| e.as_str())) {
+        Some(c) => c,
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID EFFECTS )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match cmp.find_src_mut(src_id) {
+        Some(cmp_src) => { cmp_src.composition_data.effects = chain; },
+        None => { return HttpResponse::NotFound().body("NOT FOUND\n"); }
+    }
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+/// The tags of a source, along with whether it has cover art (which is served by `get_source_cover`).
+#[derive(Deserialize, Serialize)]
+struct SerdeMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub has_cover: bool,
+    pub tags: Vec<(String, String)>
+}
+
+impl SerdeMetadata {
+    fn from_metadata(value: &TrackMetadata) -> Self {
+        SerdeMetadata {
+            title: value.title.clone(),
+            artist: value.artist.clone(),
+            album: value.album.clone(),
+            track_number: value.track_number,
+            has_cover: value.cover.is_some(),
+            tags: value.tags.clone()
+        }
+    }
+}
+
+/// What a source is playing, the times are in seconds.
+#[derive(Deserialize, Serialize)]
+struct SerdeNowPlaying {
+    pub src_id: TSrcID,
+    pub kind: String,
+    pub metadata: Option<SerdeMetadata>,
+    /// The index of the item playing when the source is a queue.
+    pub queue_index: Option<usize>,
+    pub elapsed: f32,
+    pub duration: Option<f32>,
+    pub remaining: Option<f32>
+}
+
+/// Reports what every source of the composition is playing.
+#[get("/{cmp_id}/now-playing")]
+pub async fn get_now_playing(_cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    let now_playing = cmp.sources().iter().filter_map(|cmp_src| {
+        let np = cmp.now_playing(cmp_src.id())?;
+
+        Some(SerdeNowPlaying {
+            src_id: cmp_src.id(),
+            kind: cmp_src.src.kind().to_owned(),
+            metadata: np.metadata.map(SerdeMetadata::from_metadata),
+            queue_index: np.queue_idx,
+            elapsed: np.elapsed_secs() as f32,
+            duration: np.length_secs().map(|s| s as f32),
+            remaining: np.remaining_secs().map(|s| s as f32)
+        })
+    }).collect::<Vec<_>>();
+
+    HttpResponse::Ok().json(now_playing)
+}
+
+#[get("/{cmp_id}/src/{src_id}/metadata")]
+pub async fn get_source_metadata(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    match cmp.find_src(src_id) {
+        Some(cmp_src) => HttpResponse::Ok().json(cmp_src.src.metadata().map(SerdeMetadata::from_metadata)),
+        None => HttpResponse::NotFound().body("NOT FOUND\n")
+    }
+}
+
+/// Responds with the cover art of what a source is playing as it was embedded in the file.
+#[get("/{cmp_id}/src/{src_id}/cover")]
+pub async fn get_source_cover(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    match cmp.find_src(src_id).and_then(|cmp_src| cmp_src.src.metadata()?.cover.clone()) {
+        Some(cover) => HttpResponse::Ok().content_type(cover.media_type.as_str()).body(cover.data.to_vec()),
+        None => HttpResponse::NotFound().body("NOT FOUND\n")
+    }
+}
+
+#[derive(Deserialize)]
+struct WaveformQuery {
+    /// The number of parts (e.g. pixels) the track is split into.
+    width: Option<usize>
+}
+
+const DEFAULT_WAVEFORM_WIDTH: usize = 1000;
+const MAX_WAVEFORM_WIDTH: usize = 100_000;
+
+/// The overview of the waveform of a track, with the lowest and the highest sample of each part of it.
+#[derive(Deserialize, Serialize)]
+struct SerdeWaveform {
+    pub sample_rate: TFrameIdx,
+    /// The length of the track in seconds.
+    pub duration: f64,
+    pub peaks: Vec<[f32; 2]>
+}
+
+/// Outputs the overview of the waveform of what a source is playing, which isn't found until it has been made.
+#[get("/{cmp_id}/src/{src_id}/waveform")]
+pub async fn get_source_waveform(_path: web::Path<(String, TSrcID)>, query: web::Query<WaveformQuery>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let width = query.width.unwrap_or(DEFAULT_WAVEFORM_WIDTH);
+    if !(1..=MAX_WAVEFORM_WIDTH).contains(&width) {
+        return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID WIDTH )\n");
+    }
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    match cmp.find_src(src_id).and_then(|cmp_src| cmp_src.src.waveform_overview()) {
+        Some(overview) => HttpResponse::Ok().json(SerdeWaveform {
+            sample_rate: overview.sample_rate,
+            duration: overview.duration_secs(),
+            peaks: overview.peaks(width).into_iter().map(|(min, max)| [min, max]).collect()
+        }),
+        None => HttpResponse::NotFound().body("NOT FOUND\n")
+    }
+}
+
+/// Sets what happens to a source once it has played to its end from the body (`remove`, `hold`, `loop` or `loop:{times}`).
+#[post("/{cmp_id}/src/{src_id}/finish")]
+pub async fn set_source_finish_policy(body: web::Bytes, _path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let policy = match String::from_utf8(body.to_vec()).ok().and_then(|st| st.trim().parse::<FinishPolicy>().ok()) {
+        Some(v) => v,
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID FINISH POLICY )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match cmp.find_src_mut(src_id) {
+        Some(cmp_src) => { cmp_src.composition_data.finish_policy = policy; },
+        None => { return HttpResponse::NotFound().body("NOT FOUND\n"); }
+    }
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+/// Handy macro for finding a queue source of a composition and handling errors related to it.
+macro_rules! find_queue {
+    ($cmp:expr, $src_id:expr) => {
+        match $cmp.find_src($src_id).map(|s| &s.src) {
+            Some(Source::Queue(q)) => q,
+            Some(_) => { return HttpResponse::BadRequest().body("BAD REQUEST ( NOT A QUEUE )\n"); },
+            None => { return HttpResponse::NotFound().body("NOT FOUND\n"); }
+        }
+    };
+}
+
+/// Same as `find_queue`, but gives the queue for making changes to it.
+macro_rules! find_queue_mut {
+    ($cmp:expr, $src_id:expr) => {
+        match $cmp.find_src_mut($src_id).map(|s| &mut s.src) {
+            Some(Source::Queue(q)) => q,
+            Some(_) => { return HttpResponse::BadRequest().body("BAD REQUEST ( NOT A QUEUE )\n"); },
+            None => { return HttpResponse::NotFound().body("NOT FOUND\n"); }
+        }
+    };
+}
+
+/// The representational type of the playlist of a queue source.
+#[derive(Deserialize, Serialize)]
+struct SerdeQueue {
+    /// The index of the item that is playing, none once the queue has ended.
+    pub current: Option<usize>,
+    /// How far (in seconds) the current item has played.
+    pub position: f32,
+    pub repeat: String,
+    /// How the items fade into one another, none when they play back to back.
+    pub crossfade: Option<String>,
+    pub items: Vec<SerdeQueueItem>
+}
+
+#[derive(Deserialize, Serialize)]
+struct SerdeQueueItem {
+    pub kind: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// The length of the item in seconds if it's known and it ends.
+    pub duration: Option<f32>
+}
+
+impl SerdeQueueItem {
+    fn from_src(value: &Source) -> Self {
+        let metadata = value.metadata();
+
+        SerdeQueueItem {
+            kind: value.kind().to_owned(),
+            title: metadata.and_then(|m| m.title.clone()),
+            artist: metadata.and_then(|m| m.artist.clone()),
+            duration: value.duration().filter(|d| *d != 0).map(|d| d as f32 / value.sample_rate() as f32)
+        }
+    }
+}
+
+#[get("/{cmp_id}/src/{src_id}/queue")]
+pub async fn get_queue(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+    let queue = find_queue!(cmp, src_id);
+
+    HttpResponse::Ok().json(SerdeQueue {
+        current: queue.current(),
+        position: queue.current_position() as f32 / queue.sample_rate() as f32,
+        repeat: queue.repeat().to_string(),
+        crossfade: queue.crossfade().map(|c| c.to_string()),
+        items: queue.sources().iter().map(SerdeQueueItem::from_src).collect()
+    })
+}
+
+/// Applies one of the transport controls of a queue source (next, prev, shuffle) to it.
+fn control_queue(path: web::Path<(String, TSrcID)>, data: web::Data<State>, control: fn(&mut QueueSrc)) -> HttpResponse {
+    let (cmp_id, src_id) = path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    control(find_queue_mut!(cmp, src_id));
+
+    // Skipping in a queue that has ended starts it again
+    cmp.forget_src_finished(src_id);
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+#[post("/{cmp_id}/src/{src_id}/queue/next")]
+pub async fn skip_queue_next(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_queue(_path, data, QueueSrc::skip_next)
+}
+
+#[post("/{cmp_id}/src/{src_id}/queue/prev")]
+pub async fn skip_queue_previous(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_queue(_path, data, QueueSrc::skip_previous)
+}
+
+#[post("/{cmp_id}/src/{src_id}/queue/shuffle")]
+pub async fn shuffle_queue(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_queue(_path, data, QueueSrc::shuffle)
+}
+
+/// Sets the repeat mode of a queue source from the body (`off`, `one` or `all`).
+#[post("/{cmp_id}/src/{src_id}/queue/repeat")]
+pub async fn set_queue_repeat(body: web::Bytes, _path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let repeat = match String::from_utf8(body.to_vec()).ok().and_then(|st| st.trim().parse::<RepeatMode>().ok()) {
+        Some(v) => v,
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID REPEAT MODE )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    find_queue_mut!(cmp, src_id).set_repeat(repeat);
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+/// Sets the crossfade of a queue source from the body (e.g. `equal-power:dur=5`), or plays the items back to back with `off`.
+#[post("/{cmp_id}/src/{src_id}/queue/crossfade")]
+pub async fn set_queue_crossfade(body: web::Bytes, _path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id) = _path.into_inner();
+
+    let crossfade = match String::from_utf8(body.to_vec()).ok().as_deref().map(str::trim) {
+        Some("off") => None,
+        Some(st) => match st.parse::<Crossfade>() {
+            Ok(c) => Some(c),
+            Err(_) => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID CROSSFADE )\n"); }
+        },
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID CROSSFADE )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    find_queue_mut!(cmp, src_id).set_crossfade(crossfade);
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+#[post("/{cmp_id}/src/{src_id}/queue/jump/{idx}")]
+pub async fn jump_queue(_path: web::Path<(String, TSrcID, usize)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id, idx) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match find_queue_mut!(cmp, src_id).jump_to(idx) {
+        Some(()) => {
+            cmp.forget_src_finished(src_id);
+            HttpResponse::Ok().body("OK\n")
+        },
+        None => HttpResponse::BadRequest().body("BAD REQUEST ( INVALID INDEX )\n")
+    }
+}
+
+#[post("/{cmp_id}/src/{src_id}/queue/move/{from}/{to}")]
+pub async fn move_queue_item(_path: web::Path<(String, TSrcID, usize, usize)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id, from, to) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match find_queue_mut!(cmp, src_id).move_item(from, to) {
+        Some(()) => HttpResponse::Ok().body("OK\n"),
+        None => HttpResponse::BadRequest().body("BAD REQUEST ( INVALID INDEX )\n")
+    }
+}
+
+#[delete("/{cmp_id}/src/{src_id}/queue/{idx}")]
+pub async fn remove_queue_item(_path: web::Path<(String, TSrcID, usize)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, src_id, idx) = _path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match find_queue_mut!(cmp, src_id).remove(idx) {
+        Some(_) => HttpResponse::Ok().body("OK\n"),
+        None => HttpResponse::NotFound().body("NOT FOUND\n")
+    }
+}
+
+/// Inserts the uploaded file into a queue source at the index, the same way as `upload` adds it to a composition.
+#[post("/{cmp_id}/src/{src_id}/queue/insert/{idx}")]
+pub async fn insert_queue_item(_path: web::Path<(String, TSrcID, usize)>, data: web::Data<State>, req: HttpRequest, mut payload: web::Payload) -> impl Responder {
+    let (cmp_id, src_id, idx) = _path.into_inner();
+
+    let (src, dyn_buf_data) = match start_upload(&req, &mut payload).await {
+        Ok(v) => v,
+        Err(res) => { return res; }
+    };
+
+    {
+        let cmp_reg = data.cmp_reg.lock().unwrap();
+        let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+        if find_queue_mut!(cmp, src_id).insert(idx, src.into()).is_none() {
+            return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID INDEX )\n");
+        }
+        cmp.forget_src_finished(src_id);
+        log::debug!("Inserted the uploaded stream into queue '{}' of composition '{}' at {}.", src_id, cmp_id, idx);
+    }
+
+    finish_upload(payload, dyn_buf_data).await
+}
+
+/// Applies one of the simple on/off controls of a source (mute, solo, pause) to it.
+fn control_source(path: web::Path<(String, TSrcID)>, data: web::Data<State>, control: fn(&mut CompositionState, TSrcID) -> Option<()>) -> HttpResponse {
+    let (cmp_id, src_id) = path.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+    match control(&mut cmp, src_id) {
+        Some(()) => HttpResponse::Ok().body("OK\n"),
+        None => HttpResponse::NotFound().body("NOT FOUND\n")
+    }
+}
+
+#[post("/{cmp_id}/src/{src_id}/mute")]
+pub async fn mute_source(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_source(_path, data, |cmp, id| cmp.set_src_muted(id, true))
+}
+
+#[post("/{cmp_id}/src/{src_id}/unmute")]
+pub async fn unmute_source(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_source(_path, data, |cmp, id| cmp.set_src_muted(id, false))
+}
+
+#[post("/{cmp_id}/src/{src_id}/solo")]
+pub async fn solo_source(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_source(_path, data, |cmp, id| cmp.set_src_solo(id, true))
+}
+
+#[post("/{cmp_id}/src/{src_id}/unsolo")]
+pub async fn unsolo_source(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_source(_path, data, |cmp, id| cmp.set_src_solo(id, false))
+}
+
+#[post("/{cmp_id}/src/{src_id}/pause")]
+pub async fn pause_source(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_source(_path, data, |cmp, id| cmp.set_src_paused(id, true))
+}
+
+#[post("/{cmp_id}/src/{src_id}/play")]
+pub async fn play_source(_path: web::Path<(String, TSrcID)>, data: web::Data<State>) -> impl Responder {
+    control_source(_path, data, |cmp, id| cmp.set_src_paused(id, false))
+}
+
+#[get("/{cmp_id}/effects")]
+pub async fn get_effects(_cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    HttpResponse::Ok().json(cmp.get_effects().specs())
+}
+
+/// Replaces the effects of the master bus with the ones in the body (a list of effect specs, e.g. `["comp:threshold=-20"]`).
+#[post("/{cmp_id}/effects")]
+pub async fn set_effects(body: web::Json<Vec<String>>, _cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let chain = match EffectChain::parse(body.iter().map(|e| e.as_str())) {
+        Some(c) => c,
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID EFFECTS )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    cmp.set_effects(chain);
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+/// Outputs the limiter of the composition and its current and maximum gain reduction.
+#[get("/{cmp_id}/limiter")]
+pub async fn get_limiter(_cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    HttpResponse::Ok().json(SerdeLimiter::from_state(&cmp))
+}
+
+#[derive(Deserialize)]
+struct SpectrumQuery {
+    /// The number of frames analysed, a power of two.
+    size: Option<usize>,
+    /// The sample-rate of the compositor the frames are taken from.
+    sample_rate: Option<TFrameIdx>
+}
+
+const DEFAULT_SPECTRUM_SIZE: usize = 2048;
+const DEFAULT_SPECTRUM_SAMPLE_RATE: TFrameIdx = 48000;
+/// How long the compositor gets for computing the frames of a spectrum.
+const SPECTRUM_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A snapshot of the spectrum of the output, the magnitudes are in dBFS with a bin every `bin_hz` from 0 Hz on.
+#[derive(Deserialize, Serialize)]
+struct SerdeSpectrum {
+    pub sample_rate: TFrameIdx,
+    pub size: usize,
+    pub bin_hz: f32,
+    pub magnitudes_db: Vec<f32>
+}
+
+impl SerdeSpectrum {
+    fn from_spectrum(value: Spectrum) -> Self {
+        SerdeSpectrum {
+            sample_rate: value.sample_rate,
+            size: value.size,
+            bin_hz: value.bin_hz(),
+            magnitudes_db: value.magnitudes_db
+        }
+    }
+}
+
+/// Analyses the frames the compositor of the composition computes next, starting the compositor if there isn't one.
+#[get("/{cmp_id}/spectrum")]
+pub async fn get_spectrum(_cmp_id: web::Path<(String,)>, query: web::Query<SpectrumQuery>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let size = query.size.unwrap_or(DEFAULT_SPECTRUM_SIZE);
+    if !spectrum::is_valid_size(size) {
+        return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID SIZE )\n");
+    }
+    let sample_rate = query.sample_rate.unwrap_or(DEFAULT_SPECTRUM_SAMPLE_RATE);
+    if sample_rate == 0 {
+        return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID SAMPLE RATE )\n");
+    }
+
+    let (mut cursor, channels) = {
+        let mut cmp_reg = data.cmp_reg.lock().unwrap();
+        let (channels, is_paused) = {
+            let cmp = find_cmp_read!(cmp_reg, cmp_id);
+            (cmp.get_channels() as usize, cmp.is_paused())
+        };
+
+        // Paused compositions are silent and their compositors don't compute anything to wait for
+        if is_paused {
+            return HttpResponse::Ok().json(SerdeSpectrum::from_spectrum(spectrum::analyze(&vec![0.0; size * channels], channels, sample_rate)));
+        }
+
+        match cmp_reg.get_cursor(&cmp_id, sample_rate, DEFAULT_BLOCK_SIZE) {
+            Some(cursor) => (cursor, channels),
+            None => { return HttpResponse::NotFound().body("NOT FOUND\n"); }
+        }
+    };
+
+    match web::block(move || spectrum::capture(&mut cursor, channels, sample_rate, size, SPECTRUM_TIMEOUT)).await {
+        Ok(Some(spectrum)) => HttpResponse::Ok().json(SerdeSpectrum::from_spectrum(spectrum)),
+        _ => HttpResponse::ServiceUnavailable().body("SERVICE UNAVAILABLE\n")
+    }
+}
+
+/// Outputs the levels of the output of the composition and of its sources (before their amplification) in the last buffer computed.
+#[get("/{cmp_id}/levels")]
+pub async fn get_levels(_cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let cmp = find_cmp_read!(cmp_reg, cmp_id);
+
+    HttpResponse::Ok().json(SerdeCompositionLevels {
+        output: cmp.get_levels().map(SerdeLevels::from_levels),
+        sources: cmp.sources().iter()
+            .filter_map(|cmp_src| Some(SerdeSrcLevels {
+                src_id: cmp_src.id(),
+                levels: SerdeLevels::from_levels(cmp_src.composition_data.levels()?)
+            }))
+            .collect()
+    })
+}
+
+/// Sets the limiter from its spec in the body (e.g. `brickwall:ceiling=-1`).
+#[post("/{cmp_id}/limiter")]
+pub async fn set_limiter(body: web::Bytes, _cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let limiter = match String::from_utf8(body.to_vec()).ok().and_then(|st| st.trim().parse::<LimiterConfig>().ok()) {
+        Some(l) => l,
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID LIMITER )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    cmp.set_limiter(limiter);
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+/// Resets the maximum gain reduction of the limiter.
+#[post("/{cmp_id}/limiter/reset")]
+pub async fn reset_limiter(_cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    cmp.reset_gain_reduction();
+
+    HttpResponse::Ok().body("OK\n")
+}
+
+/// Sets the loudness target (in LUFS) the sources get normalized to, or turns normalization off with `off` as the body.
+#[post("/{cmp_id}/loudness-target")]
+pub async fn set_loudness_target(body: web::Bytes, _cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+
+    let target = match String::from_utf8(body.to_vec()).as_deref().map(str::trim) {
+        Ok("off") => None,
+        Ok(st) => match st.parse::<f32>() {
+            Ok(v) if v.is_finite() && v <= 0.0 => Some(v),
+            _ => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID LOUDNESS TARGET )\n"); }
+        },
+        Err(_) => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID LOUDNESS TARGET )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    cmp.set_loudness_target(target);
+
+    HttpResponse::Ok().body("OK\n")
+}
+
 #[post("/{cmp_id}/pause")]
 pub async fn set_pause(_cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
     let (cmp_id,) = _cmp_id.into_inner();
@@ -117,10 +911,61 @@ pub async fn set_play(_cmp_id: web::Path<(String,)>, data: web::Data<State>) ->
     HttpResponse::Ok().body("OK\n")
 }
 
-#[post("/{cmp_id}/upload")]
-pub async fn upload(_cmp_id: web::Path<(String,)>, data: web::Data<State>, mut payload: web::Payload) -> impl Responder {
+/// Adds a generator from its spec in the body (e.g. `sine:freq=1000`) and responds with the ID of the source.
+#[post("/{cmp_id}/gen")]
+pub async fn add_generator(body: web::Bytes, _cmp_id: web::Path<(String,)>, data: web::Data<State>) -> impl Responder {
     let (cmp_id,) = _cmp_id.into_inner();
-    
+
+    let src = match String::from_utf8(body.to_vec()).ok().and_then(|st| st.trim().parse::<GeneratorSrc>().ok()) {
+        Some(src) => src,
+        None => { return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID GENERATOR )\n"); }
+    };
+
+    let cmp_reg = data.cmp_reg.lock().unwrap();
+    let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+    let src_id = cmp.push_src_default(src.into());
+
+    HttpResponse::Ok().body(format!("{}\n", src_id))
+}
+
+/// The sample-rate nested compositions are read at when none is given.
+const DEFAULT_NESTED_SAMPLE_RATE: TFrameIdx = 48000;
+
+#[derive(Deserialize)]
+struct NestQuery {
+    sample_rate: Option<TFrameIdx>,
+    block_size: Option<usize>
+}
+
+/// Adds the output of the composition `inner_id` as a source of the composition and responds with the ID of the source.
+#[post("/{cmp_id}/nest/{inner_id}")]
+pub async fn nest(path: web::Path<(String, String)>, query: web::Query<NestQuery>, data: web::Data<State>) -> impl Responder {
+    let (cmp_id, inner_id) = path.into_inner();
+
+    let sample_rate = query.sample_rate.unwrap_or(DEFAULT_NESTED_SAMPLE_RATE);
+    if sample_rate == 0 {
+        return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID SAMPLE RATE )\n");
+    }
+
+    let block_size = query.block_size.unwrap_or(DEFAULT_BLOCK_SIZE);
+    if !is_valid_block_size(block_size) {
+        return HttpResponse::BadRequest().body("BAD REQUEST ( INVALID BLOCK SIZE )\n");
+    }
+
+    match data.cmp_reg.lock().unwrap().nest(&cmp_id, &inner_id, sample_rate, block_size) {
+        Ok(src_id) => HttpResponse::Ok().body(format!("{}\n", src_id)),
+        Err(NestError::NotFound) => HttpResponse::NotFound().body("NOT FOUND\n"),
+        Err(NestError::Cycle) => HttpResponse::BadRequest().body("BAD REQUEST ( CYCLE )\n")
+    }
+}
+
+/// Receives the start of an uploaded file, enough for probing its format, and opens it as a source.
+/// The rest of the file is received by `finish_upload` while the source is already playing.
+/// 
+/// The `Content-Type` of the request (e.g. `audio/flac`) helps with probing the format, though it's found out from the data without it too.
+async fn start_upload(req: &HttpRequest, payload: &mut web::Payload) -> Result<(FormattedStreamSource, Arc<DataLock>), HttpResponse> {
+    let hint = hint_of_mime_type(req.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default());
+
     let dyn_buf = Box::new(DynFmtBuf::new());
     let dyn_buf_data = dyn_buf.data_lock();
 
@@ -129,7 +974,7 @@ pub async fn upload(_cmp_id: web::Path<(String,)>, data: web::Data<State>, mut p
         let buf =
             match buf {
                 Ok(b) => b,
-                Err(e) => { eprintln!("Failed to receive file: {}", e); return HttpResponse::InternalServerError().finish(); },
+                Err(e) => { eprintln!("Failed to receive file: {}", e); return Err(HttpResponse::InternalServerError().finish()); },
             };
     
         let v = buf.into_iter().collect::<Vec<_>>();
@@ -140,21 +985,31 @@ pub async fn upload(_cmp_id: web::Path<(String,)>, data: web::Data<State>, mut p
         if 512 < byte_counter { break; }
     }
 
-    let src = FormattedStreamSource::open_stream(dyn_buf, Some(StreamOrigin::RemoteClient));
+    match FormattedStreamSource::open_stream(dyn_buf, &hint, Some(StreamOrigin::RemoteClient)) {
+        Ok(mut s) => {
+            s.set_overview(overview_of_incoming_stream(Box::new(DynFmtBuf::reading(dyn_buf_data.clone())), hint));
 
-    let src = 
-        match src {
-            Some(s) => s,
-            None => { return HttpResponse::InternalServerError().body("INTERNAL SERVER ERROR\n"); }
-        };
-    
-    {
-        let cmp_reg = data.cmp_reg.lock().unwrap();
-        let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+            Ok((s, dyn_buf_data))
+        },
+        Err(err) => Err(source_error_response(&err))
+    }
+}
 
-        cmp.push_src_default(src.into());
+/// The response to an upload that couldn't be opened as a source.
+fn source_error_response(err: &SourceError) -> HttpResponse {
+    match err {
+        SourceError::UnsupportedFormat => HttpResponse::UnsupportedMediaType().body("UNSUPPORTED MEDIA TYPE\n"),
+        SourceError::NoAudioTrack => HttpResponse::UnprocessableEntity().body("UNPROCESSABLE ENTITY ( NO AUDIO TRACK )\n"),
+        SourceError::UnknownDuration => HttpResponse::UnprocessableEntity().body("UNPROCESSABLE ENTITY ( UNKNOWN DURATION )\n"),
+        SourceError::Decode(_) => HttpResponse::UnprocessableEntity().body("UNPROCESSABLE ENTITY ( UNDECODABLE )\n"),
+        SourceError::NotFound | SourceError::Io(_) => {
+            log::error!("Failed to open an uploaded file: {}", err);
+            HttpResponse::InternalServerError().body("INTERNAL SERVER ERROR\n")
+        }
     }
+}
 
+async fn finish_upload(mut payload: web::Payload, dyn_buf_data: Arc<DataLock>) -> HttpResponse {
     while let Some(buf) = payload.next().await {
         let buf =
             match buf {
@@ -169,4 +1024,24 @@ pub async fn upload(_cmp_id: web::Path<(String,)>, data: web::Data<State>, mut p
     dyn_buf_data.set_eof();
 
     HttpResponse::Ok().body("OK\n")
-}
\ No newline at end of file
+}
+
+#[post("/{cmp_id}/upload")]
+pub async fn upload(_cmp_id: web::Path<(String,)>, data: web::Data<State>, req: HttpRequest, mut payload: web::Payload) -> impl Responder {
+    let (cmp_id,) = _cmp_id.into_inner();
+    
+    let (src, dyn_buf_data) = match start_upload(&req, &mut payload).await {
+        Ok(v) => v,
+        Err(res) => { return res; }
+    };
+    
+    {
+        let cmp_reg = data.cmp_reg.lock().unwrap();
+        let mut cmp = find_cmp_write!(cmp_reg, cmp_id);
+
+        let src_id = cmp.push_src_default(src.into());
+        log::debug!("Added the uploaded stream as source '{}' of composition '{}'.", src_id, cmp_id);
+    }
+
+    finish_upload(payload, dyn_buf_data).await
+}
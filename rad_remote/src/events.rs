@@ -0,0 +1,93 @@
+use std::{convert::Infallible, thread};
+
+use rad_compositor::{composition::TSrcID, event::{Event, GLOBAL_EVENT_BUS, SUBSCRIBER_BACKLOG}, source::TFrameIdx};
+use actix_web::{get, web::{self, Bytes}, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+/// The representational type of the events that is going to be exposed through the API.
+#[derive(Deserialize, Serialize)]
+struct SerdeEvent {
+    pub event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src: Option<TSrcID>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<TFrameIdx>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adapter: Option<String>
+}
+
+impl SerdeEvent {
+    fn from_event(value: &Event) -> Self {
+        let mut res = SerdeEvent {
+            event: value.name().to_owned(),
+            cmp: value.cmp_id().map(str::to_owned),
+            src: None,
+            kind: None,
+            policy: None,
+            time_ms: None,
+            sample_rate: None,
+            adapter: None
+        };
+
+        match value {
+            Event::CompositionSeeked { time_ms, .. } => res.time_ms = Some(*time_ms),
+            Event::SourceAdded { src_id, kind, .. } => { res.src = Some(*src_id); res.kind = Some(kind.clone()); },
+            Event::SourceRemoved { src_id, .. } |
+            Event::SourceFaulted { src_id, .. } => res.src = Some(*src_id),
+            Event::SourceFinished { src_id, policy, .. } => { res.src = Some(*src_id); res.policy = Some(policy.to_string()); },
+            Event::CompositorStarted { sample_rate, .. } |
+            Event::CompositorKilled { sample_rate, .. } => res.sample_rate = Some(*sample_rate),
+            Event::AdapterClosed { adapter_id } => res.adapter = Some(adapter_id.clone()),
+            Event::CompositionAdded { .. } | Event::CompositionPaused { .. } | Event::CompositionResumed { .. } => {}
+        }
+
+        res
+    }
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// Only the events of this composition (and the ones that aren't about any composition) are sent when it's given.
+    cmp: Option<String>
+}
+
+/// Streams the events of the daemon as server-sent events, each named after the event and carrying it as JSON.
+#[get("/v1/events")]
+pub async fn stream_events(query: web::Query<EventsQuery>) -> impl Responder {
+    let events = GLOBAL_EVENT_BUS.subscribe();
+    let (mut sender, receiver) = futures::channel::mpsc::channel::<Result<Bytes, Infallible>>(SUBSCRIBER_BACKLOG);
+    let cmp_filter = query.into_inner().cmp;
+
+    // The bus hands the events out on a blocking channel, so they're passed on to the response from a thread of their own.
+    // The thread stops with the first event after the client has gone, or once the client has fallen too far behind.
+    thread::Builder::new()
+        .name("remote-events".to_owned())
+        .spawn(move || {
+            for event in events {
+                if cmp_filter.as_deref().is_some_and(|cmp_id| event.cmp_id().is_some_and(|id| id != cmp_id)) {
+                    continue;
+                }
+
+                let json = serde_json::to_string(&SerdeEvent::from_event(&event)).unwrap();
+                let msg = format!("event: {}\ndata: {}\n\n", event.name(), json);
+
+                if sender.try_send(Ok(Bytes::from(msg))).is_err() {
+                    return;
+                }
+            }
+        })
+        .unwrap();
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(receiver)
+}
@@ -6,9 +6,10 @@ use actix_web::{middleware::NormalizePath, web, App, HttpServer};
 use rad_compositor::{adapter::AdapterHandle, cmp_reg::CompositionRegistry};
 
 mod cmp;
+mod events;
 
 struct State {
-    cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>,
+    cmp_reg: Arc<Mutex<CompositionRegistry>>,
     #[allow(dead_code)]
     adapters: Mutex<Vec<AdapterHandle>>
 }
@@ -16,9 +17,9 @@ struct State {
 // TODO: Add authentication
 // TODO: Add ability to control and monitor the adapters.
 /// Starts the REST API used to control and configure the service.
-pub async fn start_remote_server(cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>, adapters: Vec<AdapterHandle>, addr: SocketAddr) -> std::io::Result<()> { 
+pub async fn start_remote_server(cmp_reg: Arc<Mutex<CompositionRegistry>>, adapters: Vec<AdapterHandle>, addr: SocketAddr) -> std::io::Result<()> { 
     let state = State {
-        cmp_reg: cmp_reg,
+        cmp_reg,
         adapters: Mutex::new(adapters)
     };
 
@@ -28,12 +29,50 @@ pub async fn start_remote_server(cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>,
         App::new()
             .app_data(data.clone())
             .wrap(NormalizePath::trim())
+            .service(events::stream_events)
             .service(
                 web::scope("/v1/cmp")
                     .service(cmp::get_info)
                     .service(cmp::get_info_json)
                     .service(cmp::set_time)
                     .service(cmp::set_pause)
+                    .service(cmp::get_effects)
+                    .service(cmp::set_effects)
+                    .service(cmp::get_limiter)
+                    .service(cmp::set_limiter)
+                    .service(cmp::reset_limiter)
+                    .service(cmp::get_levels)
+                    .service(cmp::get_spectrum)
+                    .service(cmp::set_loudness_target)
+                    .service(cmp::get_sources)
+                    .service(cmp::get_now_playing)
+                    .service(cmp::get_source_metadata)
+                    .service(cmp::get_source_cover)
+                    .service(cmp::get_source_waveform)
+                    .service(cmp::remove_source)
+                    .service(cmp::set_source_amp)
+                    .service(cmp::set_source_matrix)
+                    .service(cmp::get_source_effects)
+                    .service(cmp::set_source_effects)
+                    .service(cmp::set_source_finish_policy)
+                    .service(cmp::get_queue)
+                    .service(cmp::set_queue_repeat)
+                    .service(cmp::set_queue_crossfade)
+                    .service(cmp::jump_queue)
+                    .service(cmp::move_queue_item)
+                    .service(cmp::insert_queue_item)
+                    .service(cmp::remove_queue_item)
+                    .service(cmp::skip_queue_next)
+                    .service(cmp::skip_queue_previous)
+                    .service(cmp::shuffle_queue)
+                    .service(cmp::mute_source)
+                    .service(cmp::unmute_source)
+                    .service(cmp::solo_source)
+                    .service(cmp::unsolo_source)
+                    .service(cmp::pause_source)
+                    .service(cmp::play_source)
+                    .service(cmp::add_generator)
+                    .service(cmp::nest)
                     .service(cmp::upload)
             )
     })
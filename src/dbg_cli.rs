@@ -1,6 +1,6 @@
-use std::{env, fs::canonicalize, io::{stdin, stdout, Write}, path::PathBuf};
+use std::{env, fs::canonicalize, io::{stdin, stdout, Write}, path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread::{self, sleep}, time::Duration};
 
-use rad_compositor::{composition::TWrappedCompositionState, source::{formatted::FormattedStreamSource, utils::sample_buf::queue_from_directory, TFrameIdx}};
+use rad_compositor::{channel_layout::MixMatrix, cmp_reg::NestError, composition::{is_valid_amplification, FinishPolicy, TSrcID, TWrappedCompositionState}, compositor::{level::{Levels, LEVEL_FLOOR_DB}, limiter::LimiterConfig, DEFAULT_BLOCK_SIZE}, effect::EffectChain, event::GLOBAL_EVENT_BUS, source::{formatted::FormattedStreamSource, generator::GeneratorSrc, queue::Crossfade, utils::sample_buf::queue_from_directory, BaseSource, Source, TFrameIdx}};
 
 use crate::{arg_config::ArgConfig, file_config::PState};
 
@@ -11,10 +11,38 @@ const ASCII_START_BANNER: &str = "
 const HELP_PAGE: &str =
 "> op [{filepath} | +{relative to {data-dir}/...}] -> Opens a new audio file
 > sc {cmp-id} | set-cmp {cmp-id}                  -> Selects the composition for use with other commands
+> gen {generator}                                 -> Adds a generator (e.g. 'sine:freq=1000', 'sweep:from=20,to=20000,dur=10',
+                                                     'noise:color=pink', 'silence:dur=5', 'click:bpm=120') to the selected composition
+> nest {cmp-id}                                   -> Adds the output of another composition as a source of the selected composition
 > amp                                             -> Outputs amplification of the selected composition
 > amp {amp}                                       -> Changes amplification of the selected composition
+> src lst                                         -> Lists the sources of the selected composition
+> np                                              -> Shows what the sources of the selected composition are playing
+> src del {src-id}                                -> Removes a source from the selected composition
+> src mute {src-id}                               -> Mutes/Unmutes a source
+> src solo {src-id}                               -> Solos/Unsolos a source
+> src p {src-id}                                  -> Pauses/Plays a source while the composition keeps going
+> src amp {src-id} {amp}                          -> Changes amplification of a source
+> src fin {src-id} {policy}                       -> Sets what happens to a source once it ends ('remove', 'hold', 'loop' or 'loop:{times}')
+> src mtx {src-id} [{c,c,...} | def]              -> Sets a custom mixing matrix (a row for each output channel) or the default up/down-mix
+> fx {src-id | m}                                 -> Lists the effects of a source or of the master bus (m)
+> fx {src-id | m} [{effect} ... | clr]            -> Replaces or clears the effects of a source or of the master bus (m),
+                                                     effects are written like 'hp:freq=80' or 'comp:threshold=-20,ratio=3'
+> lim                                             -> Outputs the limiter of the selected composition and its gain reduction
+> lim {limiter} | lim rst                         -> Sets the limiter (e.g. 'brickwall:ceiling=-1', 'soft-clip', 'off') or resets the max gain reduction
+> lvl                                             -> Shows the levels of the selected composition and of its sources live until enter is pressed
+> norm                                            -> Outputs the loudness target of the selected composition and the loudness of its sources
+> norm {lufs} | norm off                          -> Normalizes the sources of the selected composition to a loudness (e.g. -16) or stops normalizing them
+> q {src-id}                                     -> Lists the items of a queue source, marking the one that is playing
+> q {src-id} [next | prev | shuffle]              -> Skips to the next or previous item of a queue or shuffles its items
+> q {src-id} rpt [off | one | all]                -> Sets whether a queue repeats nothing, the current item or all of its items
+> q {src-id} xf {crossfade} | q {src-id} xf off   -> Crossfades the items of a queue (e.g. 'equal-power:dur=5', 'linear:dur=2') or plays them back to back
+> q {src-id} jmp {idx} | q {src-id} del {idx}     -> Plays or removes the item at an index of a queue
+> q {src-id} mv {from} {to}                       -> Moves an item of a queue to another index
+> q {src-id} ins {idx} {filepath}                 -> Opens a file (the same way as 'op') and inserts it into a queue at an index
 > ap lst                                          -> Lists the adapters
 > ap del {ap-id}                                  -> Deletes an adapter by ID
+> ev                                              -> Starts/Stops printing the events (e.g. pauses, seeks, added sources) as they happen
 > t | time                                        -> Time value of a composition in seconds
 > go {time(second)}                               -> Sets timeline value
 > help                                            -> Prints the help page
@@ -28,12 +56,85 @@ fn format_f32_sec(seconds: f32) -> String {
 }
 
 const QUEUE_SAMPLE_RATE: TFrameIdx = 48000;
+const NESTED_SAMPLE_RATE: TFrameIdx = 48000;
 const OPEN_DIR_SEARCH_DEPTH: u8 = u8::MAX;
 
+/// Resolves the paths given to commands, where `+` stands for the audio directory and `~` for the home directory.
+fn resolve_path(run_conf: &ArgConfig, path: &str) -> Option<PathBuf> {
+	let path = path.trim_start().trim_end();
+	
+	if path.is_empty() {
+		eprintln!("No path was given.");
+		return None;
+	}
+
+	let path = match path.chars().next().unwrap() {
+		'+' => {
+			run_conf.audio_dir().join(path.strip_prefix("+").unwrap())
+		},
+		'~' => {
+			if cfg!(unix) {
+				let home_dir = env::var_os("HOME");
+				
+				let without_prefix = match path.strip_prefix("~/") {
+					Some(path) => path,
+					None => {
+						// $HOME + "" = $HOME
+						if path.len() == 1 {
+							""
+						} else {
+							eprintln!("Invalid path");
+							return None;
+						}
+					}
+				};
+
+				match home_dir {
+					Some(home_dir) => PathBuf::from(home_dir).join(without_prefix),
+					None => PathBuf::from(path),
+				}
+			} else {
+				PathBuf::from(path)
+			}
+		},
+		_ => {
+			PathBuf::from(path)
+		}
+	};
+
+	Some(path)
+}
+
+/// The dBs below full scale drawn by the level meters of `lvl`.
+const METER_RANGE_DB: f32 = 60.0;
+const METER_WIDTH: usize = 30;
+
+/// A line of the level meters, where the bar is the RMS of the loudest channel and the mark after it is the highest peak.
+fn format_levels(label: &str, levels: Option<&Levels>) -> String {
+	let levels = match levels {
+		Some(l) => l,
+		None => { return format!(" {:>8} | -", label); }
+	};
+
+	let peak = levels.max_peak_db();
+	let rms = levels.rms_db.iter().copied().fold(LEVEL_FLOOR_DB, f32::max);
+	let cells = |db: f32| (((db + METER_RANGE_DB) / METER_RANGE_DB).clamp(0.0, 1.0) * METER_WIDTH as f32) as usize;
+	let (rms_cells, peak_cells) = (cells(rms), cells(peak));
+
+	let bar = (0..METER_WIDTH).map(|i| {
+		if i < rms_cells { '█' } else if i + 1 == peak_cells { '|' } else { ' ' }
+	}).collect::<String>();
+	let lufs = levels.short_term_lufs.map_or("-".to_owned(), |lufs| format!("{:.1}", lufs));
+
+	format!(" {:>8} | {} | {:>6.1} dB peak | {:>6.1} dB RMS | {:>6} LUFS", label, bar, peak, rms, lufs)
+}
+
 pub fn start_dbg_cli(run_conf: &ArgConfig, p_state: &mut PState) {
 	let PState { ref mut cmp_reg, ref mut adapters, remote_addr: _remote_addr } = p_state;
 	// The composition state selected by the `sc` command
 	let mut curr_cmp: Option<TWrappedCompositionState> = None;
+	// Set to stop the thread started by the `ev` command from printing events
+	let mut stop_events: Option<Arc<AtomicBool>> = None;
 	
 	let mut stdout = stdout();
 	let stdin = stdin();
@@ -72,6 +173,303 @@ pub fn start_dbg_cli(run_conf: &ArgConfig, p_state: &mut PState) {
 
 				cmp.set_paused(!is_paused);
 			},
+			["src", "lst"] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				// Hardcoded space of each field in characters:
+				// ID(8) | Kind(8) | Offset(12) | Amp(8) | State(8)
+				println!("\x1b[0;30m    ID    |   Kind   |    Offset    |   Amp    |  State  \x1b[0m");
+				for cmp_src in curr_cmp.read().unwrap().sources() {
+					let data = &cmp_src.composition_data;
+					let state_str = if data.is_faulted() { "Faulted" } else if data.is_finished() { "Finished" } else if data.is_paused() { "Paused" } else if data.muted { "Muted" } else if data.solo { "Solo" } else { "Playing" };
+
+					println!(" {:^8} | {:^8} | {:^12} | {:^8} | {:^8}", cmp_src.id(), cmp_src.src.kind(), data.frame_offset, data.amplification, state_str);
+				}
+			},
+			["np"] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let cmp = curr_cmp.read().unwrap();
+				for cmp_src in cmp.sources() {
+					let np = match cmp.now_playing(cmp_src.id()) {
+						Some(np) => np,
+						None => continue
+					};
+
+					let title = np.metadata.and_then(|m| m.title.as_deref()).unwrap_or("Unknown");
+					let artist = np.metadata.and_then(|m| m.artist.as_deref()).unwrap_or("Unknown");
+					let length = np.length_secs().map_or("-".to_owned(), |s| format_f32_sec(s as f32));
+					let item = np.queue_idx.map_or(String::new(), |idx| format!(" (#{})", idx));
+
+					println!(" {:>8} | {} - {}{} | {} / {}", cmp_src.id(), artist, title, item, format_f32_sec(np.elapsed_secs() as f32), length);
+				}
+			},
+			["src", "del", _id] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let id = match _id.parse::<TSrcID>() {
+					Ok(id) => id,
+					Err(_) => { eprintln!("Invalid source ID"); continue; }
+				};
+
+				if curr_cmp.write().unwrap().remove_src(id).is_none() {
+					eprintln!("No source was found with the specified ID");
+				}
+			},
+			["src", action @ ("mute" | "solo" | "p"), _id] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let id = match _id.parse::<TSrcID>() {
+					Ok(id) => id,
+					Err(_) => { eprintln!("Invalid source ID"); continue; }
+				};
+
+				let mut cmp = curr_cmp.write().unwrap();
+				let data = match cmp.find_src_mut(id) {
+					Some(cmp_src) => &mut cmp_src.composition_data,
+					None => { eprintln!("No source was found with the specified ID"); continue; }
+				};
+
+				match action {
+					"mute" => data.muted = !data.muted,
+					"solo" => data.solo = !data.solo,
+					_ => {
+						let is_paused = data.is_paused();
+						cmp.set_src_paused(id, !is_paused);
+					}
+				}
+			},
+			["src", "amp", _id, _amp] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let (id, amp) = match (_id.parse::<TSrcID>(), _amp.parse::<f32>()) {
+					(Ok(id), Ok(amp)) if is_valid_amplification(amp) => (id, amp),
+					_ => { eprintln!("Invalid source ID or amplification"); continue; }
+				};
+
+				match curr_cmp.write().unwrap().find_src_mut(id) {
+					Some(cmp_src) => cmp_src.composition_data.amplification = amp,
+					None => eprintln!("No source was found with the specified ID")
+				};
+			},
+			["src", "fin", _id, _policy] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let (id, policy) = match (_id.parse::<TSrcID>(), _policy.parse::<FinishPolicy>()) {
+					(Ok(id), Ok(policy)) => (id, policy),
+					_ => { eprintln!("Invalid source ID or finish policy"); continue; }
+				};
+
+				match curr_cmp.write().unwrap().find_src_mut(id) {
+					Some(cmp_src) => cmp_src.composition_data.finish_policy = policy,
+					None => eprintln!("No source was found with the specified ID")
+				};
+			},
+			["src", "mtx", _id, _coeffs] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let id = match _id.parse::<TSrcID>() {
+					Ok(id) => id,
+					Err(_) => { eprintln!("Invalid source ID"); continue; }
+				};
+
+				let mut cmp = curr_cmp.write().unwrap();
+				let out_channels = cmp.get_channels();
+				let cmp_src = match cmp.find_src_mut(id) {
+					Some(cmp_src) => cmp_src,
+					None => { eprintln!("No source was found with the specified ID"); continue; }
+				};
+
+				if _coeffs == "def" {
+					cmp_src.composition_data.mix_matrix = None;
+					continue;
+				}
+
+				let coeffs = match _coeffs.split(',').map(|c| c.parse::<f32>()).collect::<Result<Vec<_>, _>>() {
+					Ok(coeffs) => coeffs,
+					Err(_) => { eprintln!("Invalid coefficients"); continue; }
+				};
+
+				match MixMatrix::new(cmp_src.src.channels(), out_channels, coeffs) {
+					Some(matrix) => cmp_src.composition_data.mix_matrix = Some(matrix),
+					None => eprintln!("The matrix must have {} rows of {} coefficients", out_channels, cmp_src.src.channels())
+				}
+			},
+			["fx", target, ref specs @ ..] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				// `None` stands for the master bus
+				let src_id = match target {
+					"m" => None,
+					_ => match target.parse::<TSrcID>() {
+						Ok(id) => Some(id),
+						Err(_) => { eprintln!("Invalid source ID"); continue; }
+					}
+				};
+
+				let mut cmp = curr_cmp.write().unwrap();
+				let chain = match src_id {
+					None => cmp.get_effects(),
+					Some(id) => match cmp.find_src(id) {
+						Some(cmp_src) => &cmp_src.composition_data.effects,
+						None => { eprintln!("No source was found with the specified ID"); continue; }
+					}
+				};
+
+				if specs.is_empty() {
+					for (i, spec) in chain.specs().iter().enumerate() {
+						println!(" {}. {}", i + 1, spec);
+					}
+
+					continue;
+				}
+
+				let new_chain = match specs {
+					["clr"] => EffectChain::new(Vec::new()),
+					_ => match EffectChain::parse(specs.iter().copied()) {
+						Some(chain) => chain,
+						None => { eprintln!("Invalid effects"); continue; }
+					}
+				};
+
+				match src_id {
+					None => cmp.set_effects(new_chain),
+					Some(id) => cmp.find_src_mut(id).unwrap().composition_data.effects = new_chain
+				}
+			},
+			["lim"] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let cmp = curr_cmp.read().unwrap();
+				let gain_reduction = cmp.get_gain_reduction();
+				println!("{}", cmp.get_limiter());
+				println!("Gain reduction: {:.1} dB (max {:.1} dB)", gain_reduction.current_db, gain_reduction.max_db);
+			},
+			["lim", "rst"] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				curr_cmp.write().unwrap().reset_gain_reduction();
+			},
+			["lim", _limiter] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				match _limiter.parse::<LimiterConfig>() {
+					Ok(limiter) => curr_cmp.write().unwrap().set_limiter(limiter),
+					Err(_) => eprintln!("Invalid limiter")
+				}
+			},
+			["lvl"] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				// Enter is waited for on another thread so that the meters keep being drawn meanwhile
+				let stop = Arc::new(AtomicBool::new(false));
+				let _stop = stop.clone();
+				thread::spawn(move || {
+					let _ = std::io::stdin().read_line(&mut String::new());
+					_stop.store(true, Ordering::Relaxed);
+				});
+
+				let mut drawn_lines = 0;
+				while !stop.load(Ordering::Relaxed) {
+					let lines = {
+						let cmp = curr_cmp.read().unwrap();
+
+						let mut lines = vec![format_levels("Output", cmp.get_levels())];
+						lines.extend(cmp.sources().iter().map(|cmp_src| format_levels(&cmp_src.id().to_string(), cmp_src.composition_data.levels())));
+
+						lines
+					};
+
+					// Draws over the meters drawn before
+					if drawn_lines != 0 {
+						print!("\x1b[{}A\x1b[J", drawn_lines);
+					}
+					for line in lines.iter() {
+						println!("{}", line);
+					}
+					stdout.flush().unwrap();
+
+					drawn_lines = lines.len();
+					sleep(Duration::from_millis(100));
+				}
+			},
+			["norm"] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let cmp = curr_cmp.read().unwrap();
+				match cmp.get_loudness_target() {
+					Some(target) => println!("Target: {} LUFS", target),
+					None => println!("Target: off")
+				}
+
+				// Hardcoded space of each field in characters:
+				// ID(8) | Loudness(12) | Gain(10)
+				println!("\x1b[0;30m    ID    |   Loudness   |   Gain   \x1b[0m");
+				for cmp_src in cmp.sources() {
+					let loudness = match cmp_src.src.loudness() {
+						Some(lufs) => format!("{:.1} LUFS", lufs),
+						None => "?".to_owned()
+					};
+					let gain_db = 20.0 * cmp_src.composition_data.normalization_gain().log10();
+
+					println!(" {:^8} | {:^12} | {:^8} ", cmp_src.id(), loudness, format!("{:+.1} dB", gain_db));
+				}
+			},
+			["norm", _target] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let target = match _target {
+					"off" => None,
+					_ => match _target.parse::<f32>() {
+						Ok(v) if v.is_finite() && v <= 0.0 => Some(v),
+						_ => { eprintln!("Invalid loudness target"); continue; }
+					}
+				};
+
+				curr_cmp.write().unwrap().set_loudness_target(target);
+			},
 			["ap", "lst"] => {
 				const IS_CLOSED_TRUE_STR:  &str = "Closed";
 				const IS_CLOSED_FALSE_STR: &str = "Open";
@@ -137,54 +535,45 @@ pub fn start_dbg_cli(run_conf: &ArgConfig, p_state: &mut PState) {
 
 				curr_cmp = c;
 			},
-			// Opens a file as a source and adds it to the selected composition
-			["op", path] | ["open", path] => {
+			["gen", spec] => {
 				let curr_cmp = match &curr_cmp {
 					None => { eprintln!("Please select a composition first."); continue; },
 					Some(cmp) => cmp
 				};
 
-				let path = path.trim_start().trim_end();
-				
-				if path.is_empty() {
-					eprintln!("No path was given.");
-					continue;
+				match spec.parse::<GeneratorSrc>() {
+					Ok(src) => {
+						let id = curr_cmp.write().unwrap().push_src_default(src.into());
+						println!("Added the generator as source '{}'.", id);
+					},
+					Err(_) => eprintln!("Invalid generator")
 				}
+			},
+			["nest", inner_id] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
 
-				let path = match path.chars().next().unwrap() {
-					'+' => {
-						run_conf.audio_dir().join(path.strip_prefix("+").unwrap())
-					},
-					'~' => {
-						if cfg!(unix) {
-							let home_dir = env::var_os("HOME");
-							
-							let without_prefix = match path.strip_prefix("~/") {
-								Some(path) => path,
-								None => {
-									// $HOME + "" = $HOME
-									if path.len() == 1 {
-										""
-									} else {
-										eprintln!("Invalid path");
-										continue;
-									}
-								}
-							};
-
-							match home_dir {
-								Some(home_dir) => PathBuf::from(home_dir).join(without_prefix),
-								None => PathBuf::from(path),
-							}
-						} else {
-							PathBuf::from(path)
-						}
-					},
-					_ => {
-						PathBuf::from(path)
-					}
+				let outer_id = curr_cmp.read().unwrap().get_id().clone();
+				match cmp_reg.lock().unwrap().nest(&outer_id, inner_id, NESTED_SAMPLE_RATE, DEFAULT_BLOCK_SIZE) {
+					Ok(id) => println!("Added the composition as source '{}'.", id),
+					Err(NestError::NotFound) => eprintln!("No composition exists with this ID."),
+					Err(NestError::Cycle) => eprintln!("The composition can't be nested as it would end up inside of itself.")
+				}
+			},
+			// Opens a file as a source and adds it to the selected composition
+			["op", path] | ["open", path] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
 				};
-			
+
+				let path = match resolve_path(run_conf, path) {
+					Some(path) => path,
+					None => continue
+				};
+
 				if !path.exists() { eprintln!("File does not exist."); continue; }
 				
 				if path.is_dir() {
@@ -194,7 +583,8 @@ pub fn start_dbg_cli(run_conf: &ArgConfig, p_state: &mut PState) {
 						None => { eprintln!("Something went wrong while creating a queue."); continue; }
 					};
 
-					curr_cmp.write().unwrap().push_src_default(queue.into());
+					let id = curr_cmp.write().unwrap().push_src_default(queue.into());
+					println!("Added the queue as source '{}'.", id);
 
 					continue;
 				}
@@ -203,11 +593,92 @@ pub fn start_dbg_cli(run_conf: &ArgConfig, p_state: &mut PState) {
 				
 				log::debug!("Initializing the source");
 				
-				if let Some(src) = FormattedStreamSource::open_path(path) {
-					curr_cmp.write().unwrap().push_src_default(src.into());
-				} else {
-					eprintln!("Failed to create the source.");
-					// log::error!("Source initialization failed with error '{:?}'.", err);
+				match FormattedStreamSource::open_path(path) {
+					Ok(src) => {
+						let id = curr_cmp.write().unwrap().push_src_default(src.into());
+						println!("Added the file as source '{}'.", id);
+					},
+					Err(err) => eprintln!("Failed to create the source: {}.", err)
+				}
+			},
+			// Controls the playlist of a queue source
+			["q", _id, ref args @ ..] => {
+				let curr_cmp = match &curr_cmp {
+					None => { eprintln!("Please select a composition first."); continue; },
+					Some(cmp) => cmp
+				};
+
+				let id = match _id.parse::<TSrcID>() {
+					Ok(id) => id,
+					Err(_) => { eprintln!("Invalid source ID"); continue; }
+				};
+
+				// Opening files before locking the composition, as it may take a while
+				let new_src = match args {
+					["ins", _, path] => {
+						let Some(path) = resolve_path(run_conf, path) else { continue; };
+						match FormattedStreamSource::open_path(path) {
+							Ok(src) => Some(src),
+							Err(err) => { eprintln!("Failed to create the source: {}.", err); continue; }
+						}
+					},
+					_ => None
+				};
+
+				let mut cmp = curr_cmp.write().unwrap();
+				let queue = match cmp.find_src_mut(id).map(|s| &mut s.src) {
+					Some(Source::Queue(queue)) => queue,
+					Some(_) => { eprintln!("The source isn't a queue"); continue; },
+					None => { eprintln!("No source was found with the specified ID"); continue; }
+				};
+
+				let parse_idx = |idx: &str| idx.parse::<usize>().ok().filter(|idx| *idx < queue.len());
+
+				// Whether the queue may start playing again, in case it has ended
+				let mut is_restarted = false;
+
+				match *args {
+					[] => {
+						println!("Repeat: {}", queue.repeat());
+						println!("Crossfade: {}", queue.crossfade().map_or("off".to_owned(), |c| c.to_string()));
+						for (i, src) in queue.sources().iter().enumerate() {
+							let marker = if queue.current() == Some(i) { "▶" } else { " " };
+							println!(" {} {:>4} | {}", marker, i, src.kind());
+						}
+					},
+					["next"] => { queue.skip_next(); is_restarted = true; },
+					["prev"] => { queue.skip_previous(); is_restarted = true; },
+					["shuffle"] => queue.shuffle(),
+					["rpt", mode] => match mode.parse() {
+						Ok(mode) => queue.set_repeat(mode),
+						Err(_) => eprintln!("Invalid repeat mode, it's one of 'off', 'one' and 'all'")
+					},
+					["xf", "off"] => queue.set_crossfade(None),
+					["xf", spec] => match spec.parse::<Crossfade>() {
+						Ok(crossfade) => queue.set_crossfade(Some(crossfade)),
+						Err(_) => eprintln!("Invalid crossfade")
+					},
+					["jmp", idx] => match parse_idx(idx) {
+						Some(idx) => { queue.jump_to(idx); is_restarted = true; },
+						None => eprintln!("Invalid index")
+					},
+					["del", idx] => match parse_idx(idx) {
+						Some(idx) => { queue.remove(idx); },
+						None => eprintln!("Invalid index")
+					},
+					["mv", from, to] => match (parse_idx(from), parse_idx(to)) {
+						(Some(from), Some(to)) => { queue.move_item(from, to); },
+						_ => eprintln!("Invalid index")
+					},
+					["ins", idx, _] => match idx.parse::<usize>().ok().filter(|idx| *idx <= queue.len()) {
+						Some(idx) => { let _ = queue.insert(idx, new_src.unwrap().into()); is_restarted = true; },
+						None => eprintln!("Invalid index")
+					},
+					_ => println!("Invalid command")
+				}
+
+				if is_restarted {
+					cmp.forget_src_finished(id);
 				}
 			},
 			["go", _sec] => {
@@ -223,6 +694,27 @@ pub fn start_dbg_cli(run_conf: &ArgConfig, p_state: &mut PState) {
 					continue;
 				};
 			},
+			["ev"] => {
+				if let Some(stop) = stop_events.take() {
+					stop.store(true, Ordering::Relaxed);
+					continue;
+				}
+
+				let stop = Arc::new(AtomicBool::new(false));
+				let _stop = stop.clone();
+				let events = GLOBAL_EVENT_BUS.subscribe();
+
+				thread::spawn(move || {
+					// The receiver is dropped with the first event after stopping, which unsubscribes it
+					for event in events {
+						if _stop.load(Ordering::Relaxed) { return; }
+
+						println!("\r\x1b[38;5;250m[{}]\x1b[0m", event);
+					}
+				});
+
+				stop_events = Some(stop);
+			},
 			["t"] | ["time"] => {
 				let curr_cmp = match &curr_cmp {
 					None => { eprintln!("Please select a composition first."); continue; },
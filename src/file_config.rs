@@ -1,16 +1,21 @@
-use std::{collections::HashSet, fs::File, io::Read, net::{SocketAddr, ToSocketAddrs}, sync::{Arc, Mutex, RwLock}};
+use std::{collections::{HashMap, HashSet}, fmt::Display, fs::File, io::Read, net::{SocketAddr, ToSocketAddrs}, path::Path, sync::{Arc, Mutex, RwLock}};
 
-use rad_compositor::{adapter::AdapterHandle, cmp_reg::CompositionRegistry, composition::CompositionState, source::TFrameIdx};
-use rad_net_stream::{init_simple_http_adapter, init_udp_adapter};
+use rad_compositor::{adapter::AdapterHandle, channel_layout::ChannelLayout, cmp_reg::{CompositionRegistry, NestError}, composition::CompositionState, compositor::{is_valid_block_size, limiter::LimiterConfig, DEFAULT_BLOCK_SIZE, MAX_BLOCK_SIZE, MIN_BLOCK_SIZE}, effect::EffectChain, loudness::cache::GLOBAL_LOUDNESS_CACHE, resampler::ResampleQuality, source::{formatted::{decode_ahead::set_decode_ahead_ms, seek::{set_seek_precision, SeekPrecision}}, generator::GeneratorSrc, TFrameIdx}};
+use rad_net_stream::{init_simple_http_adapter, init_udp_adapter, MAX_UDP_BLOCK_SIZE};
 use serde::Deserialize;
 use toml::Table;
 
 const DEFAULT_REMOTE_ADDR: &str = "127.0.0.1:4600";
+/// The sample-rate nested compositions are read at when the composition nesting them isn't heard through any endpoint.
+const DEFAULT_NESTED_SAMPLE_RATE: TFrameIdx = 48000;
 
 #[derive(Deserialize)]
 struct FileConfig {
 	// TODO: Change this field's name.
 	api_addr: Option<String>,
+	loudness_cache: Option<String>,
+	decode_ahead_ms: Option<u32>,
+	seek: Option<String>,
 	composition: Vec<Composition>,
 	endpoints: Endpoints
 }
@@ -20,6 +25,13 @@ struct Composition {
 	id: String,
 	amp: f32,
 	pause: bool,
+	ramp_ms: Option<u32>,
+	layout: Option<String>,
+	effects: Option<Vec<String>>,
+	limiter: Option<String>,
+	loudness_target: Option<f32>,
+	nested: Option<Vec<String>>,
+	generators: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -34,12 +46,24 @@ struct OutputEndpoint {
 	ap: Table,
 	cast: String,
 	sample_rate: TFrameIdx,
-	channels: u8
+	channels: u8,
+	block_size: Option<usize>,
+	resampler: Option<String>
+}
+
+/// A mistake in the configuration file that is reported rather than panicked on, found before any endpoint is started.
+#[derive(Debug)]
+pub struct ConfigError(String);
+
+impl Display for ConfigError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.0)
+	}
 }
 
 pub struct PState  {
 	pub remote_addr: SocketAddr,
-	pub cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>,
+	pub cmp_reg: Arc<Mutex<CompositionRegistry>>,
 	pub adapters: Vec<AdapterHandle>
 }
 
@@ -48,16 +72,55 @@ fn create_corresponding_composition_state(conf: &Composition) -> CompositionStat
 		panic!("Composition ID can't be left empty.")
 	}
 
-	let mut res = CompositionState::new(conf.id.clone(), conf.amp);
+	let layout = match conf.layout {
+		Some(ref layout) => layout.parse::<ChannelLayout>()
+			.unwrap_or_else(|_| panic!("Invalid channel layout '{}' was chosen for composition '{}'.", layout, conf.id)),
+		None => ChannelLayout::Stereo
+	};
+
+	let mut res = CompositionState::new(conf.id.clone(), conf.amp, layout);
+
+	if let Some(ramp_ms) = conf.ramp_ms {
+		res.set_ramp_ms(ramp_ms);
+	}
+
+	if let Some(ref effects) = conf.effects {
+		let chain = EffectChain::parse(effects.iter().map(|e| e.as_str()))
+			.unwrap_or_else(|| panic!("Invalid effects {:?} were given to composition '{}'.", effects, conf.id));
+
+		res.set_effects(chain);
+	}
+
+	if let Some(ref limiter) = conf.limiter {
+		let limiter = limiter.parse::<LimiterConfig>()
+			.unwrap_or_else(|_| panic!("Invalid limiter '{}' was chosen for composition '{}'.", limiter, conf.id));
+
+		res.set_limiter(limiter);
+	}
+
+	if let Some(target) = conf.loudness_target {
+		if !target.is_finite() || 0.0 < target {
+			panic!("Invalid loudness target '{}' was chosen for composition '{}'.", target, conf.id);
+		}
+
+		res.set_loudness_target(Some(target));
+	}
+
+	for spec in conf.generators.iter().flatten() {
+		let src = spec.parse::<GeneratorSrc>()
+			.unwrap_or_else(|_| panic!("Invalid generator '{}' was given to composition '{}'.", spec, conf.id));
+
+		res.push_src_offset(src.into(), 0);
+	}
 
 	if conf.pause {
-		res.set_paused_since(res.start_time().clone());
+		res.set_paused_since(*res.start_time());
 	}
 
 	res
 }
 
-fn create_composition_registry<const BUF_SIZE: usize>(compositions: &Vec<Composition>) -> CompositionRegistry<BUF_SIZE> {
+fn create_composition_registry(compositions: &[Composition]) -> CompositionRegistry {
 	let mut ids = HashSet::new();
 	let mut reg = CompositionRegistry::new();
 
@@ -75,7 +138,107 @@ fn create_composition_registry<const BUF_SIZE: usize>(compositions: &Vec<Composi
 	reg
 }
 
-fn create_corresponding_output_endpoint(cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>, end_conf: &OutputEndpoint) -> AdapterHandle {	
+/// Finds the endpoint a composition is heard through, either directly or through the compositions it's nested in.
+fn hearing_endpoint<'a>(cmp_id: &str, compositions: &[Composition], endpoints: &'a [OutputEndpoint]) -> Option<&'a OutputEndpoint> {
+	let mut to_visit = vec![cmp_id];
+	let mut visited = HashSet::new();
+
+	while let Some(cmp_id) = to_visit.pop() {
+		// Nesting cycles are reported when nesting, here they're just not followed
+		if !visited.insert(cmp_id) { continue; }
+
+		if let Some(end_conf) = endpoints.iter().find(|e| e.cast == cmp_id) {
+			return Some(end_conf);
+		}
+
+		to_visit.extend(compositions.iter()
+			.filter(|c| c.nested.iter().flatten().any(|inner_id| inner_id == cmp_id))
+			.map(|c| c.id.as_str()));
+	}
+
+	None
+}
+
+/// Adds the nested compositions as sources, which is done after creating the endpoints so that the nested compositions are read with their resampling qualities.
+/// Each of them is read at the sample-rate and block size of the endpoint the composition nesting it is heard through,
+/// so that it isn't resampled there and shares its compositor with the endpoints reading it the same way.
+fn nest_compositions(cmp_reg: &Mutex<CompositionRegistry>, compositions: &[Composition], endpoints: &[OutputEndpoint]) {
+	let mut cmp_reg = cmp_reg.lock().unwrap();
+
+	for cmp_conf in compositions.iter() {
+		let (sample_rate, block_size) = hearing_endpoint(&cmp_conf.id, compositions, endpoints)
+			.map_or((DEFAULT_NESTED_SAMPLE_RATE, DEFAULT_BLOCK_SIZE), |end_conf| (end_conf.sample_rate, end_conf.block_size.unwrap_or(DEFAULT_BLOCK_SIZE)));
+
+		for inner_id in cmp_conf.nested.iter().flatten() {
+			match cmp_reg.nest(&cmp_conf.id, inner_id, sample_rate, block_size) {
+				Ok(_) => {},
+				Err(NestError::NotFound) => panic!("Composition '{}' nests composition '{}' which doesn't exist.", cmp_conf.id, inner_id),
+				Err(NestError::Cycle) => panic!("Composition '{}' can't nest composition '{}' as it would end up inside of itself.", cmp_conf.id, inner_id)
+			}
+		}
+	}
+}
+
+/// Checks the endpoints against each other and the compositions they cast, so that a faulty endpoint is reported before any of them is started.
+fn validate_endpoints(cmp_reg: &CompositionRegistry, endpoints: &[OutputEndpoint]) -> Result<(), ConfigError> {
+	for end_conf in endpoints {
+		let cmp = cmp_reg.find_composition(&end_conf.cast)
+			.ok_or_else(|| ConfigError(format!("Endpoint '{}' casts composition '{}' which doesn't exist.", end_conf.id, end_conf.cast)))?;
+
+		let cmp_channels = cmp.read().unwrap().get_channels();
+		if cmp_channels != end_conf.channels {
+			return Err(ConfigError(format!(
+				"Endpoint '{}' has {} channels while composition '{}' has {}, they must be the same.",
+				end_conf.id, end_conf.channels, end_conf.cast, cmp_channels
+			)));
+		}
+
+		let block_size = end_conf.block_size.unwrap_or(DEFAULT_BLOCK_SIZE);
+		if !is_valid_block_size(block_size) {
+			return Err(ConfigError(format!(
+				"Endpoint '{}' has a block size of {} samples while it has to be between {} and {}.",
+				end_conf.id, block_size, MIN_BLOCK_SIZE, MAX_BLOCK_SIZE
+			)));
+		}
+
+		if end_conf.adapter == "net-udp" && MAX_UDP_BLOCK_SIZE < block_size {
+			return Err(ConfigError(format!(
+				"Endpoint '{}' has a block size of {} samples while UDP datagrams only fit {}.",
+				end_conf.id, block_size, MAX_UDP_BLOCK_SIZE
+			)));
+		}
+	}
+
+	// The endpoints of a sample-rate share its compositors, and with that their resampling quality
+	let mut qualities: HashMap<TFrameIdx, (&str, ResampleQuality)> = HashMap::new();
+
+	for end_conf in endpoints {
+		if let Some(ref resampler) = end_conf.resampler {
+			let quality = resampler.parse::<ResampleQuality>()
+				.map_err(|_| ConfigError(format!("Endpoint '{}' has an invalid resampler '{}'.", end_conf.id, resampler)))?;
+
+			match qualities.get(&end_conf.sample_rate) {
+				Some((other_id, other)) if *other != quality => {
+					return Err(ConfigError(format!(
+						"Endpoints '{}' and '{}' have the same sample-rate but different resamplers ('{}' and '{}'), which they'd have to share.",
+						other_id, end_conf.id, other.name(), quality.name()
+					)));
+				},
+				_ => { qualities.insert(end_conf.sample_rate, (&end_conf.id, quality)); }
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn create_corresponding_output_endpoint(cmp_reg: Arc<Mutex<CompositionRegistry>>, end_conf: &OutputEndpoint) -> AdapterHandle {	
+	if let Some(quality) = end_conf.resampler.as_deref().and_then(|r| r.parse::<ResampleQuality>().ok()) {
+		cmp_reg.lock().unwrap().set_resample_quality(end_conf.sample_rate, quality);
+	}
+
+	let block_size = end_conf.block_size.unwrap_or(DEFAULT_BLOCK_SIZE);
+
 	match end_conf.adapter.as_str() {
 		"net-udp" => {
 			let adapter_args = &end_conf.ap;
@@ -84,19 +247,19 @@ fn create_corresponding_output_endpoint(cmp_reg: Arc<Mutex<CompositionRegistry<1
 				adapter_args.get("bind").expect("Filed 'ap:bind' can't be left empty.")
 				.as_str().expect("Field 'ap:bind' has to be a string.")
 				.to_socket_addrs().expect("Field 'ap:bind' has to be a proper address.")
-				.nth(0).expect("No addresses were found in 'ap:bind'.");
+				.next().expect("No addresses were found in 'ap:bind'.");
 
 			let dest_addr =
 				adapter_args.get("dest").expect("Filed 'ap:dest' can't be left empty.")
 				.as_str().expect("Field 'ap:dest' has to be a string.")
 				.to_socket_addrs().expect("Field 'ap:dest' has to be a proper address.")
-				.nth(0).expect("No addresses were found in 'ap:dest'.");
+				.next().expect("No addresses were found in 'ap:dest'.");
 
 			init_udp_adapter(
 				end_conf.id.clone(),
 				bind_addr,
 				dest_addr,
-				cmp_reg.lock().unwrap().get_active_buf(&end_conf.cast, end_conf.sample_rate).unwrap()
+				cmp_reg.lock().unwrap().get_cursor(&end_conf.cast, end_conf.sample_rate, block_size).unwrap()
 			)
 		},
 		"net-simple-http" => {
@@ -106,12 +269,13 @@ fn create_corresponding_output_endpoint(cmp_reg: Arc<Mutex<CompositionRegistry<1
 				adapter_args.get("bind").expect("Filed 'ap:bind' can't be left empty.")
 				.as_str().expect("Field 'ap:bind' has to be a string.")
 				.to_socket_addrs().expect("Field 'ap:bind' has to be a proper address.")
-				.nth(0).expect("No addresses were found in 'ap:bind'.");
+				.next().expect("No addresses were found in 'ap:bind'.");
 
 			init_simple_http_adapter(
 				end_conf.id.clone(),
 				end_conf.sample_rate,
 				end_conf.channels,
+				block_size,
 				bind_addr,
 				end_conf.cast.clone(),
 				cmp_reg.clone()
@@ -129,7 +293,7 @@ fn create_corresponding_output_endpoint(cmp_reg: Arc<Mutex<CompositionRegistry<1
 }
 
 // For now only output endpoints will be supported but support endpoints for receiving audio from devices like microphones on external devices may be implemented in the feature but isn't a planned feature yet.
-fn create_endpoints(cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>, endpoints_config: &Endpoints) -> Vec<AdapterHandle> {
+fn create_endpoints(cmp_reg: Arc<Mutex<CompositionRegistry>>, endpoints_config: &Endpoints) -> Vec<AdapterHandle> {
 	let mut adapters = Vec::with_capacity(endpoints_config.out.len());
 	let mut ids = HashSet::new();
 
@@ -144,7 +308,7 @@ fn create_endpoints(cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>, endpoints_co
 		}
 
 		adapters.push(
-			create_corresponding_output_endpoint(cmp_reg.clone(), &end_conf)
+			create_corresponding_output_endpoint(cmp_reg.clone(), end_conf)
 		);		
 	}
 
@@ -152,9 +316,10 @@ fn create_endpoints(cmp_reg: Arc<Mutex<CompositionRegistry<1024>>>, endpoints_co
 }
 
 /// Configures the program state according to the configuration file.
-/// Caution: This function with panic in case of encountering any errors while trying to read and set the program up according to it,
+/// Caution: This function with panic in case of encountering most errors while trying to read and set the program up according to it,
 /// as in any case the program is not intended to be ran in case of a faulty configuration file.
-pub fn init_with_file_config(path: &str) -> PState {
+/// The mistakes in the endpoints are returned instead, before anything is started.
+pub fn init_with_file_config(path: &str) -> Result<PState, ConfigError> {
 	log::debug!("Reading the configuration file at '{path}'.");
 	
 	let mut raw_config = String::new();
@@ -172,11 +337,31 @@ pub fn init_with_file_config(path: &str) -> PState {
 			Err(_) => panic!("Failed to parse '{path}'.")
 		};
 	
+	if let Some(ref cache_path) = config.loudness_cache {
+		if let Err(e) = GLOBAL_LOUDNESS_CACHE.lock().unwrap().open(Path::new(cache_path)) {
+			panic!("Failed to open the loudness cache at '{cache_path}' due to io error '{e}'.");
+		}
+	}
+
+	if let Some(ms) = config.decode_ahead_ms {
+		set_decode_ahead_ms(ms);
+	}
+
+	if let Some(ref seek) = config.seek {
+		let precision = seek.parse::<SeekPrecision>()
+			.unwrap_or_else(|_| panic!("Invalid seek precision '{}' was chosen in the configuration file.", seek));
+		set_seek_precision(precision);
+	}
+
 	let cmp_reg = Arc::new(Mutex::new(create_composition_registry(&config.composition)));
 
+	validate_endpoints(&cmp_reg.lock().unwrap(), &config.endpoints.out)?;
+
 	let out_adapters = create_endpoints(cmp_reg.clone(), &config.endpoints);
 
-	PState {
+	nest_compositions(&cmp_reg, &config.composition, &config.endpoints.out);
+
+	Ok(PState {
 		remote_addr: config.api_addr
 			.unwrap_or(DEFAULT_REMOTE_ADDR.into())
 			.parse()
@@ -184,5 +369,5 @@ pub fn init_with_file_config(path: &str) -> PState {
 		cmp_reg,
 		adapters: out_adapters,
 		
-	}
+	})
 }
\ No newline at end of file
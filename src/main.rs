@@ -19,7 +19,13 @@ async fn main() {
     }
 
     // Setting things up using the file configuration.
-    let mut state = init_with_file_config(arg_config.file_config_path.as_os_str().to_str().unwrap());
+    let mut state = match init_with_file_config(arg_config.file_config_path.as_os_str().to_str().unwrap()) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Faulty configuration file: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     if arg_config.dbg_cli {
         // Compact cli controller for debugging: